@@ -3,6 +3,17 @@ use sea_orm_migration::prelude::*;
 mod m20260205_000001_init;
 mod m20260205_000002_relations;
 mod m20260205_000003_add_credits;
+mod m20261016_000004_hashed_keys;
+mod m20261016_000005_user_tokens;
+mod m20261016_000006_key_rate_limit;
+mod m20261016_000007_credit_ledger;
+mod m20261016_000008_screen_result_usage;
+mod m20261016_000009_payments;
+mod m20261016_000010_admin_audit;
+mod m20261016_000011_store_images;
+mod m20261016_000012_screen_result_images;
+mod m20261016_000013_screen_result_turns;
+mod m20261016_000014_screen_result_prompt;
 
 pub struct Migrator;
 
@@ -13,6 +24,17 @@ impl MigratorTrait for Migrator {
       Box::new(m20260205_000001_init::Migration),
       Box::new(m20260205_000002_relations::Migration),
       Box::new(m20260205_000003_add_credits::Migration),
+      Box::new(m20261016_000004_hashed_keys::Migration),
+      Box::new(m20261016_000005_user_tokens::Migration),
+      Box::new(m20261016_000006_key_rate_limit::Migration),
+      Box::new(m20261016_000007_credit_ledger::Migration),
+      Box::new(m20261016_000008_screen_result_usage::Migration),
+      Box::new(m20261016_000009_payments::Migration),
+      Box::new(m20261016_000010_admin_audit::Migration),
+      Box::new(m20261016_000011_store_images::Migration),
+      Box::new(m20261016_000012_screen_result_images::Migration),
+      Box::new(m20261016_000013_screen_result_turns::Migration),
+      Box::new(m20261016_000014_screen_result_prompt::Migration),
     ]
   }
 }
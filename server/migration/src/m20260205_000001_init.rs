@@ -1,43 +1,356 @@
 use sea_orm_migration::prelude::*;
-use sea_orm_migration::sea_orm::ConnectionTrait;
+use sea_orm_migration::sea_orm::DatabaseBackend;
+
+use crate::m20260205_000002_relations::{foreign_key, FOREIGN_KEYS};
 
 #[derive(DeriveMigrationName)]
 pub struct Migration;
 
+#[derive(DeriveIden)]
+pub(crate) enum Links {
+  Table,
+  Id,
+  UserId,
+  Code,
+  Pin,
+  CTime,
+}
+
+#[derive(DeriveIden)]
+pub(crate) enum Keys {
+  Table,
+  Id,
+  UserId,
+  Name,
+  Key,
+  CTime,
+  ETime,
+}
+
+#[derive(DeriveIden)]
+pub(crate) enum Packages {
+  Table,
+  Id,
+  Name,
+  RateLimit,
+  CDate,
+  EDate,
+}
+
+#[derive(DeriveIden)]
+pub(crate) enum Payments {
+  Table,
+  Id,
+  TransactionId,
+  Currency,
+  Price,
+  CTime,
+}
+
+#[derive(DeriveIden)]
+pub(crate) enum Roles {
+  Table,
+  Id,
+  UserId,
+  Name,
+  Elevation,
+  CTime,
+  ETime,
+}
+
+#[derive(DeriveIden)]
+pub(crate) enum Settings {
+  Table,
+  Id,
+  UserId,
+  Name,
+  Config,
+  CTime,
+  ETime,
+}
+
+#[derive(DeriveIden)]
+pub(crate) enum ScreenResults {
+  Table,
+  Id,
+  UserId,
+  FileName,
+  Debug,
+  CTime,
+  ETime,
+  Status,
+}
+
+#[derive(DeriveIden)]
+pub(crate) enum Subscriptions {
+  Table,
+  Id,
+  UserId,
+  PaymentId,
+  PackageId,
+  ExpiresAt,
+  Credits,
+  CDate,
+}
+
+#[derive(DeriveIden)]
+pub(crate) enum Users {
+  Table,
+  Id,
+  Email,
+  Password,
+  FirstName,
+  LastName,
+  Confirmd,
+  CDate,
+  EDate,
+}
+
 #[async_trait::async_trait]
 impl MigrationTrait for Migration {
   async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
-    let sql = include_str!("../sql/001_init.sql");
-    let cleaned = strip_sql_comments(sql);
-    for statement in cleaned.split(';') {
-      let stmt = statement.trim();
-      if stmt.is_empty() {
-        continue;
-      }
-      eprintln!("Applying SQL:\n{stmt}\n");
-      if let Err(err) = manager.get_connection().execute_unprepared(stmt).await {
-        return Err(DbErr::Custom(format!(
-          "Migration failed for statement:\n{stmt}\nError: {err}"
-        )));
-      }
-    }
+    let backend = manager.get_database_backend();
+
+    create_table(
+      manager,
+      Table::create()
+        .table(Links::Table)
+        .col(uuid_pk(Links::Id))
+        .col(uuid_ref(Links::UserId))
+        .col(ColumnDef::new(Links::Code).string_len(64).null())
+        .col(ColumnDef::new(Links::Pin).char_len(4).null())
+        .col(created(Links::CTime))
+        .to_owned(),
+    )
+    .await?;
+
+    create_table(
+      manager,
+      Table::create()
+        .table(Keys::Table)
+        .col(uuid_pk(Keys::Id))
+        .col(uuid_ref(Keys::UserId))
+        .col(ColumnDef::new(Keys::Name).string_len(64).null())
+        .col(ColumnDef::new(Keys::Key).string_len(512).not_null())
+        .col(created(Keys::CTime))
+        .col(updated(Keys::ETime, backend))
+        .to_owned(),
+    )
+    .await?;
+
+    create_table(
+      manager,
+      Table::create()
+        .table(Packages::Table)
+        .col(
+          ColumnDef::new(Packages::Id)
+            .big_integer()
+            .not_null()
+            .auto_increment()
+            .primary_key(),
+        )
+        .col(ColumnDef::new(Packages::Name).string_len(100).not_null())
+        .col(
+          ColumnDef::new(Packages::RateLimit)
+            .integer()
+            .not_null()
+            .default(0),
+        )
+        .col(created(Packages::CDate))
+        .col(updated(Packages::EDate, backend))
+        .to_owned(),
+    )
+    .await?;
+
+    create_table(
+      manager,
+      Table::create()
+        .table(Payments::Table)
+        .col(uuid_pk(Payments::Id))
+        .col(
+          ColumnDef::new(Payments::TransactionId)
+            .string_len(128)
+            .null(),
+        )
+        .col(ColumnDef::new(Payments::Currency).string_len(8).null())
+        .col(ColumnDef::new(Payments::Price).decimal_len(10, 2).null())
+        .col(created(Payments::CTime))
+        .to_owned(),
+    )
+    .await?;
+
+    create_table(
+      manager,
+      Table::create()
+        .table(Roles::Table)
+        .col(uuid_pk(Roles::Id))
+        .col(uuid_ref(Roles::UserId))
+        .col(ColumnDef::new(Roles::Name).string_len(64).null())
+        .col(ColumnDef::new(Roles::Elevation).integer().null().default(0))
+        .col(created(Roles::CTime))
+        .col(updated(Roles::ETime, backend))
+        .to_owned(),
+    )
+    .await?;
+
+    create_table(
+      manager,
+      Table::create()
+        .table(Settings::Table)
+        .col(uuid_pk(Settings::Id))
+        .col(uuid_ref(Settings::UserId))
+        .col(ColumnDef::new(Settings::Name).string_len(64).null())
+        .col(ColumnDef::new(Settings::Config).json().null())
+        .col(created(Settings::CTime))
+        .col(updated(Settings::ETime, backend))
+        .to_owned(),
+    )
+    .await?;
+
+    create_table(
+      manager,
+      Table::create()
+        .table(ScreenResults::Table)
+        .col(uuid_pk(ScreenResults::Id))
+        .col(uuid_ref(ScreenResults::UserId))
+        .col(
+          ColumnDef::new(ScreenResults::FileName)
+            .string_len(255)
+            .not_null(),
+        )
+        .col(ColumnDef::new(ScreenResults::Debug).json().null())
+        .col(created(ScreenResults::CTime))
+        .col(updated(ScreenResults::ETime, backend))
+        // RUNNING, DONE or ERROR
+        .col(
+          ColumnDef::new(ScreenResults::Status)
+            .string_len(16)
+            .not_null()
+            .default("RUNNING"),
+        )
+        .to_owned(),
+    )
+    .await?;
+
+    create_table(
+      manager,
+      Table::create()
+        .table(Subscriptions::Table)
+        .col(
+          ColumnDef::new(Subscriptions::Id)
+            .big_integer()
+            .not_null()
+            .auto_increment()
+            .primary_key(),
+        )
+        .col(uuid_ref(Subscriptions::UserId))
+        .col(uuid_ref(Subscriptions::PaymentId))
+        .col(
+          ColumnDef::new(Subscriptions::PackageId)
+            .big_integer()
+            .null(),
+        )
+        .col(
+          ColumnDef::new(Subscriptions::ExpiresAt)
+            .timestamp_with_time_zone()
+            .null(),
+        )
+        .col(created(Subscriptions::CDate))
+        .to_owned(),
+    )
+    .await?;
+
+    create_table(
+      manager,
+      Table::create()
+        .table(Users::Table)
+        .col(uuid_pk(Users::Id))
+        .col(ColumnDef::new(Users::Email).string_len(190).not_null())
+        .col(ColumnDef::new(Users::Password).string_len(190).not_null())
+        .col(ColumnDef::new(Users::FirstName).string_len(100).null())
+        .col(ColumnDef::new(Users::LastName).string_len(100).null())
+        .col(
+          ColumnDef::new(Users::Confirmd)
+            .boolean()
+            .not_null()
+            .default(false),
+        )
+        .col(created(Users::CDate))
+        .col(updated(Users::EDate, backend))
+        .to_owned(),
+    )
+    .await?;
+
     Ok(())
   }
 
-  async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let tables = [
+      Subscriptions::Table.into_iden(),
+      ScreenResults::Table.into_iden(),
+      Settings::Table.into_iden(),
+      Roles::Table.into_iden(),
+      Keys::Table.into_iden(),
+      Links::Table.into_iden(),
+      Payments::Table.into_iden(),
+      Packages::Table.into_iden(),
+      Users::Table.into_iden(),
+    ];
+    for table in tables {
+      manager
+        .drop_table(Table::drop().table(table).if_exists().to_owned())
+        .await?;
+    }
     Ok(())
   }
 }
 
-fn strip_sql_comments(sql: &str) -> String {
-  let mut out = String::new();
-  for line in sql.lines() {
-    let trimmed = line.trim();
-    if trimmed.starts_with("--") || trimmed.is_empty() {
-      continue;
+/// SQLite cannot add constraints to an existing table, so there the foreign keys from
+/// `m20260205_000002_relations` are declared when the table is created.
+async fn create_table(
+  manager: &SchemaManager<'_>,
+  mut table: TableCreateStatement,
+) -> Result<(), DbErr> {
+  if manager.get_database_backend() == DatabaseBackend::Sqlite {
+    let name = match table.get_table_name() {
+      Some(TableRef::Table(iden)) => iden.to_string(),
+      _ => String::new(),
+    };
+    for (constraint, from_table, column, to_table) in FOREIGN_KEYS {
+      if from_table == name {
+        table.foreign_key(&mut foreign_key(constraint, from_table, column, to_table));
+      }
     }
-    out.push_str(line);
-    out.push('\n');
   }
-  out
+  manager.create_table(table.if_not_exists().to_owned()).await
+}
+
+fn uuid_pk(column: impl IntoIden) -> ColumnDef {
+  ColumnDef::new(column)
+    .char_len(36)
+    .not_null()
+    .primary_key()
+    .to_owned()
+}
+
+fn uuid_ref(column: impl IntoIden) -> ColumnDef {
+  ColumnDef::new(column).char_len(36).null().to_owned()
+}
+
+fn created(column: impl IntoIden) -> ColumnDef {
+  ColumnDef::new(column)
+    .timestamp_with_time_zone()
+    .null()
+    .default(Expr::current_timestamp())
+    .to_owned()
+}
+
+/// Only MySQL can refresh the column itself; elsewhere the entities set it in `before_save`,
+/// which bulk `update_many` calls skip.
+fn updated(column: impl IntoIden, backend: DatabaseBackend) -> ColumnDef {
+  let mut def = created(column);
+  if backend == DatabaseBackend::MySql {
+    def.extra("ON UPDATE CURRENT_TIMESTAMP");
+  }
+  def
 }
@@ -1,46 +1,119 @@
 use sea_orm_migration::prelude::*;
-use sea_orm_migration::sea_orm::ConnectionTrait;
+use sea_orm_migration::sea_orm::DatabaseBackend;
 
 #[derive(DeriveMigrationName)]
 pub struct Migration;
 
+/// (constraint, table, column, referenced table); the referenced column is always `id`.
+pub(crate) const FOREIGN_KEYS: [(&str, &str, &str, &str); 8] = [
+  ("fk_links_user_id", "links", "user_id", "users"),
+  ("fk_keys_user_id", "keys", "user_id", "users"),
+  ("fk_roles_user_id", "roles", "user_id", "users"),
+  (
+    "fk_screen_results_user_id",
+    "screen_results",
+    "user_id",
+    "users",
+  ),
+  ("fk_settings_user_id", "settings", "user_id", "users"),
+  (
+    "fk_subscriptions_user_id",
+    "subscriptions",
+    "user_id",
+    "users",
+  ),
+  (
+    "fk_subscriptions_package_id",
+    "subscriptions",
+    "package_id",
+    "packages",
+  ),
+  (
+    "fk_subscriptions_payment_id",
+    "subscriptions",
+    "payment_id",
+    "payments",
+  ),
+];
+
+// Indexes for faster lookups / phpMyAdmin relations view
+const INDEXES: [(&str, &str, &str); 8] = [
+  ("idx_links_user_id", "links", "user_id"),
+  ("idx_keys_user_id", "keys", "user_id"),
+  ("idx_roles_user_id", "roles", "user_id"),
+  ("idx_screen_results_user_id", "screen_results", "user_id"),
+  ("idx_settings_user_id", "settings", "user_id"),
+  ("idx_subscriptions_user_id", "subscriptions", "user_id"),
+  (
+    "idx_subscriptions_package_id",
+    "subscriptions",
+    "package_id",
+  ),
+  (
+    "idx_subscriptions_payment_id",
+    "subscriptions",
+    "payment_id",
+  ),
+];
+
+pub(crate) fn foreign_key(
+  name: &str,
+  table: &str,
+  column: &str,
+  to_table: &str,
+) -> ForeignKeyCreateStatement {
+  ForeignKey::create()
+    .name(name)
+    .from(Alias::new(table), Alias::new(column))
+    .to(Alias::new(to_table), Alias::new("id"))
+    .to_owned()
+}
+
 #[async_trait::async_trait]
 impl MigrationTrait for Migration {
   async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
-    let conn = manager.get_connection();
-    let statements = [
-      // Indexes for faster lookups / phpMyAdmin relations view
-      "ALTER TABLE links ADD INDEX idx_links_user_id (user_id)",
-      "ALTER TABLE `keys` ADD INDEX idx_keys_user_id (user_id)",
-      "ALTER TABLE roles ADD INDEX idx_roles_user_id (user_id)",
-      "ALTER TABLE screen_results ADD INDEX idx_screen_results_user_id (user_id)",
-      "ALTER TABLE settings ADD INDEX idx_settings_user_id (user_id)",
-      "ALTER TABLE subscriptions ADD INDEX idx_subscriptions_user_id (user_id)",
-      "ALTER TABLE subscriptions ADD INDEX idx_subscriptions_package_id (package_id)",
-      "ALTER TABLE subscriptions ADD INDEX idx_subscriptions_payment_id (payment_id)",
-      // Foreign keys
-      "ALTER TABLE links ADD CONSTRAINT fk_links_user_id FOREIGN KEY (user_id) REFERENCES users(id)",
-      "ALTER TABLE `keys` ADD CONSTRAINT fk_keys_user_id FOREIGN KEY (user_id) REFERENCES users(id)",
-      "ALTER TABLE roles ADD CONSTRAINT fk_roles_user_id FOREIGN KEY (user_id) REFERENCES users(id)",
-      "ALTER TABLE screen_results ADD CONSTRAINT fk_screen_results_user_id FOREIGN KEY (user_id) REFERENCES users(id)",
-      "ALTER TABLE settings ADD CONSTRAINT fk_settings_user_id FOREIGN KEY (user_id) REFERENCES users(id)",
-      "ALTER TABLE subscriptions ADD CONSTRAINT fk_subscriptions_user_id FOREIGN KEY (user_id) REFERENCES users(id)",
-      "ALTER TABLE subscriptions ADD CONSTRAINT fk_subscriptions_package_id FOREIGN KEY (package_id) REFERENCES packages(id)",
-      "ALTER TABLE subscriptions ADD CONSTRAINT fk_subscriptions_payment_id FOREIGN KEY (payment_id) REFERENCES payments(id)",
-    ];
-
-    for stmt in statements {
-      if let Err(err) = conn.execute_unprepared(stmt).await {
-        return Err(DbErr::Custom(format!(
-          "Migration failed for statement:\n{stmt}\nError: {err}"
-        )));
+    for (name, table, column) in INDEXES {
+      manager
+        .create_index(
+          Index::create()
+            .name(name)
+            .table(Alias::new(table))
+            .col(Alias::new(column))
+            .to_owned(),
+        )
+        .await?;
+    }
+
+    // SQLite gets these inline from `m20260205_000001_init`.
+    if manager.get_database_backend() != DatabaseBackend::Sqlite {
+      for (name, table, column, to_table) in FOREIGN_KEYS {
+        manager
+          .create_foreign_key(foreign_key(name, table, column, to_table))
+          .await?;
       }
     }
 
     Ok(())
   }
 
-  async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    if manager.get_database_backend() != DatabaseBackend::Sqlite {
+      for (name, table, _, _) in FOREIGN_KEYS {
+        manager
+          .drop_foreign_key(
+            ForeignKey::drop()
+              .name(name)
+              .table(Alias::new(table))
+              .to_owned(),
+          )
+          .await?;
+      }
+    }
+    for (name, table, _) in INDEXES {
+      manager
+        .drop_index(Index::drop().name(name).table(Alias::new(table)).to_owned())
+        .await?;
+    }
     Ok(())
   }
 }
@@ -1,5 +1,6 @@
 use sea_orm_migration::prelude::*;
-use sea_orm_migration::sea_orm::ConnectionTrait;
+
+use crate::m20260205_000001_init::Subscriptions;
 
 #[derive(DeriveMigrationName)]
 pub struct Migration;
@@ -7,24 +8,32 @@ pub struct Migration;
 #[async_trait::async_trait]
 impl MigrationTrait for Migration {
   async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
-    let conn = manager.get_connection();
-    let stmt = "ALTER TABLE subscriptions ADD COLUMN credits INT NOT NULL DEFAULT 0";
-    if let Err(err) = conn.execute_unprepared(stmt).await {
-      if is_duplicate_column(&err) {
-        return Ok(());
-      }
-      return Err(DbErr::Custom(format!(
-        "Migration failed for statement:\n{stmt}\nError: {err}"
-      )));
+    if manager.has_column("subscriptions", "credits").await? {
+      return Ok(());
     }
-    Ok(())
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Subscriptions::Table)
+          .add_column(
+            ColumnDef::new(Subscriptions::Credits)
+              .integer()
+              .not_null()
+              .default(0),
+          )
+          .to_owned(),
+      )
+      .await
   }
 
-  async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
-    Ok(())
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Subscriptions::Table)
+          .drop_column(Subscriptions::Credits)
+          .to_owned(),
+      )
+      .await
   }
 }
-
-fn is_duplicate_column(err: &DbErr) -> bool {
-  err.to_string().contains("1060")
-}
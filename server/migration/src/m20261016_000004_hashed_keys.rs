@@ -0,0 +1,156 @@
+use rand::RngCore;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::ConnectionTrait;
+use sha2::{Digest, Sha256};
+
+use crate::m20260205_000001_init::{Keys, Roles};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+enum ApiKeys {
+  Prefix,
+  Salt,
+  Scopes,
+  ExpiresAt,
+  LastUsedAt,
+  RevokedAt,
+}
+
+/// Keys issued before scopes existed keep what every user could do with them.
+const LEGACY_SCOPES: &str = "ingest,history";
+/// Keys of staff users (`roles.elevation` of at least `MODERATOR`) also keep the admin API.
+const LEGACY_STAFF_SCOPES: &str = "ingest,history,admin";
+/// `roles.elevation` of the seeded `mod` role, as in `admin::MODERATOR`.
+const MODERATOR: i32 = 50;
+const PREFIX_LEN: usize = 11;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // One column per statement; SQLite cannot add several in one ALTER TABLE.
+    let columns = [
+      ColumnDef::new(ApiKeys::Prefix)
+        .string_len(16)
+        .null()
+        .to_owned(),
+      ColumnDef::new(ApiKeys::Salt)
+        .string_len(64)
+        .null()
+        .to_owned(),
+      ColumnDef::new(ApiKeys::Scopes)
+        .string_len(128)
+        .null()
+        .to_owned(),
+      ColumnDef::new(ApiKeys::ExpiresAt)
+        .timestamp_with_time_zone()
+        .null()
+        .to_owned(),
+      ColumnDef::new(ApiKeys::LastUsedAt)
+        .timestamp_with_time_zone()
+        .null()
+        .to_owned(),
+      ColumnDef::new(ApiKeys::RevokedAt)
+        .timestamp_with_time_zone()
+        .null()
+        .to_owned(),
+    ];
+    for mut column in columns {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(Keys::Table)
+            .add_column(&mut column)
+            .to_owned(),
+        )
+        .await?;
+    }
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_keys_prefix")
+          .table(Keys::Table)
+          .col(ApiKeys::Prefix)
+          .to_owned(),
+      )
+      .await?;
+
+    // Hash the plaintext keys that are already stored.
+    let conn = manager.get_connection();
+    let backend = conn.get_database_backend();
+    let select = Query::select()
+      .columns([Keys::Id, Keys::Key])
+      .from(Keys::Table)
+      .and_where(Expr::col(ApiKeys::Salt).is_null())
+      .to_owned();
+    for row in conn.query_all(backend.build(&select)).await? {
+      let id: String = row.try_get("", "id")?;
+      let key: String = row.try_get("", "key")?;
+      let mut salt = [0u8; 16];
+      rand::thread_rng().fill_bytes(&mut salt);
+      let salt = hex::encode(salt);
+      let hash = hex::encode(
+        Sha256::new()
+          .chain_update(&salt)
+          .chain_update(&key)
+          .finalize(),
+      );
+      let prefix: String = key.chars().take(PREFIX_LEN).collect();
+      let update = Query::update()
+        .table(Keys::Table)
+        .values([
+          (Keys::Key.into_iden(), hash.into()),
+          (ApiKeys::Salt.into_iden(), salt.into()),
+          (ApiKeys::Prefix.into_iden(), prefix.into()),
+          (ApiKeys::Scopes.into_iden(), LEGACY_SCOPES.into()),
+        ])
+        .and_where(Expr::col(Keys::Id).eq(id))
+        .to_owned();
+      conn.execute(backend.build(&update)).await?;
+    }
+    let staff = Query::select()
+      .column(Roles::UserId)
+      .from(Roles::Table)
+      .and_where(Expr::col(Roles::Elevation).gte(MODERATOR))
+      .to_owned();
+    let update = Query::update()
+      .table(Keys::Table)
+      .value(ApiKeys::Scopes, LEGACY_STAFF_SCOPES)
+      .and_where(Expr::col(ApiKeys::Scopes).eq(LEGACY_SCOPES))
+      .and_where(Expr::col(Keys::UserId).in_subquery(staff))
+      .to_owned();
+    conn.execute(backend.build(&update)).await?;
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_index(
+        Index::drop()
+          .name("idx_keys_prefix")
+          .table(Keys::Table)
+          .to_owned(),
+      )
+      .await?;
+    let columns = [
+      ApiKeys::Prefix,
+      ApiKeys::Salt,
+      ApiKeys::Scopes,
+      ApiKeys::ExpiresAt,
+      ApiKeys::LastUsedAt,
+      ApiKeys::RevokedAt,
+    ];
+    for column in columns {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(Keys::Table)
+            .drop_column(column)
+            .to_owned(),
+        )
+        .await?;
+    }
+    Ok(())
+  }
+}
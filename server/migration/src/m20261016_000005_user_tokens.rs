@@ -0,0 +1,104 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20260205_000001_init::Users;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// One-time tokens mailed to users for email confirmation and password reset.
+#[derive(DeriveIden)]
+pub(crate) enum UserTokens {
+  Table,
+  Id,
+  UserId,
+  Kind,
+  TokenHash,
+  ExpiresAt,
+  UsedAt,
+  CTime,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let mut table = Table::create()
+      .table(UserTokens::Table)
+      .if_not_exists()
+      .col(
+        ColumnDef::new(UserTokens::Id)
+          .char_len(36)
+          .not_null()
+          .primary_key(),
+      )
+      .col(ColumnDef::new(UserTokens::UserId).char_len(36).not_null())
+      // CONFIRM or RESET
+      .col(ColumnDef::new(UserTokens::Kind).string_len(16).not_null())
+      .col(
+        ColumnDef::new(UserTokens::TokenHash)
+          .string_len(64)
+          .not_null(),
+      )
+      .col(
+        ColumnDef::new(UserTokens::ExpiresAt)
+          .timestamp_with_time_zone()
+          .not_null(),
+      )
+      .col(
+        ColumnDef::new(UserTokens::UsedAt)
+          .timestamp_with_time_zone()
+          .null(),
+      )
+      .col(
+        ColumnDef::new(UserTokens::CTime)
+          .timestamp_with_time_zone()
+          .null()
+          .default(Expr::current_timestamp()),
+      )
+      .to_owned();
+    let mut user_fk = ForeignKey::create()
+      .name("fk_user_tokens_user_id")
+      .from(UserTokens::Table, UserTokens::UserId)
+      .to(Users::Table, Users::Id)
+      .on_delete(ForeignKeyAction::Cascade)
+      .to_owned();
+    table.foreign_key(&mut user_fk);
+    manager.create_table(table).await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_user_tokens_token_hash")
+          .table(UserTokens::Table)
+          .col(UserTokens::TokenHash)
+          .unique()
+          .to_owned(),
+      )
+      .await?;
+
+    // Self-registration checks for the address first; this catches two sign-ups racing.
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_users_email")
+          .table(Users::Table)
+          .col(Users::Email)
+          .unique()
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_index(
+        Index::drop()
+          .name("idx_users_email")
+          .table(Users::Table)
+          .to_owned(),
+      )
+      .await?;
+    manager
+      .drop_table(Table::drop().table(UserTokens::Table).to_owned())
+      .await
+  }
+}
@@ -0,0 +1,39 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20260205_000001_init::Keys;
+
+#[derive(DeriveIden)]
+enum KeyLimits {
+  RateLimit,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    if manager.has_column("keys", "rate_limit").await? {
+      return Ok(());
+    }
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Keys::Table)
+          .add_column(ColumnDef::new(KeyLimits::RateLimit).integer().null())
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Keys::Table)
+          .drop_column(KeyLimits::RateLimit)
+          .to_owned(),
+      )
+      .await
+  }
+}
@@ -0,0 +1,108 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20260205_000001_init::{Subscriptions, Users};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Every change to `subscriptions.credits`, with the balance after it.
+#[derive(DeriveIden)]
+pub(crate) enum CreditLedger {
+  Table,
+  Id,
+  SubscriptionId,
+  UserId,
+  Kind,
+  Amount,
+  Balance,
+  ReservationId,
+  ScreenResultId,
+  Note,
+  CTime,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let mut table = Table::create()
+      .table(CreditLedger::Table)
+      .if_not_exists()
+      .col(
+        ColumnDef::new(CreditLedger::Id)
+          .big_integer()
+          .not_null()
+          .auto_increment()
+          .primary_key(),
+      )
+      .col(
+        ColumnDef::new(CreditLedger::SubscriptionId)
+          .big_integer()
+          .not_null(),
+      )
+      .col(ColumnDef::new(CreditLedger::UserId).char_len(36).null())
+      // GRANT, RESERVE, COMMIT, REFUND or ADJUST
+      .col(ColumnDef::new(CreditLedger::Kind).string_len(16).not_null())
+      .col(ColumnDef::new(CreditLedger::Amount).integer().not_null())
+      .col(ColumnDef::new(CreditLedger::Balance).integer().not_null())
+      .col(
+        ColumnDef::new(CreditLedger::ReservationId)
+          .big_integer()
+          .null(),
+      )
+      .col(
+        ColumnDef::new(CreditLedger::ScreenResultId)
+          .char_len(36)
+          .null(),
+      )
+      .col(ColumnDef::new(CreditLedger::Note).string_len(255).null())
+      .col(
+        ColumnDef::new(CreditLedger::CTime)
+          .timestamp_with_time_zone()
+          .null()
+          .default(Expr::current_timestamp()),
+      )
+      .to_owned();
+    let mut subscription_fk = ForeignKey::create()
+      .name("fk_credit_ledger_subscription_id")
+      .from(CreditLedger::Table, CreditLedger::SubscriptionId)
+      .to(Subscriptions::Table, Subscriptions::Id)
+      .on_delete(ForeignKeyAction::Cascade)
+      .to_owned();
+    let mut user_fk = ForeignKey::create()
+      .name("fk_credit_ledger_user_id")
+      .from(CreditLedger::Table, CreditLedger::UserId)
+      .to(Users::Table, Users::Id)
+      .on_delete(ForeignKeyAction::SetNull)
+      .to_owned();
+    table.foreign_key(&mut subscription_fk);
+    table.foreign_key(&mut user_fk);
+    manager.create_table(table).await?;
+
+    // A reservation is settled (committed or refunded) at most once.
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_credit_ledger_reservation_id")
+          .table(CreditLedger::Table)
+          .col(CreditLedger::ReservationId)
+          .unique()
+          .to_owned(),
+      )
+      .await?;
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_credit_ledger_subscription_id")
+          .table(CreditLedger::Table)
+          .col(CreditLedger::SubscriptionId)
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(CreditLedger::Table).to_owned())
+      .await
+  }
+}
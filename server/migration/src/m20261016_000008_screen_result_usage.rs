@@ -0,0 +1,70 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20260205_000001_init::ScreenResults;
+
+#[derive(DeriveIden)]
+enum Usage {
+  Model,
+  InputTokens,
+  OutputTokens,
+  Credits,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    if manager.has_column("screen_results", "input_tokens").await? {
+      return Ok(());
+    }
+    // One column per statement; SQLite cannot add several in one ALTER TABLE.
+    let columns = [
+      ColumnDef::new(Usage::Model)
+        .string_len(64)
+        .null()
+        .to_owned(),
+      ColumnDef::new(Usage::InputTokens)
+        .big_integer()
+        .null()
+        .to_owned(),
+      ColumnDef::new(Usage::OutputTokens)
+        .big_integer()
+        .null()
+        .to_owned(),
+      ColumnDef::new(Usage::Credits).integer().null().to_owned(),
+    ];
+    for mut column in columns {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(ScreenResults::Table)
+            .add_column(&mut column)
+            .to_owned(),
+        )
+        .await?;
+    }
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let columns = [
+      Usage::Model,
+      Usage::InputTokens,
+      Usage::OutputTokens,
+      Usage::Credits,
+    ];
+    for column in columns {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(ScreenResults::Table)
+            .drop_column(column)
+            .to_owned(),
+        )
+        .await?;
+    }
+    Ok(())
+  }
+}
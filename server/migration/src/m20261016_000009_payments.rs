@@ -0,0 +1,160 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20260205_000001_init::{Packages, Payments};
+
+#[derive(DeriveIden)]
+enum PackageTerms {
+  Credits,
+  DurationDays,
+  Price,
+  Currency,
+}
+
+#[derive(DeriveIden)]
+enum PaymentDetails {
+  UserId,
+  PackageId,
+  Provider,
+  Status,
+  RefundedAt,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    if manager.has_column("payments", "status").await? {
+      return Ok(());
+    }
+    // One column per statement; SQLite cannot add several in one ALTER TABLE.
+    let package_columns = [
+      ColumnDef::new(PackageTerms::Credits)
+        .integer()
+        .not_null()
+        .default(0)
+        .to_owned(),
+      ColumnDef::new(PackageTerms::DurationDays)
+        .integer()
+        .not_null()
+        .default(30)
+        .to_owned(),
+      ColumnDef::new(PackageTerms::Price)
+        .decimal_len(10, 2)
+        .null()
+        .to_owned(),
+      ColumnDef::new(PackageTerms::Currency)
+        .string_len(8)
+        .null()
+        .to_owned(),
+    ];
+    for mut column in package_columns {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(Packages::Table)
+            .add_column(&mut column)
+            .to_owned(),
+        )
+        .await?;
+    }
+
+    let payment_columns = [
+      ColumnDef::new(PaymentDetails::UserId)
+        .char_len(36)
+        .null()
+        .to_owned(),
+      ColumnDef::new(PaymentDetails::PackageId)
+        .big_integer()
+        .null()
+        .to_owned(),
+      ColumnDef::new(PaymentDetails::Provider)
+        .string_len(32)
+        .null()
+        .to_owned(),
+      ColumnDef::new(PaymentDetails::Status)
+        .string_len(16)
+        .not_null()
+        .default("PAID")
+        .to_owned(),
+      ColumnDef::new(PaymentDetails::RefundedAt)
+        .timestamp_with_time_zone()
+        .null()
+        .to_owned(),
+    ];
+    for mut column in payment_columns {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(Payments::Table)
+            .add_column(&mut column)
+            .to_owned(),
+        )
+        .await?;
+    }
+
+    // Webhooks are retried; the provider's transaction id makes them idempotent.
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_payments_transaction_id")
+          .table(Payments::Table)
+          .col(Payments::TransactionId)
+          .unique()
+          .to_owned(),
+      )
+      .await?;
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_payments_user_id")
+          .table(Payments::Table)
+          .col(PaymentDetails::UserId)
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    for name in ["idx_payments_transaction_id", "idx_payments_user_id"] {
+      manager
+        .drop_index(Index::drop().name(name).table(Payments::Table).to_owned())
+        .await?;
+    }
+    let payment_columns = [
+      PaymentDetails::UserId,
+      PaymentDetails::PackageId,
+      PaymentDetails::Provider,
+      PaymentDetails::Status,
+      PaymentDetails::RefundedAt,
+    ];
+    for column in payment_columns {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(Payments::Table)
+            .drop_column(column)
+            .to_owned(),
+        )
+        .await?;
+    }
+    let package_columns = [
+      PackageTerms::Credits,
+      PackageTerms::DurationDays,
+      PackageTerms::Price,
+      PackageTerms::Currency,
+    ];
+    for column in package_columns {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(Packages::Table)
+            .drop_column(column)
+            .to_owned(),
+        )
+        .await?;
+    }
+    Ok(())
+  }
+}
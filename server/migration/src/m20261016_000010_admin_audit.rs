@@ -0,0 +1,71 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20260205_000001_init::Users;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// One row per call to the `/admin` API.
+#[derive(DeriveIden)]
+enum AdminAudit {
+  Table,
+  Id,
+  ActorId,
+  Elevation,
+  Action,
+  Target,
+  Details,
+  CTime,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let mut table = Table::create()
+      .table(AdminAudit::Table)
+      .if_not_exists()
+      .col(
+        ColumnDef::new(AdminAudit::Id)
+          .big_integer()
+          .not_null()
+          .auto_increment()
+          .primary_key(),
+      )
+      .col(ColumnDef::new(AdminAudit::ActorId).char_len(36).null())
+      .col(ColumnDef::new(AdminAudit::Elevation).integer().not_null())
+      .col(ColumnDef::new(AdminAudit::Action).string_len(64).not_null())
+      .col(ColumnDef::new(AdminAudit::Target).string_len(64).null())
+      .col(ColumnDef::new(AdminAudit::Details).json().null())
+      .col(
+        ColumnDef::new(AdminAudit::CTime)
+          .timestamp_with_time_zone()
+          .null()
+          .default(Expr::current_timestamp()),
+      )
+      .to_owned();
+    let mut actor_fk = ForeignKey::create()
+      .name("fk_admin_audit_actor_id")
+      .from(AdminAudit::Table, AdminAudit::ActorId)
+      .to(Users::Table, Users::Id)
+      .on_delete(ForeignKeyAction::SetNull)
+      .to_owned();
+    table.foreign_key(&mut actor_fk);
+    manager.create_table(table).await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_admin_audit_actor_id")
+          .table(AdminAudit::Table)
+          .col(AdminAudit::ActorId)
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(AdminAudit::Table).to_owned())
+      .await
+  }
+}
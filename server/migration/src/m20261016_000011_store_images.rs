@@ -0,0 +1,45 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20260205_000001_init::Users;
+
+/// Per-user opt-out of keeping uploaded screenshots.
+#[derive(DeriveIden)]
+enum UserImages {
+  StoreImages,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    if manager.has_column("users", "store_images").await? {
+      return Ok(());
+    }
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Users::Table)
+          .add_column(
+            ColumnDef::new(UserImages::StoreImages)
+              .boolean()
+              .not_null()
+              .default(true),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Users::Table)
+          .drop_column(UserImages::StoreImages)
+          .to_owned(),
+      )
+      .await
+  }
+}
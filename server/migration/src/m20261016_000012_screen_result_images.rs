@@ -0,0 +1,69 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20260205_000001_init::ScreenResults;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Images after the first of a multi-image capture; the first stays in
+/// `screen_results.file_name`.
+#[derive(DeriveIden)]
+enum ScreenResultImages {
+  Table,
+  Id,
+  ScreenResultId,
+  Position,
+  FileName,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let mut table = Table::create()
+      .table(ScreenResultImages::Table)
+      .if_not_exists()
+      .col(
+        ColumnDef::new(ScreenResultImages::Id)
+          .big_integer()
+          .not_null()
+          .auto_increment()
+          .primary_key(),
+      )
+      .col(
+        ColumnDef::new(ScreenResultImages::ScreenResultId)
+          .char_len(36)
+          .not_null(),
+      )
+      .col(ColumnDef::new(ScreenResultImages::Position).integer().not_null())
+      .col(
+        ColumnDef::new(ScreenResultImages::FileName)
+          .string_len(255)
+          .not_null(),
+      )
+      .to_owned();
+    let mut screen_result_fk = ForeignKey::create()
+      .name("fk_screen_result_images_screen_result_id")
+      .from(ScreenResultImages::Table, ScreenResultImages::ScreenResultId)
+      .to(ScreenResults::Table, ScreenResults::Id)
+      .on_delete(ForeignKeyAction::Cascade)
+      .to_owned();
+    table.foreign_key(&mut screen_result_fk);
+    manager.create_table(table).await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_screen_result_images_screen_result_id")
+          .table(ScreenResultImages::Table)
+          .col(ScreenResultImages::ScreenResultId)
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(ScreenResultImages::Table).to_owned())
+      .await
+  }
+}
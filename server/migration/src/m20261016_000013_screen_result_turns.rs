@@ -0,0 +1,86 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20260205_000001_init::ScreenResults;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Follow-up questions on a screen result, each with the answer streamed back for it.
+#[derive(DeriveIden)]
+enum ScreenResultTurns {
+  Table,
+  Id,
+  ScreenResultId,
+  Position,
+  Question,
+  Answer,
+  Status,
+  Model,
+  InputTokens,
+  OutputTokens,
+  Credits,
+  CTime,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let mut table = Table::create()
+      .table(ScreenResultTurns::Table)
+      .if_not_exists()
+      .col(
+        ColumnDef::new(ScreenResultTurns::Id)
+          .big_integer()
+          .not_null()
+          .auto_increment()
+          .primary_key(),
+      )
+      .col(
+        ColumnDef::new(ScreenResultTurns::ScreenResultId)
+          .char_len(36)
+          .not_null(),
+      )
+      .col(ColumnDef::new(ScreenResultTurns::Position).integer().not_null())
+      .col(ColumnDef::new(ScreenResultTurns::Question).text().not_null())
+      .col(ColumnDef::new(ScreenResultTurns::Answer).text().null())
+      // RUNNING, DONE or ERROR, like `screen_results.status`
+      .col(ColumnDef::new(ScreenResultTurns::Status).string_len(16).not_null())
+      .col(ColumnDef::new(ScreenResultTurns::Model).string_len(64).null())
+      .col(ColumnDef::new(ScreenResultTurns::InputTokens).big_integer().null())
+      .col(ColumnDef::new(ScreenResultTurns::OutputTokens).big_integer().null())
+      .col(ColumnDef::new(ScreenResultTurns::Credits).integer().null())
+      .col(
+        ColumnDef::new(ScreenResultTurns::CTime)
+          .timestamp_with_time_zone()
+          .null()
+          .default(Expr::current_timestamp()),
+      )
+      .to_owned();
+    let mut screen_result_fk = ForeignKey::create()
+      .name("fk_screen_result_turns_screen_result_id")
+      .from(ScreenResultTurns::Table, ScreenResultTurns::ScreenResultId)
+      .to(ScreenResults::Table, ScreenResults::Id)
+      .on_delete(ForeignKeyAction::Cascade)
+      .to_owned();
+    table.foreign_key(&mut screen_result_fk);
+    manager.create_table(table).await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_screen_result_turns_position")
+          .table(ScreenResultTurns::Table)
+          .col(ScreenResultTurns::ScreenResultId)
+          .col(ScreenResultTurns::Position)
+          .unique()
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(ScreenResultTurns::Table).to_owned())
+      .await
+  }
+}
@@ -0,0 +1,39 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20260205_000001_init::ScreenResults;
+
+#[derive(DeriveIden)]
+enum Prompt {
+  Prompt,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    if manager.has_column("screen_results", "prompt").await? {
+      return Ok(());
+    }
+    manager
+      .alter_table(
+        Table::alter()
+          .table(ScreenResults::Table)
+          .add_column(ColumnDef::new(Prompt::Prompt).text().null())
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(ScreenResults::Table)
+          .drop_column(Prompt::Prompt)
+          .to_owned(),
+      )
+      .await
+  }
+}
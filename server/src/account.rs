@@ -0,0 +1,108 @@
+use axum::{
+  body::Bytes,
+  extract::State,
+  http::{HeaderMap, StatusCode},
+  Json,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::{ActiveModelTrait, ColumnTrait, Condition, EntityTrait, Set};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::UserInfo;
+use crate::entity::{screen_results, users};
+use crate::keys::{authenticate, ApiKey};
+use crate::rate_limit::RateWindow;
+use crate::{
+  active_subscription, image_store, internal_error, parse_json_body, unauthorized, AppState,
+  ErrorResponse,
+};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+#[derive(Serialize)]
+pub struct MeResponse {
+  user: UserInfo,
+  /// Name of the active package; `None` without an active subscription.
+  package: Option<String>,
+  credits: i64,
+  expires_at: Option<DateTime<Utc>>,
+  /// `None` when the package has no rate limit.
+  rate_limit: Option<RateWindow>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateMeRequest {
+  store_images: Option<bool>,
+}
+
+/// GET /me: who the API key belongs to and what it may still spend.
+pub async fn me(
+  State(state): State<AppState>,
+  headers: HeaderMap,
+) -> Result<Json<MeResponse>, ApiError> {
+  let key = authenticate(&state.db, &headers).await?;
+  let user = find_user(&state, &key.user_id).await?;
+  me_response(&state, &key, user).await
+}
+
+/// PATCH /me: changes the caller's preferences. Turning `store_images` off also deletes
+/// the screenshots already stored.
+pub async fn update_me(
+  State(state): State<AppState>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Result<Json<MeResponse>, ApiError> {
+  let key = authenticate(&state.db, &headers).await?;
+  let request: UpdateMeRequest = parse_json_body(&body)?;
+  let mut user = find_user(&state, &key.user_id).await?;
+  if let Some(store_images) = request.store_images {
+    if store_images != user.store_images {
+      let mut active: users::ActiveModel = user.into();
+      active.store_images = Set(store_images);
+      user = active
+        .update(&state.db)
+        .await
+        .map_err(internal_error("DB error"))?;
+    }
+    if !store_images {
+      image_store::purge(
+        &state.db,
+        state.images.as_ref(),
+        Condition::all().add(screen_results::Column::UserId.eq(user.id.as_str())),
+      )
+      .await
+      .map_err(internal_error("Image purge failed"))?;
+    }
+  }
+  me_response(&state, &key, user).await
+}
+
+async fn find_user(state: &AppState, user_id: &str) -> Result<users::Model, ApiError> {
+  users::Entity::find_by_id(user_id)
+    .one(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?
+    .ok_or_else(|| unauthorized("Invalid API key", Some(100)))
+}
+
+async fn me_response(
+  state: &AppState,
+  key: &ApiKey,
+  user: users::Model,
+) -> Result<Json<MeResponse>, ApiError> {
+  let subscription = active_subscription(&state.db, &user.id).await?;
+  let rate_limit = subscription
+    .as_ref()
+    .and_then(|subscription| state.rate_limiter.window(key, subscription.rate_limit));
+  Ok(Json(MeResponse {
+    user: UserInfo::from(user),
+    package: subscription
+      .as_ref()
+      .and_then(|subscription| subscription.package.clone()),
+    credits: subscription
+      .as_ref()
+      .map_or(0, |subscription| subscription.credits),
+    expires_at: subscription.and_then(|subscription| subscription.expires_at),
+    rate_limit,
+  }))
+}
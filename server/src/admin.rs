@@ -0,0 +1,577 @@
+use axum::{
+  body::Bytes,
+  extract::{Path, Query, Request, State},
+  http::StatusCode,
+  middleware::{self, Next},
+  response::Response,
+  routing::{delete, get, post, put},
+  Extension, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+  ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait,
+  QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::entity::{admin_audit, keys, packages, roles, screen_results, subscriptions, users};
+use crate::keys::{require_scope, Scope};
+use crate::{
+  active_subscription, active_subscriptions, bad_request, credits, error_response, forbidden,
+  internal_error, parse_json_body, AppState, ErrorResponse,
+};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+/// `roles.elevation` of the seeded `mod` role; enough to look around and revoke keys.
+pub const MODERATOR: i32 = 50;
+/// `roles.elevation` of the seeded `admin` role; needed to change credits and packages.
+pub const ADMIN: i32 = 100;
+const DEFAULT_LIMIT: u64 = 50;
+const MAX_LIMIT: u64 = 200;
+
+/// The caller of an `/admin` endpoint, resolved by `resolve_staff`.
+#[derive(Clone)]
+pub struct Staff {
+  user_id: String,
+  elevation: i32,
+}
+
+impl Staff {
+  fn require(&self, elevation: i32) -> Result<(), ApiError> {
+    if self.elevation < elevation {
+      return Err(forbidden(
+        &format!(
+          "Requires elevation {elevation}, you have {}",
+          self.elevation
+        ),
+        Some(113),
+      ));
+    }
+    Ok(())
+  }
+}
+
+pub fn router(state: AppState) -> Router<AppState> {
+  Router::new()
+    .route("/users", get(list_users))
+    .route("/users/:id/credits", post(adjust_credits))
+    .route("/users/:id/package", put(change_package))
+    .route("/keys/:id", delete(revoke_key))
+    .route("/screen_results", get(list_screen_results))
+    .route("/screen_results/:id", get(get_screen_result))
+    .route("/audit", get(list_audit))
+    .route_layer(middleware::from_fn_with_state(state, resolve_staff))
+}
+
+/// Lets through keys with the `admin` scope whose user has at least `MODERATOR` elevation.
+async fn resolve_staff(
+  State(state): State<AppState>,
+  mut request: Request,
+  next: Next,
+) -> Result<Response, ApiError> {
+  let key = require_scope(&state.db, request.headers(), Scope::Admin).await?;
+  let elevation = elevation(&state.db, &key.user_id).await?;
+  if elevation < MODERATOR {
+    return Err(forbidden("Admin API requires a staff role", Some(113)));
+  }
+  request.extensions_mut().insert(Staff {
+    user_id: key.user_id,
+    elevation,
+  });
+  Ok(next.run(request).await)
+}
+
+/// The highest `roles.elevation` of the user, 0 without a role.
+async fn elevation<C: ConnectionTrait>(db: &C, user_id: &str) -> Result<i32, ApiError> {
+  let roles = roles::Entity::find()
+    .filter(roles::Column::UserId.eq(user_id))
+    .all(db)
+    .await
+    .map_err(internal_error("DB error"))?;
+  Ok(
+    roles
+      .iter()
+      .filter_map(|role| role.elevation)
+      .max()
+      .unwrap_or(0),
+  )
+}
+
+async fn audit<C: ConnectionTrait>(
+  db: &C,
+  staff: &Staff,
+  action: &str,
+  target: Option<&str>,
+  details: serde_json::Value,
+) -> Result<(), ApiError> {
+  admin_audit::ActiveModel {
+    actor_id: Set(Some(staff.user_id.clone())),
+    elevation: Set(staff.elevation),
+    action: Set(action.to_string()),
+    target: Set(target.map(str::to_string)),
+    details: Set(Some(details)),
+    ..Default::default()
+  }
+  .insert(db)
+  .await
+  .map_err(internal_error("DB error"))?;
+  Ok(())
+}
+
+fn page(limit: Option<u64>, offset: Option<u64>) -> (u64, u64) {
+  (
+    limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+    offset.unwrap_or(0),
+  )
+}
+
+async fn find_user(db: &DatabaseConnection, id: &str) -> Result<users::Model, ApiError> {
+  users::Entity::find_by_id(id)
+    .one(db)
+    .await
+    .map_err(internal_error("DB error"))?
+    .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "User not found", None))
+}
+
+#[derive(Deserialize)]
+struct UsersQuery {
+  /// Part of the email address.
+  search: Option<String>,
+  limit: Option<u64>,
+  offset: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct AdminUser {
+  id: String,
+  email: String,
+  first_name: Option<String>,
+  last_name: Option<String>,
+  confirmed: bool,
+  role: Option<String>,
+  elevation: i32,
+  /// `None` without an active subscription.
+  package: Option<String>,
+  credits: Option<i64>,
+  expires_at: Option<DateTime<Utc>>,
+  created_at: Option<DateTime<Utc>>,
+}
+
+/// GET /admin/users
+async fn list_users(
+  State(state): State<AppState>,
+  Extension(staff): Extension<Staff>,
+  Query(query): Query<UsersQuery>,
+) -> Result<Json<Vec<AdminUser>>, ApiError> {
+  staff.require(MODERATOR)?;
+  let (limit, offset) = page(query.limit, query.offset);
+  let mut select = users::Entity::find().order_by_asc(users::Column::Email);
+  if let Some(search) = query.search.as_deref().filter(|search| !search.is_empty()) {
+    select = select.filter(users::Column::Email.contains(search));
+  }
+  let found = select
+    .limit(limit)
+    .offset(offset)
+    .all(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?;
+  let ids: Vec<String> = found.iter().map(|user| user.id.clone()).collect();
+  let roles = roles::Entity::find()
+    .filter(roles::Column::UserId.is_in(ids.iter().cloned()))
+    .all(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?;
+  let mut active = active_subscriptions(&state.db, &ids).await?;
+
+  let mut listed = Vec::with_capacity(found.len());
+  for user in found {
+    let role = roles
+      .iter()
+      .filter(|role| role.user_id.as_deref() == Some(user.id.as_str()))
+      .max_by_key(|role| role.elevation.unwrap_or(0));
+    let subscription = active.remove(&user.id);
+    listed.push(AdminUser {
+      role: role.and_then(|role| role.name.clone()),
+      elevation: role.and_then(|role| role.elevation).unwrap_or(0),
+      package: subscription.as_ref().and_then(|sub| sub.package.clone()),
+      credits: subscription.as_ref().map(|sub| sub.credits),
+      expires_at: subscription.and_then(|sub| sub.expires_at),
+      id: user.id,
+      email: user.email,
+      first_name: user.first_name,
+      last_name: user.last_name,
+      confirmed: user.confirmd,
+      created_at: user.c_date,
+    });
+  }
+  audit(
+    &state.db,
+    &staff,
+    "users.list",
+    None,
+    json!({ "search": query.search, "limit": limit, "offset": offset }),
+  )
+  .await?;
+  Ok(Json(listed))
+}
+
+#[derive(Deserialize)]
+struct AdjustCreditsRequest {
+  /// Credits to add; negative to take them away.
+  amount: i32,
+  note: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CreditsResponse {
+  subscription_id: i64,
+  credits: i32,
+}
+
+/// POST /admin/users/:id/credits: adds to or takes from the active subscription.
+async fn adjust_credits(
+  State(state): State<AppState>,
+  Extension(staff): Extension<Staff>,
+  Path(user_id): Path<String>,
+  body: Bytes,
+) -> Result<Json<CreditsResponse>, ApiError> {
+  staff.require(ADMIN)?;
+  let request: AdjustCreditsRequest = parse_json_body(&body)?;
+  if request.amount == 0 {
+    return Err(bad_request("`amount` must not be zero"));
+  }
+  find_user(&state.db, &user_id).await?;
+  let subscription = active_subscription(&state.db, &user_id)
+    .await?
+    .ok_or_else(|| bad_request("User has no active subscription"))?;
+
+  let txn = state.db.begin().await.map_err(internal_error("DB error"))?;
+  let entry = credits::adjust(
+    &txn,
+    subscription.id,
+    Some(&user_id),
+    credits::ADJUST,
+    request.amount,
+    Some(request.note.as_deref().unwrap_or("admin adjustment")),
+  )
+  .await
+  .map_err(|err| match err {
+    DbErr::RecordNotUpdated => bad_request("The subscription has fewer credits than that"),
+    err => internal_error("DB error")(err),
+  })?;
+  audit(
+    &txn,
+    &staff,
+    "credits.adjust",
+    Some(&user_id),
+    json!({
+      "subscription_id": subscription.id,
+      "amount": request.amount,
+      "balance": entry.balance,
+      "note": request.note,
+    }),
+  )
+  .await?;
+  txn.commit().await.map_err(internal_error("DB error"))?;
+  Ok(Json(CreditsResponse {
+    subscription_id: subscription.id,
+    credits: entry.balance,
+  }))
+}
+
+#[derive(Deserialize)]
+struct ChangePackageRequest {
+  /// Package name, e.g. `Pro`.
+  package: String,
+  /// Defaults to the current expiry, or the package's duration for a new subscription.
+  expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct SubscriptionResponse {
+  subscription_id: i64,
+  package: String,
+  credits: i32,
+  expires_at: Option<DateTime<Utc>>,
+}
+
+/// PUT /admin/users/:id/package: moves the active subscription to another package, or
+/// starts one. Credits are left alone; grant them separately.
+async fn change_package(
+  State(state): State<AppState>,
+  Extension(staff): Extension<Staff>,
+  Path(user_id): Path<String>,
+  body: Bytes,
+) -> Result<Json<SubscriptionResponse>, ApiError> {
+  staff.require(ADMIN)?;
+  let request: ChangePackageRequest = parse_json_body(&body)?;
+  find_user(&state.db, &user_id).await?;
+  let package = packages::Entity::find()
+    .filter(packages::Column::Name.eq(request.package.trim()))
+    .one(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?
+    .ok_or_else(|| bad_request("Unknown package"))?;
+  let current = active_subscription(&state.db, &user_id).await?;
+
+  let txn = state.db.begin().await.map_err(internal_error("DB error"))?;
+  let previous = current.as_ref().and_then(|sub| sub.package.clone());
+  let saved = match current {
+    Some(current) => {
+      let mut active: subscriptions::ActiveModel = subscriptions::Entity::find_by_id(current.id)
+        .one(&txn)
+        .await
+        .map_err(internal_error("DB error"))?
+        .ok_or_else(|| error_response(StatusCode::CONFLICT, "Subscription changed", None))?
+        .into();
+      active.package_id = Set(Some(package.id));
+      if let Some(expires_at) = request.expires_at {
+        active.expires_at = Set(Some(expires_at));
+      }
+      active.update(&txn).await
+    }
+    None => {
+      let expires_at = request
+        .expires_at
+        .unwrap_or_else(|| Utc::now() + chrono::Duration::days(i64::from(package.duration_days)));
+      subscriptions::ActiveModel {
+        user_id: Set(Some(user_id.clone())),
+        package_id: Set(Some(package.id)),
+        expires_at: Set(Some(expires_at)),
+        credits: Set(0),
+        ..Default::default()
+      }
+      .insert(&txn)
+      .await
+    }
+  }
+  .map_err(internal_error("DB error"))?;
+  audit(
+    &txn,
+    &staff,
+    "package.change",
+    Some(&user_id),
+    json!({
+      "subscription_id": saved.id,
+      "from": previous,
+      "to": package.name,
+      "expires_at": saved.expires_at,
+    }),
+  )
+  .await?;
+  txn.commit().await.map_err(internal_error("DB error"))?;
+  Ok(Json(SubscriptionResponse {
+    subscription_id: saved.id,
+    package: package.name,
+    credits: saved.credits,
+    expires_at: saved.expires_at,
+  }))
+}
+
+/// DELETE /admin/keys/:id: revokes anyone's key, except those of higher-ranked staff.
+async fn revoke_key(
+  State(state): State<AppState>,
+  Extension(staff): Extension<Staff>,
+  Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+  staff.require(MODERATOR)?;
+  let key = keys::Entity::find_by_id(id)
+    .one(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?
+    .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "API key not found", None))?;
+  if let Some(owner) = key.user_id.as_deref() {
+    if elevation(&state.db, owner).await? > staff.elevation {
+      return Err(forbidden("Cannot revoke keys of a higher role", Some(113)));
+    }
+  }
+
+  let txn = state.db.begin().await.map_err(internal_error("DB error"))?;
+  let (key_id, owner) = (key.id.clone(), key.user_id.clone());
+  let already_revoked = key.revoked_at.is_some();
+  if !already_revoked {
+    let mut active: keys::ActiveModel = key.into();
+    active.revoked_at = Set(Some(Utc::now()));
+    active
+      .update(&txn)
+      .await
+      .map_err(internal_error("DB error"))?;
+  }
+  audit(
+    &txn,
+    &staff,
+    "keys.revoke",
+    Some(&key_id),
+    json!({ "user_id": owner, "already_revoked": already_revoked }),
+  )
+  .await?;
+  txn.commit().await.map_err(internal_error("DB error"))?;
+  Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct ScreenResultsQuery {
+  user_id: Option<String>,
+  status: Option<String>,
+  limit: Option<u64>,
+  offset: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct AdminScreenResult {
+  id: String,
+  user_id: Option<String>,
+  file_name: String,
+  status: String,
+  model: Option<String>,
+  input_tokens: Option<i64>,
+  output_tokens: Option<i64>,
+  credits: Option<i32>,
+  created_at: Option<DateTime<Utc>>,
+  /// Only returned by GET /admin/screen_results/:id.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  debug: Option<serde_json::Value>,
+}
+
+impl From<screen_results::Model> for AdminScreenResult {
+  fn from(model: screen_results::Model) -> Self {
+    Self {
+      id: model.id,
+      user_id: model.user_id,
+      file_name: model.file_name,
+      status: model.status,
+      model: model.model,
+      input_tokens: model.input_tokens,
+      output_tokens: model.output_tokens,
+      credits: model.credits,
+      created_at: model.c_time,
+      debug: None,
+    }
+  }
+}
+
+/// GET /admin/screen_results: newest first.
+async fn list_screen_results(
+  State(state): State<AppState>,
+  Extension(staff): Extension<Staff>,
+  Query(query): Query<ScreenResultsQuery>,
+) -> Result<Json<Vec<AdminScreenResult>>, ApiError> {
+  staff.require(MODERATOR)?;
+  let (limit, offset) = page(query.limit, query.offset);
+  let mut select = screen_results::Entity::find().order_by_desc(screen_results::Column::CTime);
+  if let Some(user_id) = query.user_id.as_deref() {
+    select = select.filter(screen_results::Column::UserId.eq(user_id));
+  }
+  if let Some(status) = query.status.as_deref() {
+    select = select.filter(screen_results::Column::Status.eq(status.to_ascii_uppercase()));
+  }
+  let found = select
+    .limit(limit)
+    .offset(offset)
+    .all(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?;
+  audit(
+    &state.db,
+    &staff,
+    "screen_results.list",
+    query.user_id.as_deref(),
+    json!({ "status": query.status, "limit": limit, "offset": offset }),
+  )
+  .await?;
+  Ok(Json(
+    found.into_iter().map(AdminScreenResult::from).collect(),
+  ))
+}
+
+/// GET /admin/screen_results/:id: one result with its debug payload.
+async fn get_screen_result(
+  State(state): State<AppState>,
+  Extension(staff): Extension<Staff>,
+  Path(id): Path<String>,
+) -> Result<Json<AdminScreenResult>, ApiError> {
+  staff.require(MODERATOR)?;
+  let found = screen_results::Entity::find_by_id(id)
+    .one(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?
+    .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "Screen result not found", None))?;
+  audit(
+    &state.db,
+    &staff,
+    "screen_results.view",
+    Some(&found.id),
+    json!({ "user_id": found.user_id }),
+  )
+  .await?;
+  let debug = found.debug.clone();
+  let mut result = AdminScreenResult::from(found);
+  result.debug = debug;
+  Ok(Json(result))
+}
+
+#[derive(Deserialize)]
+struct AuditQuery {
+  actor_id: Option<String>,
+  action: Option<String>,
+  limit: Option<u64>,
+  offset: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct AuditEntry {
+  id: i64,
+  actor_id: Option<String>,
+  elevation: i32,
+  action: String,
+  target: Option<String>,
+  details: Option<serde_json::Value>,
+  created_at: Option<DateTime<Utc>>,
+}
+
+/// GET /admin/audit: newest first.
+async fn list_audit(
+  State(state): State<AppState>,
+  Extension(staff): Extension<Staff>,
+  Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEntry>>, ApiError> {
+  staff.require(ADMIN)?;
+  let (limit, offset) = page(query.limit, query.offset);
+  let mut select = admin_audit::Entity::find().order_by_desc(admin_audit::Column::Id);
+  if let Some(actor_id) = query.actor_id.as_deref() {
+    select = select.filter(admin_audit::Column::ActorId.eq(actor_id));
+  }
+  if let Some(action) = query.action.as_deref() {
+    select = select.filter(admin_audit::Column::Action.eq(action));
+  }
+  let found = select
+    .limit(limit)
+    .offset(offset)
+    .all(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?;
+  audit(
+    &state.db,
+    &staff,
+    "audit.list",
+    query.actor_id.as_deref(),
+    json!({ "action": query.action, "limit": limit, "offset": offset }),
+  )
+  .await?;
+  Ok(Json(
+    found
+      .into_iter()
+      .map(|entry| AuditEntry {
+        id: entry.id,
+        actor_id: entry.actor_id,
+        elevation: entry.elevation,
+        action: entry.action,
+        target: entry.target,
+        details: entry.details,
+        created_at: entry.c_time,
+      })
+      .collect(),
+  ))
+}
@@ -0,0 +1,435 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::{
+  body::Bytes,
+  extract::{Query, State},
+  http::StatusCode,
+  Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+  ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set, SqlErr,
+  TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::entity::{keys, user_tokens, users};
+use crate::keys::{constant_time_eq, issue_key, random_hex, Scope};
+use crate::mail::Mail;
+use crate::{
+  bad_request, bad_request_code, error_response, forbidden, internal_error, parse_json_body,
+  unauthorized, AppState, ErrorResponse,
+};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+/// API keys handed out by `/auth/login` carry this name so a password reset can revoke them.
+pub const SESSION_KEY_NAME: &str = "session";
+const SESSION_DAYS: i64 = 30;
+const CONFIRM_HOURS: i64 = 48;
+const RESET_HOURS: i64 = 1;
+const MIN_PASSWORD_LEN: usize = 8;
+const MAX_PASSWORD_LEN: usize = 256;
+
+const KIND_CONFIRM: &str = "CONFIRM";
+const KIND_RESET: &str = "RESET";
+
+#[derive(Deserialize)]
+struct RegisterRequest {
+  email: String,
+  password: String,
+  first_name: Option<String>,
+  last_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LoginRequest {
+  email: String,
+  password: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmRequest {
+  token: String,
+}
+
+/// `{email}` mails a reset link; `{token, password}` sets the new password.
+#[derive(Deserialize)]
+struct ResetRequest {
+  email: Option<String>,
+  token: Option<String>,
+  password: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct UserInfo {
+  pub(crate) id: String,
+  pub(crate) email: String,
+  pub(crate) first_name: Option<String>,
+  pub(crate) last_name: Option<String>,
+  pub(crate) confirmed: bool,
+  /// Whether uploaded screenshots are kept for the history.
+  pub(crate) store_images: bool,
+}
+
+impl From<users::Model> for UserInfo {
+  fn from(user: users::Model) -> Self {
+    Self {
+      id: user.id,
+      email: user.email,
+      first_name: user.first_name,
+      last_name: user.last_name,
+      confirmed: user.confirmd,
+      store_images: user.store_images,
+    }
+  }
+}
+
+#[derive(Serialize)]
+pub struct SessionResponse {
+  token: String,
+  expires_at: Option<DateTime<Utc>>,
+  user: UserInfo,
+}
+
+#[derive(Serialize)]
+pub struct StatusResponse {
+  status: &'static str,
+}
+
+pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+  let salt = SaltString::generate(&mut rand::rngs::OsRng);
+  Ok(
+    Argon2::default()
+      .hash_password(password.as_bytes(), &salt)?
+      .to_string(),
+  )
+}
+
+/// Accepts argon2 hashes and, for rows written before hashing existed, plaintext.
+fn verify_password(stored: &str, password: &str) -> bool {
+  match PasswordHash::new(stored) {
+    Ok(hash) => Argon2::default()
+      .verify_password(password.as_bytes(), &hash)
+      .is_ok(),
+    Err(_) => constant_time_eq(stored.as_bytes(), password.as_bytes()),
+  }
+}
+
+pub async fn register(
+  State(state): State<AppState>,
+  body: Bytes,
+) -> Result<(StatusCode, Json<UserInfo>), ApiError> {
+  let request: RegisterRequest = parse_json_body(&body)?;
+  let email = normalize_email(&request.email)?;
+  validate_password(&request.password)?;
+
+  let existing = users::Entity::find()
+    .filter(users::Column::Email.eq(email.as_str()))
+    .one(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?;
+  if existing.is_some() {
+    return Err(already_registered());
+  }
+
+  let password = hash_password(&request.password).map_err(internal_error("Hash failed"))?;
+  // Nothing is kept unless the confirmation mail went out, so the user can simply retry.
+  let txn = state.db.begin().await.map_err(internal_error("DB error"))?;
+  let inserted = users::ActiveModel {
+    id: Set(Uuid::new_v4().to_string()),
+    email: Set(email),
+    password: Set(password),
+    first_name: Set(clean_name(request.first_name)),
+    last_name: Set(clean_name(request.last_name)),
+    confirmd: Set(false),
+    ..Default::default()
+  }
+  .insert(&txn)
+  .await;
+  let user = match inserted {
+    Ok(user) => user,
+    Err(err) if matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+      return Err(already_registered());
+    }
+    Err(err) => return Err(internal_error("DB error")(err)),
+  };
+
+  let token = issue_user_token(&txn, &user.id, KIND_CONFIRM, Duration::hours(CONFIRM_HOURS))
+    .await
+    .map_err(internal_error("DB error"))?;
+  let mail = Mail {
+    to: user.email.clone(),
+    subject: "Confirm your Faux account".to_string(),
+    body: format!(
+      "Welcome to Faux!\n\nConfirm your email address by opening:\n{}/auth/confirm?token={token}\n\nThe link expires in {CONFIRM_HOURS} hours.",
+      state.public_url
+    ),
+  };
+  state
+    .mailer
+    .send(&mail)
+    .await
+    .map_err(internal_error("Failed to send mail"))?;
+  txn.commit().await.map_err(internal_error("DB error"))?;
+
+  Ok((StatusCode::CREATED, Json(UserInfo::from(user))))
+}
+
+fn already_registered() -> ApiError {
+  error_response(
+    StatusCode::CONFLICT,
+    "Email address is already registered",
+    Some(106),
+  )
+}
+
+pub async fn login(
+  State(state): State<AppState>,
+  body: Bytes,
+) -> Result<Json<SessionResponse>, ApiError> {
+  let request: LoginRequest = parse_json_body(&body)?;
+  let email = request.email.trim().to_ascii_lowercase();
+  let user = users::Entity::find()
+    .filter(users::Column::Email.eq(email))
+    .one(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?;
+  let Some(user) = user.filter(|user| verify_password(&user.password, &request.password)) else {
+    return Err(unauthorized("Invalid email or password", Some(100)));
+  };
+  if !user.confirmd {
+    return Err(forbidden("Email address is not confirmed", Some(107)));
+  }
+
+  if PasswordHash::new(&user.password).is_err() {
+    let password = hash_password(&request.password).map_err(internal_error("Hash failed"))?;
+    let mut active: users::ActiveModel = user.clone().into();
+    active.password = Set(password);
+    active
+      .update(&state.db)
+      .await
+      .map_err(internal_error("DB error"))?;
+  }
+
+  let expires_at = Utc::now() + Duration::days(SESSION_DAYS);
+  let (key, token) = issue_key(
+    &state.db,
+    &user.id,
+    SESSION_KEY_NAME,
+    &[Scope::Ingest, Scope::History],
+    Some(expires_at),
+    None,
+  )
+  .await
+  .map_err(internal_error("DB error"))?;
+  Ok(Json(SessionResponse {
+    token,
+    expires_at: key.expires_at,
+    user: UserInfo::from(user),
+  }))
+}
+
+/// `GET /auth/confirm?token=` is the link from the mail; `POST` takes `{token}`.
+pub async fn confirm_link(
+  State(state): State<AppState>,
+  Query(request): Query<ConfirmRequest>,
+) -> Result<Json<StatusResponse>, ApiError> {
+  confirm_token(&state.db, &request.token).await
+}
+
+pub async fn confirm(
+  State(state): State<AppState>,
+  body: Bytes,
+) -> Result<Json<StatusResponse>, ApiError> {
+  let request: ConfirmRequest = parse_json_body(&body)?;
+  confirm_token(&state.db, &request.token).await
+}
+
+async fn confirm_token(
+  db: &DatabaseConnection,
+  token: &str,
+) -> Result<Json<StatusResponse>, ApiError> {
+  let txn = db.begin().await.map_err(internal_error("DB error"))?;
+  let record = consume_user_token(&txn, token, KIND_CONFIRM).await?;
+  users::Entity::update_many()
+    .col_expr(users::Column::Confirmd, Expr::value(true))
+    .filter(users::Column::Id.eq(record.user_id))
+    .exec(&txn)
+    .await
+    .map_err(internal_error("DB error"))?;
+  txn.commit().await.map_err(internal_error("DB error"))?;
+  Ok(Json(StatusResponse {
+    status: "confirmed",
+  }))
+}
+
+pub async fn reset(
+  State(state): State<AppState>,
+  body: Bytes,
+) -> Result<(StatusCode, Json<StatusResponse>), ApiError> {
+  let request: ResetRequest = parse_json_body(&body)?;
+  if let Some(token) = request.token {
+    let password = request
+      .password
+      .ok_or_else(|| bad_request("Missing `password`"))?;
+    validate_password(&password)?;
+    let password = hash_password(&password).map_err(internal_error("Hash failed"))?;
+
+    let txn = state.db.begin().await.map_err(internal_error("DB error"))?;
+    let record = consume_user_token(&txn, &token, KIND_RESET).await?;
+    users::Entity::update_many()
+      .col_expr(users::Column::Password, Expr::value(password))
+      .filter(users::Column::Id.eq(record.user_id.as_str()))
+      .exec(&txn)
+      .await
+      .map_err(internal_error("DB error"))?;
+    keys::Entity::update_many()
+      .col_expr(keys::Column::RevokedAt, Expr::value(Utc::now()))
+      .filter(keys::Column::UserId.eq(record.user_id.as_str()))
+      .filter(keys::Column::Name.eq(SESSION_KEY_NAME))
+      .filter(keys::Column::RevokedAt.is_null())
+      .exec(&txn)
+      .await
+      .map_err(internal_error("DB error"))?;
+    txn.commit().await.map_err(internal_error("DB error"))?;
+    return Ok((
+      StatusCode::OK,
+      Json(StatusResponse {
+        status: "password_changed",
+      }),
+    ));
+  }
+
+  let email = request
+    .email
+    .ok_or_else(|| bad_request("Missing `email` or `token`"))?
+    .trim()
+    .to_ascii_lowercase();
+  let user = users::Entity::find()
+    .filter(users::Column::Email.eq(email))
+    .one(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?;
+  // Answer the same way whether or not the address exists.
+  if let Some(user) = user {
+    let token = issue_user_token(
+      &state.db,
+      &user.id,
+      KIND_RESET,
+      Duration::hours(RESET_HOURS),
+    )
+    .await
+    .map_err(internal_error("DB error"))?;
+    let mail = Mail {
+      to: user.email,
+      subject: "Reset your Faux password".to_string(),
+      body: format!(
+        "Someone asked to reset the password of your Faux account.\n\nReset token: {token}\n\nSend it with your new password to {}/auth/reset. It expires in {RESET_HOURS} hour(s). If this was not you, ignore this mail.",
+        state.public_url
+      ),
+    };
+    state
+      .mailer
+      .send(&mail)
+      .await
+      .map_err(internal_error("Failed to send mail"))?;
+  }
+  Ok((
+    StatusCode::ACCEPTED,
+    Json(StatusResponse { status: "sent" }),
+  ))
+}
+
+async fn issue_user_token<C>(
+  db: &C,
+  user_id: &str,
+  kind: &str,
+  ttl: Duration,
+) -> Result<String, sea_orm::DbErr>
+where
+  C: sea_orm::ConnectionTrait,
+{
+  let token = random_hex(32);
+  user_tokens::ActiveModel {
+    id: Set(Uuid::new_v4().to_string()),
+    user_id: Set(user_id.to_string()),
+    kind: Set(kind.to_string()),
+    token_hash: Set(token_hash(&token)),
+    expires_at: Set(Utc::now() + ttl),
+    ..Default::default()
+  }
+  .insert(db)
+  .await?;
+  Ok(token)
+}
+
+/// Marks a live token of `kind` as used; the update only matches once.
+async fn consume_user_token<C>(
+  db: &C,
+  token: &str,
+  kind: &str,
+) -> Result<user_tokens::Model, ApiError>
+where
+  C: sea_orm::ConnectionTrait,
+{
+  let invalid = || bad_request_code("Invalid or expired token", 108);
+  let record = user_tokens::Entity::find()
+    .filter(user_tokens::Column::TokenHash.eq(token_hash(token.trim())))
+    .filter(user_tokens::Column::Kind.eq(kind))
+    .one(db)
+    .await
+    .map_err(internal_error("DB error"))?
+    .filter(|record| record.used_at.is_none() && record.expires_at > Utc::now())
+    .ok_or_else(invalid)?;
+  let result = user_tokens::Entity::update_many()
+    .col_expr(user_tokens::Column::UsedAt, Expr::value(Utc::now()))
+    .filter(user_tokens::Column::Id.eq(record.id.as_str()))
+    .filter(user_tokens::Column::UsedAt.is_null())
+    .exec(db)
+    .await
+    .map_err(internal_error("DB error"))?;
+  if result.rows_affected == 0 {
+    return Err(invalid());
+  }
+  Ok(record)
+}
+
+fn token_hash(token: &str) -> String {
+  hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn normalize_email(email: &str) -> Result<String, ApiError> {
+  let email = email.trim().to_ascii_lowercase();
+  let valid = email.len() <= 190
+    && email.split_once('@').is_some_and(|(local, domain)| {
+      !local.is_empty() && domain.contains('.') && !domain.starts_with('.')
+    })
+    && !email.contains(char::is_whitespace);
+  if !valid {
+    return Err(bad_request("Invalid email address"));
+  }
+  Ok(email)
+}
+
+fn validate_password(password: &str) -> Result<(), ApiError> {
+  let len = password.chars().count();
+  if len < MIN_PASSWORD_LEN {
+    return Err(bad_request(&format!(
+      "Password must be at least {MIN_PASSWORD_LEN} characters"
+    )));
+  }
+  if len > MAX_PASSWORD_LEN {
+    return Err(bad_request("Password is too long"));
+  }
+  Ok(())
+}
+
+fn clean_name(name: Option<String>) -> Option<String> {
+  name
+    .map(|name| name.trim().chars().take(100).collect::<String>())
+    .filter(|name| !name.is_empty())
+}
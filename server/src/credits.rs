@@ -0,0 +1,292 @@
+use axum::{http::StatusCode, Json};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+  ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait,
+  QueryFilter, Set, TransactionTrait,
+};
+
+use crate::entity::{credit_ledger, subscriptions};
+use crate::{error_response, forbidden, internal_error, ErrorResponse};
+
+pub const GRANT: &str = "GRANT";
+pub const RESERVE: &str = "RESERVE";
+pub const COMMIT: &str = "COMMIT";
+pub const REFUND: &str = "REFUND";
+/// Credits taken back because the payment that granted them was refunded.
+pub const REVOKE: &str = "REVOKE";
+/// Manual correction through the admin API.
+pub const ADJUST: &str = "ADJUST";
+
+/// Credits taken for one upstream call. Settle it with `commit` or `refund`; dropping it
+/// unsettled (a failed or cancelled request) refunds it in the background.
+pub struct Reservation {
+  id: i64,
+  subscription_id: i64,
+  user_id: String,
+  amount: i32,
+  db: DatabaseConnection,
+  settled: bool,
+}
+
+/// Takes `cost` credits from the subscription, or fails with 403 (code 101) if it has fewer.
+pub async fn reserve(
+  db: &DatabaseConnection,
+  user_id: &str,
+  subscription_id: i64,
+  cost: i64,
+) -> Result<Reservation, (StatusCode, Json<ErrorResponse>)> {
+  let amount = i32::try_from(cost)
+    .ok()
+    .filter(|amount| *amount >= 0)
+    .ok_or_else(|| {
+      error_response(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Invalid model cost",
+        None,
+      )
+    })?;
+  let txn = db.begin().await.map_err(internal_error("DB error"))?;
+  let result = subscriptions::Entity::update_many()
+    .col_expr(
+      subscriptions::Column::Credits,
+      Expr::col(subscriptions::Column::Credits).sub(amount),
+    )
+    .filter(subscriptions::Column::Id.eq(subscription_id))
+    .filter(subscriptions::Column::Credits.gte(amount))
+    .exec(&txn)
+    .await
+    .map_err(internal_error("DB error"))?;
+  if result.rows_affected == 0 {
+    return Err(forbidden("No credits available", Some(101)));
+  }
+  let entry = record(
+    &txn,
+    subscription_id,
+    Some(user_id),
+    RESERVE,
+    -amount,
+    None,
+    None,
+    None,
+  )
+  .await
+  .map_err(internal_error("DB error"))?;
+  txn.commit().await.map_err(internal_error("DB error"))?;
+  Ok(Reservation {
+    id: entry.id,
+    subscription_id,
+    user_id: user_id.to_string(),
+    amount,
+    db: db.clone(),
+    settled: false,
+  })
+}
+
+impl Reservation {
+  /// Settles a successful call at `charge` credits and returns what was actually taken.
+  /// A charge above the reservation takes at most what is left of the balance.
+  pub async fn commit(
+    mut self,
+    screen_result_id: &str,
+    charge: i64,
+    note: Option<&str>,
+  ) -> Result<i32, DbErr> {
+    // A failed commit leaves the reserved credits taken rather than refunding them.
+    self.settled = true;
+    let charge = i32::try_from(charge.max(0)).unwrap_or(i32::MAX);
+    let txn = self.db.begin().await?;
+    let mut extra = charge - self.amount;
+    if extra > 0 {
+      let balance = subscriptions::Entity::find_by_id(self.subscription_id)
+        .one(&txn)
+        .await?
+        .map_or(0, |subscription| subscription.credits);
+      extra = extra.min(balance.max(0));
+    }
+    if extra != 0 {
+      let mut update = subscriptions::Entity::update_many()
+        .col_expr(
+          subscriptions::Column::Credits,
+          Expr::col(subscriptions::Column::Credits).sub(extra),
+        )
+        .filter(subscriptions::Column::Id.eq(self.subscription_id));
+      if extra > 0 {
+        update = update.filter(subscriptions::Column::Credits.gte(extra));
+      }
+      if update.exec(&txn).await?.rows_affected == 0 {
+        extra = 0;
+      }
+    }
+    record(
+      &txn,
+      self.subscription_id,
+      Some(&self.user_id),
+      COMMIT,
+      -extra,
+      Some(self.id),
+      Some(screen_result_id),
+      note,
+    )
+    .await?;
+    txn.commit().await?;
+    Ok(self.amount + extra)
+  }
+
+  /// Gives the credits back; the upstream call failed, or with no `screen_result_id`, the
+  /// capture could not even be recorded.
+  pub async fn refund(mut self, screen_result_id: Option<&str>, note: &str) -> Result<(), DbErr> {
+    refund(
+      &self.db,
+      self.id,
+      self.subscription_id,
+      &self.user_id,
+      self.amount,
+      screen_result_id,
+      note,
+    )
+    .await?;
+    self.settled = true;
+    Ok(())
+  }
+}
+
+impl Drop for Reservation {
+  fn drop(&mut self) {
+    if self.settled {
+      return;
+    }
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+      eprintln!(
+        "Reservation {} dropped outside a runtime; not refunded",
+        self.id
+      );
+      return;
+    };
+    let db = self.db.clone();
+    let (id, subscription_id, amount) = (self.id, self.subscription_id, self.amount);
+    let user_id = std::mem::take(&mut self.user_id);
+    runtime.spawn(async move {
+      if let Err(err) = refund(
+        &db,
+        id,
+        subscription_id,
+        &user_id,
+        amount,
+        None,
+        "request cancelled",
+      )
+      .await
+      {
+        eprintln!("Failed to refund reservation {id}: {err}");
+      }
+    });
+  }
+}
+
+/// Adds (or with a negative `amount`, removes) credits outside of a request, e.g. a purchase
+/// or an admin correction. Never takes the balance below zero.
+pub async fn adjust<C>(
+  db: &C,
+  subscription_id: i64,
+  user_id: Option<&str>,
+  kind: &str,
+  amount: i32,
+  note: Option<&str>,
+) -> Result<credit_ledger::Model, DbErr>
+where
+  C: TransactionTrait,
+{
+  let txn = db.begin().await?;
+  let mut update = subscriptions::Entity::update_many()
+    .col_expr(
+      subscriptions::Column::Credits,
+      Expr::col(subscriptions::Column::Credits).add(amount),
+    )
+    .filter(subscriptions::Column::Id.eq(subscription_id));
+  if amount < 0 {
+    update = update.filter(subscriptions::Column::Credits.gte(-amount));
+  }
+  if update.exec(&txn).await?.rows_affected == 0 {
+    return Err(DbErr::RecordNotUpdated);
+  }
+  let entry = record(
+    &txn,
+    subscription_id,
+    user_id,
+    kind,
+    amount,
+    None,
+    None,
+    note,
+  )
+  .await?;
+  txn.commit().await?;
+  Ok(entry)
+}
+
+async fn refund(
+  db: &DatabaseConnection,
+  reservation_id: i64,
+  subscription_id: i64,
+  user_id: &str,
+  amount: i32,
+  screen_result_id: Option<&str>,
+  note: &str,
+) -> Result<(), DbErr> {
+  let txn = db.begin().await?;
+  subscriptions::Entity::update_many()
+    .col_expr(
+      subscriptions::Column::Credits,
+      Expr::col(subscriptions::Column::Credits).add(amount),
+    )
+    .filter(subscriptions::Column::Id.eq(subscription_id))
+    .exec(&txn)
+    .await?;
+  // The unique `reservation_id` rolls this back if the reservation was already settled.
+  record(
+    &txn,
+    subscription_id,
+    Some(user_id),
+    REFUND,
+    amount,
+    Some(reservation_id),
+    screen_result_id,
+    Some(note),
+  )
+  .await?;
+  txn.commit().await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn record<C>(
+  db: &C,
+  subscription_id: i64,
+  user_id: Option<&str>,
+  kind: &str,
+  amount: i32,
+  reservation_id: Option<i64>,
+  screen_result_id: Option<&str>,
+  note: Option<&str>,
+) -> Result<credit_ledger::Model, DbErr>
+where
+  C: ConnectionTrait,
+{
+  let balance = subscriptions::Entity::find_by_id(subscription_id)
+    .one(db)
+    .await?
+    .ok_or_else(|| DbErr::RecordNotFound(format!("subscription {subscription_id}")))?
+    .credits;
+  credit_ledger::ActiveModel {
+    subscription_id: Set(subscription_id),
+    user_id: Set(user_id.map(str::to_string)),
+    kind: Set(kind.to_string()),
+    amount: Set(amount),
+    balance: Set(balance),
+    reservation_id: Set(reservation_id),
+    screen_result_id: Set(screen_result_id.map(str::to_string)),
+    note: Set(note.map(|note| note.chars().take(255).collect())),
+    ..Default::default()
+  }
+  .insert(db)
+  .await
+}
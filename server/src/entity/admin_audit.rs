@@ -0,0 +1,37 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "admin_audit")]
+pub struct Model {
+  #[sea_orm(primary_key)]
+  pub id: i64,
+  pub actor_id: Option<String>,
+  /// The actor's elevation when the action was taken.
+  pub elevation: i32,
+  /// e.g. `credits.adjust` or `keys.revoke`.
+  pub action: String,
+  /// Id of the user, key or screen result acted on.
+  pub target: Option<String>,
+  pub details: Option<Json>,
+  pub c_time: Option<DateTimeUtc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+  #[sea_orm(
+    belongs_to = "super::users::Entity",
+    from = "Column::ActorId",
+    to = "super::users::Column::Id",
+    on_update = "NoAction",
+    on_delete = "SetNull"
+  )]
+  Users,
+}
+
+impl Related<super::users::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::Users.def()
+  }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
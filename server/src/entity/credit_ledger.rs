@@ -0,0 +1,55 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "credit_ledger")]
+pub struct Model {
+  #[sea_orm(primary_key)]
+  pub id: i64,
+  pub subscription_id: i64,
+  pub user_id: Option<String>,
+  /// `GRANT`, `RESERVE`, `COMMIT`, `REFUND` or `ADJUST`.
+  pub kind: String,
+  /// Change to `subscriptions.credits`; negative when credits are taken.
+  pub amount: i32,
+  /// `subscriptions.credits` right after this entry.
+  pub balance: i32,
+  /// The `RESERVE` entry a `COMMIT` or `REFUND` settles.
+  pub reservation_id: Option<i64>,
+  pub screen_result_id: Option<String>,
+  pub note: Option<String>,
+  pub c_time: Option<DateTimeUtc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+  #[sea_orm(
+    belongs_to = "super::subscriptions::Entity",
+    from = "Column::SubscriptionId",
+    to = "super::subscriptions::Column::Id",
+    on_update = "NoAction",
+    on_delete = "Cascade"
+  )]
+  Subscriptions,
+  #[sea_orm(
+    belongs_to = "super::users::Entity",
+    from = "Column::UserId",
+    to = "super::users::Column::Id",
+    on_update = "NoAction",
+    on_delete = "SetNull"
+  )]
+  Users,
+}
+
+impl Related<super::subscriptions::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::Subscriptions.def()
+  }
+}
+
+impl Related<super::users::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::Users.def()
+  }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
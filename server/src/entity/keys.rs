@@ -0,0 +1,56 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "keys")]
+pub struct Model {
+  #[sea_orm(primary_key, auto_increment = false)]
+  pub id: String,
+  pub user_id: Option<String>,
+  pub name: Option<String>,
+  /// Salted SHA-256 of the secret, hex encoded.
+  pub key: String,
+  pub c_time: Option<DateTimeUtc>,
+  pub e_time: Option<DateTimeUtc>,
+  pub prefix: Option<String>,
+  pub salt: Option<String>,
+  /// Comma-separated `ingest`, `history`, `admin`.
+  pub scopes: Option<String>,
+  pub expires_at: Option<DateTimeUtc>,
+  pub last_used_at: Option<DateTimeUtc>,
+  pub revoked_at: Option<DateTimeUtc>,
+  /// Requests per minute for this key, below the package's `rate_limit`.
+  pub rate_limit: Option<i32>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+  #[sea_orm(
+    belongs_to = "super::users::Entity",
+    from = "Column::UserId",
+    to = "super::users::Column::Id",
+    on_update = "NoAction",
+    on_delete = "NoAction"
+  )]
+  Users,
+}
+
+impl Related<super::users::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::Users.def()
+  }
+}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+  async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+  where
+    C: ConnectionTrait,
+  {
+    // MySQL refreshes `e_time` by itself; SQLite and Postgres need it set here unless the
+    // caller set it.
+    if !insert && !self.e_time.is_set() {
+      self.e_time = sea_orm::ActiveValue::Set(Some(chrono::Utc::now()));
+    }
+    Ok(self)
+  }
+}
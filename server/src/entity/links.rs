@@ -0,0 +1,32 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "links")]
+pub struct Model {
+  #[sea_orm(primary_key, auto_increment = false)]
+  pub id: String,
+  pub user_id: Option<String>,
+  pub code: Option<String>,
+  pub pin: Option<String>,
+  pub c_time: Option<DateTimeUtc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+  #[sea_orm(
+    belongs_to = "super::users::Entity",
+    from = "Column::UserId",
+    to = "super::users::Column::Id",
+    on_update = "NoAction",
+    on_delete = "NoAction"
+  )]
+  Users,
+}
+
+impl Related<super::users::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::Users.def()
+  }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1 +1,14 @@
+pub mod admin_audit;
+pub mod credit_ledger;
+pub mod keys;
+pub mod links;
+pub mod packages;
+pub mod payments;
+pub mod roles;
+pub mod screen_result_images;
+pub mod screen_result_turns;
 pub mod screen_results;
+pub mod settings;
+pub mod subscriptions;
+pub mod user_tokens;
+pub mod users;
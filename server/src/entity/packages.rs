@@ -0,0 +1,46 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "packages")]
+pub struct Model {
+  #[sea_orm(primary_key)]
+  pub id: i64,
+  pub name: String,
+  pub rate_limit: i32,
+  /// Credits granted by each purchase or renewal.
+  pub credits: i32,
+  /// Days each purchase or renewal adds to `subscriptions.expires_at`.
+  pub duration_days: i32,
+  #[sea_orm(column_type = "Decimal(Some((10, 2)))", nullable)]
+  pub price: Option<Decimal>,
+  pub currency: Option<String>,
+  pub c_date: Option<DateTimeUtc>,
+  pub e_date: Option<DateTimeUtc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+  #[sea_orm(has_many = "super::subscriptions::Entity")]
+  Subscriptions,
+}
+
+impl Related<super::subscriptions::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::Subscriptions.def()
+  }
+}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+  async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+  where
+    C: ConnectionTrait,
+  {
+    // MySQL refreshes `e_date` by itself; SQLite and Postgres need it set here unless the
+    // caller set it.
+    if !insert && !self.e_date.is_set() {
+      self.e_date = sea_orm::ActiveValue::Set(Some(chrono::Utc::now()));
+    }
+    Ok(self)
+  }
+}
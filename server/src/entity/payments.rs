@@ -0,0 +1,34 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "payments")]
+pub struct Model {
+  #[sea_orm(primary_key, auto_increment = false)]
+  pub id: String,
+  pub transaction_id: Option<String>,
+  pub currency: Option<String>,
+  #[sea_orm(column_type = "Decimal(Some((10, 2)))", nullable)]
+  pub price: Option<Decimal>,
+  pub c_time: Option<DateTimeUtc>,
+  pub user_id: Option<String>,
+  pub package_id: Option<i64>,
+  /// `PaymentProvider::name` of the provider that reported it.
+  pub provider: Option<String>,
+  /// `PAID` or `REFUNDED`.
+  pub status: String,
+  pub refunded_at: Option<DateTimeUtc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+  #[sea_orm(has_many = "super::subscriptions::Entity")]
+  Subscriptions,
+}
+
+impl Related<super::subscriptions::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::Subscriptions.def()
+  }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
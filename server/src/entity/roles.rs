@@ -0,0 +1,46 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "roles")]
+pub struct Model {
+  #[sea_orm(primary_key, auto_increment = false)]
+  pub id: String,
+  pub user_id: Option<String>,
+  pub name: Option<String>,
+  pub elevation: Option<i32>,
+  pub c_time: Option<DateTimeUtc>,
+  pub e_time: Option<DateTimeUtc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+  #[sea_orm(
+    belongs_to = "super::users::Entity",
+    from = "Column::UserId",
+    to = "super::users::Column::Id",
+    on_update = "NoAction",
+    on_delete = "NoAction"
+  )]
+  Users,
+}
+
+impl Related<super::users::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::Users.def()
+  }
+}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+  async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+  where
+    C: ConnectionTrait,
+  {
+    // MySQL refreshes `e_time` by itself; SQLite and Postgres need it set here unless the
+    // caller set it.
+    if !insert && !self.e_time.is_set() {
+      self.e_time = sea_orm::ActiveValue::Set(Some(chrono::Utc::now()));
+    }
+    Ok(self)
+  }
+}
@@ -0,0 +1,34 @@
+use sea_orm::entity::prelude::*;
+
+/// Images after the first of a multi-image capture, which stays in `screen_results.file_name`.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "screen_result_images")]
+pub struct Model {
+  #[sea_orm(primary_key)]
+  pub id: i64,
+  pub screen_result_id: String,
+  /// 1 for the second image of the request, and so on.
+  pub position: i32,
+  /// Image store key; empty once the image was purged.
+  pub file_name: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+  #[sea_orm(
+    belongs_to = "super::screen_results::Entity",
+    from = "Column::ScreenResultId",
+    to = "super::screen_results::Column::Id",
+    on_update = "NoAction",
+    on_delete = "Cascade"
+  )]
+  ScreenResults,
+}
+
+impl Related<super::screen_results::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::ScreenResults.def()
+  }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,43 @@
+use sea_orm::entity::prelude::*;
+
+/// A follow-up question on a screen result and the answer to it.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "screen_result_turns")]
+pub struct Model {
+  #[sea_orm(primary_key)]
+  pub id: i64,
+  pub screen_result_id: String,
+  /// 1 for the first follow-up, and so on.
+  pub position: i32,
+  pub question: String,
+  /// `None` until the answer finished streaming.
+  pub answer: Option<String>,
+  /// `RUNNING`, `DONE` or `ERROR`.
+  pub status: String,
+  pub model: Option<String>,
+  pub input_tokens: Option<i64>,
+  pub output_tokens: Option<i64>,
+  /// Credits charged for the follow-up, once it finished.
+  pub credits: Option<i32>,
+  pub c_time: Option<DateTimeUtc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+  #[sea_orm(
+    belongs_to = "super::screen_results::Entity",
+    from = "Column::ScreenResultId",
+    to = "super::screen_results::Column::Id",
+    on_update = "NoAction",
+    on_delete = "Cascade"
+  )]
+  ScreenResults,
+}
+
+impl Related<super::screen_results::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::ScreenResults.def()
+  }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -11,9 +11,60 @@ pub struct Model {
   pub c_time: Option<DateTimeUtc>,
   pub e_time: Option<DateTimeUtc>,
   pub status: String,
+  pub model: Option<String>,
+  pub input_tokens: Option<i64>,
+  pub output_tokens: Option<i64>,
+  /// Credits charged for the call, once it finished.
+  pub credits: Option<i32>,
+  /// What the user typed to go with the screenshot, if anything.
+  pub prompt: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+  #[sea_orm(
+    belongs_to = "super::users::Entity",
+    from = "Column::UserId",
+    to = "super::users::Column::Id",
+    on_update = "NoAction",
+    on_delete = "NoAction"
+  )]
+  Users,
+  #[sea_orm(has_many = "super::screen_result_images::Entity")]
+  ScreenResultImages,
+  #[sea_orm(has_many = "super::screen_result_turns::Entity")]
+  ScreenResultTurns,
+}
+
+impl Related<super::users::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::Users.def()
+  }
+}
+
+impl Related<super::screen_result_images::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::ScreenResultImages.def()
+  }
+}
+
+impl Related<super::screen_result_turns::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::ScreenResultTurns.def()
+  }
+}
 
-impl ActiveModelBehavior for ActiveModel {}
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+  async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+  where
+    C: ConnectionTrait,
+  {
+    // MySQL refreshes `e_time` by itself; SQLite and Postgres need it set here unless the
+    // caller set it.
+    if !insert && !self.e_time.is_set() {
+      self.e_time = sea_orm::ActiveValue::Set(Some(chrono::Utc::now()));
+    }
+    Ok(self)
+  }
+}
@@ -0,0 +1,70 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "subscriptions")]
+pub struct Model {
+  #[sea_orm(primary_key)]
+  pub id: i64,
+  pub user_id: Option<String>,
+  pub payment_id: Option<String>,
+  pub package_id: Option<i64>,
+  pub expires_at: Option<DateTimeUtc>,
+  pub credits: i32,
+  pub c_date: Option<DateTimeUtc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+  #[sea_orm(
+    belongs_to = "super::packages::Entity",
+    from = "Column::PackageId",
+    to = "super::packages::Column::Id",
+    on_update = "NoAction",
+    on_delete = "NoAction"
+  )]
+  Packages,
+  #[sea_orm(
+    belongs_to = "super::payments::Entity",
+    from = "Column::PaymentId",
+    to = "super::payments::Column::Id",
+    on_update = "NoAction",
+    on_delete = "NoAction"
+  )]
+  Payments,
+  #[sea_orm(
+    belongs_to = "super::users::Entity",
+    from = "Column::UserId",
+    to = "super::users::Column::Id",
+    on_update = "NoAction",
+    on_delete = "NoAction"
+  )]
+  Users,
+  #[sea_orm(has_many = "super::credit_ledger::Entity")]
+  CreditLedger,
+}
+
+impl Related<super::packages::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::Packages.def()
+  }
+}
+
+impl Related<super::payments::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::Payments.def()
+  }
+}
+
+impl Related<super::users::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::Users.def()
+  }
+}
+
+impl Related<super::credit_ledger::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::CreditLedger.def()
+  }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,100 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "users")]
+pub struct Model {
+  #[sea_orm(primary_key, auto_increment = false)]
+  pub id: String,
+  pub email: String,
+  pub password: String,
+  pub first_name: Option<String>,
+  pub last_name: Option<String>,
+  pub confirmd: bool,
+  pub c_date: Option<DateTimeUtc>,
+  pub e_date: Option<DateTimeUtc>,
+  /// `false` when the user opted out of keeping uploaded screenshots.
+  pub store_images: bool,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+  #[sea_orm(has_many = "super::keys::Entity")]
+  Keys,
+  #[sea_orm(has_many = "super::links::Entity")]
+  Links,
+  #[sea_orm(has_many = "super::roles::Entity")]
+  Roles,
+  #[sea_orm(has_many = "super::screen_results::Entity")]
+  ScreenResults,
+  #[sea_orm(has_many = "super::settings::Entity")]
+  Settings,
+  #[sea_orm(has_many = "super::subscriptions::Entity")]
+  Subscriptions,
+  #[sea_orm(has_many = "super::user_tokens::Entity")]
+  UserTokens,
+  #[sea_orm(has_many = "super::credit_ledger::Entity")]
+  CreditLedger,
+}
+
+impl Related<super::keys::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::Keys.def()
+  }
+}
+
+impl Related<super::links::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::Links.def()
+  }
+}
+
+impl Related<super::roles::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::Roles.def()
+  }
+}
+
+impl Related<super::screen_results::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::ScreenResults.def()
+  }
+}
+
+impl Related<super::settings::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::Settings.def()
+  }
+}
+
+impl Related<super::subscriptions::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::Subscriptions.def()
+  }
+}
+
+impl Related<super::user_tokens::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::UserTokens.def()
+  }
+}
+
+impl Related<super::credit_ledger::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::CreditLedger.def()
+  }
+}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+  async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+  where
+    C: ConnectionTrait,
+  {
+    // MySQL refreshes `e_date` by itself; SQLite and Postgres need it set here unless the
+    // caller set it.
+    if !insert && !self.e_date.is_set() {
+      self.e_date = sea_orm::ActiveValue::Set(Some(chrono::Utc::now()));
+    }
+    Ok(self)
+  }
+}
@@ -0,0 +1,224 @@
+use std::convert::Infallible;
+
+use axum::{
+  body::Bytes,
+  extract::{Path, State},
+  http::{HeaderMap, StatusCode},
+  response::sse::{Event, Sse},
+  Json,
+};
+use sea_orm::{
+  ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect, Set, SqlErr,
+};
+use serde::Deserialize;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use crate::entity::{screen_result_images, screen_result_turns, screen_results};
+use crate::history::Answer;
+use crate::keys::{require_scope, Scope};
+use crate::provider::{Role, Turn, Usage};
+use crate::upload::Upload;
+use crate::{
+  bad_request, bad_request_code, call_model_stream, charge_credits, client_closed, credits,
+  error_response, image_store, internal_error, parse_json_body, require_subscription,
+  sanitize_stream_text, select_model, send_event, AppState, ErrorResponse,
+};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+/// Longest follow-up question accepted, in characters.
+const MAX_QUESTION_CHARS: usize = 4_000;
+
+#[derive(Deserialize)]
+pub struct FollowUpRequest {
+  question: String,
+}
+
+/// POST /history/:id/follow_up: asks a further question about a finished capture. The model
+/// gets the capture's images (those still stored), its answer and the earlier follow-ups, and
+/// the reply streams back like `/ingest_stream`.
+pub async fn follow_up(
+  State(state): State<AppState>,
+  headers: HeaderMap,
+  Path(id): Path<String>,
+  body: Bytes,
+) -> Result<Sse<UnboundedReceiverStream<Result<Event, Infallible>>>, ApiError> {
+  let key = require_scope(&state.db, &headers, Scope::Ingest).await?;
+  let request: FollowUpRequest = parse_json_body(&body)?;
+  let question = request.question.trim().to_string();
+  if question.is_empty() {
+    return Err(bad_request("Question is empty"));
+  }
+  if question.chars().count() > MAX_QUESTION_CHARS {
+    return Err(bad_request(&format!(
+      "Question is longer than {MAX_QUESTION_CHARS} characters"
+    )));
+  }
+
+  let capture = screen_results::Entity::find_by_id(id)
+    .filter(screen_results::Column::UserId.eq(key.user_id.as_str()))
+    .one(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?
+    .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "History entry not found", None))?;
+  if capture.status != "DONE" {
+    return Err(error_response(
+      StatusCode::CONFLICT,
+      "Only finished answers can be followed up",
+      None,
+    ));
+  }
+
+  let user_id = key.user_id.clone();
+  let subscription = require_subscription(&state.db, &user_id).await?;
+  state.rate_limiter.check(&key, subscription.rate_limit)?;
+  let model = select_model(&headers, &state, &subscription)?;
+  if !model.spec.streaming {
+    return Err(bad_request_code(
+      &format!("Model `{}` does not support streaming", model.spec.name),
+      104,
+    ));
+  }
+
+  let earlier = screen_result_turns::Entity::find()
+    .filter(screen_result_turns::Column::ScreenResultId.eq(capture.id.as_str()))
+    .order_by_asc(screen_result_turns::Column::Position)
+    .all(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?;
+  let position = earlier.last().map_or(1, |turn| turn.position + 1);
+  let mut turns = vec![Turn {
+    role: Role::Assistant,
+    text: Answer::from_debug(capture.debug.as_ref()).text,
+  }];
+  for turn in earlier.into_iter().filter(|turn| turn.status == "DONE") {
+    turns.push(Turn {
+      role: Role::User,
+      text: turn.question,
+    });
+    turns.push(Turn {
+      role: Role::Assistant,
+      text: turn.answer.unwrap_or_default(),
+    });
+  }
+  turns.push(Turn {
+    role: Role::User,
+    text: question.clone(),
+  });
+  let images = load_images(&state, &capture).await?;
+
+  let reservation =
+    credits::reserve(&state.db, &user_id, subscription.id, model.spec.cost).await?;
+  let turn = screen_result_turns::ActiveModel {
+    screen_result_id: Set(capture.id.clone()),
+    position: Set(position),
+    question: Set(question),
+    status: Set("RUNNING".to_string()),
+    model: Set(Some(model.spec.name.clone())),
+    ..Default::default()
+  }
+  .insert(&state.db)
+  .await;
+  let turn = match turn {
+    Ok(turn) => turn,
+    Err(err) => {
+      if let Err(err) = reservation.refund(Some(&capture.id), "Follow-up not saved").await {
+        eprintln!("Failed to refund credits record_id={}: {err}", capture.id);
+      }
+      // Another follow-up on the same answer took this position first.
+      if matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) {
+        return Err(error_response(
+          StatusCode::CONFLICT,
+          "Another follow-up on this answer is in progress",
+          None,
+        ));
+      }
+      return Err(internal_error("DB error")(err));
+    }
+  };
+
+  let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<Result<Event, Infallible>>();
+  tokio::spawn(async move {
+    let mut full_text = String::new();
+    let note = capture.prompt.as_deref();
+    let answer = call_model_stream(&state, &images, note, &turns, &model, |delta| {
+      full_text.push_str(delta);
+      send_event(&tx, "delta", Some(delta.to_string()), None);
+    });
+    // As in `ingest_stream`: a client that hung up is not charged for the rest.
+    let stream_result = tokio::select! {
+      result = answer => result,
+      () = tx.closed() => Err(client_closed()),
+    };
+
+    match stream_result {
+      Ok(usage) => {
+        let answer = sanitize_stream_text(&full_text);
+        let charged = charge_credits(reservation, &capture.id, &model, usage.as_ref()).await;
+        update_turn(&state, turn, "DONE", Some(answer.clone()), usage, charged).await;
+        send_event(&tx, "done", Some(answer), None);
+      }
+      Err((_, body)) => {
+        if let Err(err) = reservation.refund(Some(&capture.id), &body.error.message).await {
+          eprintln!("Failed to refund credits record_id={}: {err}", capture.id);
+        }
+        update_turn(&state, turn, "ERROR", None, None, None).await;
+        send_event(&tx, "error", None, Some(body.0.error));
+      }
+    }
+  });
+
+  Ok(Sse::new(UnboundedReceiverStream::new(rx)))
+}
+
+/// The capture's images that are still stored, in upload order.
+async fn load_images(
+  state: &AppState,
+  capture: &screen_results::Model,
+) -> Result<Vec<Upload>, ApiError> {
+  let mut keys = vec![capture.file_name.clone()];
+  keys.extend(
+    screen_result_images::Entity::find()
+      .select_only()
+      .column(screen_result_images::Column::FileName)
+      .filter(screen_result_images::Column::ScreenResultId.eq(capture.id.as_str()))
+      .order_by_asc(screen_result_images::Column::Position)
+      .into_tuple::<String>()
+      .all(&state.db)
+      .await
+      .map_err(internal_error("DB error"))?,
+  );
+  let mut images = Vec::new();
+  for key in keys.iter().filter(|key| !key.is_empty()) {
+    match state.images.get(key).await {
+      Ok(Some(bytes)) => images.push(Upload {
+        bytes,
+        mime: image_store::mime_for(key),
+      }),
+      Ok(None) => {}
+      Err(err) => eprintln!("Failed to load image {key}: {err}"),
+    }
+  }
+  Ok(images)
+}
+
+async fn update_turn(
+  state: &AppState,
+  turn: screen_result_turns::Model,
+  status: &str,
+  answer: Option<String>,
+  usage: Option<Usage>,
+  credits: Option<i32>,
+) {
+  let mut active: screen_result_turns::ActiveModel = turn.into();
+  active.status = Set(status.to_string());
+  active.answer = Set(answer);
+  if let Some(usage) = usage {
+    active.input_tokens = Set(i64::try_from(usage.input_tokens).ok());
+    active.output_tokens = Set(i64::try_from(usage.output_tokens).ok());
+  }
+  active.credits = Set(credits);
+  if let Err(err) = active.update(&state.db).await {
+    eprintln!("Failed to update follow-up: {err}");
+  }
+}
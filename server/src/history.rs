@@ -0,0 +1,239 @@
+use axum::{
+  extract::{Path, Query, State},
+  http::{HeaderMap, StatusCode},
+  Json,
+};
+use base64::Engine as _;
+use chrono::{DateTime, Utc};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde::{Deserialize, Serialize};
+
+use crate::entity::{screen_result_images, screen_result_turns, screen_results};
+use crate::keys::{require_scope, Scope};
+use crate::{error_response, image_store, internal_error, AppState, ErrorResponse};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+const DEFAULT_LIMIT: u64 = 20;
+const MAX_LIMIT: u64 = 100;
+const PREVIEW_CHARS: usize = 120;
+
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+  /// `DONE`, `ERROR` or `RUNNING`, any case.
+  status: Option<String>,
+  /// RFC 3339 bounds on when the capture was made.
+  from: Option<DateTime<Utc>>,
+  to: Option<DateTime<Utc>>,
+  limit: Option<u64>,
+  offset: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct HistoryItem {
+  id: String,
+  status: String,
+  model: Option<String>,
+  credits: Option<i32>,
+  created_at: Option<DateTime<Utc>>,
+  /// Start of the answer, or of the error message.
+  preview: String,
+}
+
+#[derive(Serialize)]
+pub struct HistoryEntry {
+  id: String,
+  status: String,
+  model: Option<String>,
+  credits: Option<i32>,
+  created_at: Option<DateTime<Utc>>,
+  /// What the user typed to go with the screenshot.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  prompt: Option<String>,
+  text: String,
+  code: String,
+  error: Option<String>,
+  /// `None` when the image is no longer stored.
+  image: Option<HistoryImage>,
+  /// The other images of a multi-image capture that are still stored, in upload order.
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  more_images: Vec<HistoryImage>,
+  /// Questions asked about the answer afterwards, oldest first.
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  follow_ups: Vec<HistoryFollowUp>,
+}
+
+#[derive(Serialize)]
+pub struct HistoryFollowUp {
+  question: String,
+  /// `None` while it is being answered or when answering failed.
+  answer: Option<String>,
+  status: String,
+  created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct HistoryImage {
+  mime: String,
+  /// Base64 of the uploaded bytes.
+  data: String,
+}
+
+/// The answer stored in `screen_results.debug`: `text`, `code` and the error message.
+pub(crate) struct Answer {
+  pub(crate) text: String,
+  code: String,
+  error: Option<String>,
+}
+
+impl Answer {
+  pub(crate) fn from_debug(debug: Option<&serde_json::Value>) -> Self {
+    let field = |value: Option<&serde_json::Value>| {
+      value
+        .and_then(serde_json::Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+    };
+    let Some(debug) = debug else {
+      return Self {
+        text: String::new(),
+        code: String::new(),
+        error: None,
+      };
+    };
+    // `/ingest` stores the whole response, `/ingest_stream` only the final text.
+    let response = debug.get("response").unwrap_or(debug);
+    Self {
+      text: field(response.get("text")),
+      code: field(response.get("code")),
+      error: debug
+        .get("error")
+        .and_then(|error| error.get("message"))
+        .and_then(serde_json::Value::as_str)
+        .map(str::to_string),
+    }
+  }
+
+  fn preview(&self) -> String {
+    let source = self.error.as_deref().unwrap_or(&self.text);
+    let line = source.split_whitespace().collect::<Vec<_>>().join(" ");
+    match line.char_indices().nth(PREVIEW_CHARS) {
+      Some((end, _)) => format!("{}...", &line[..end]),
+      None => line,
+    }
+  }
+}
+
+/// GET /history: the caller's captures, newest first.
+pub async fn list_history(
+  State(state): State<AppState>,
+  headers: HeaderMap,
+  Query(query): Query<HistoryQuery>,
+) -> Result<Json<Vec<HistoryItem>>, ApiError> {
+  let key = require_scope(&state.db, &headers, Scope::History).await?;
+  let mut select = screen_results::Entity::find()
+    .filter(screen_results::Column::UserId.eq(key.user_id))
+    .order_by_desc(screen_results::Column::CTime);
+  if let Some(status) = query.status.as_deref().filter(|status| !status.is_empty()) {
+    select = select.filter(screen_results::Column::Status.eq(status.to_ascii_uppercase()));
+  }
+  if let Some(from) = query.from {
+    select = select.filter(screen_results::Column::CTime.gte(from));
+  }
+  if let Some(to) = query.to {
+    select = select.filter(screen_results::Column::CTime.lt(to));
+  }
+  let found = select
+    .limit(query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT))
+    .offset(query.offset.unwrap_or(0))
+    .all(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?;
+  Ok(Json(
+    found
+      .into_iter()
+      .map(|model| HistoryItem {
+        preview: Answer::from_debug(model.debug.as_ref()).preview(),
+        id: model.id,
+        status: model.status,
+        model: model.model,
+        credits: model.credits,
+        created_at: model.c_time,
+      })
+      .collect(),
+  ))
+}
+
+/// GET /history/:id: one capture with its answer, images and follow-ups.
+pub async fn get_history(
+  State(state): State<AppState>,
+  headers: HeaderMap,
+  Path(id): Path<String>,
+) -> Result<Json<HistoryEntry>, ApiError> {
+  let key = require_scope(&state.db, &headers, Scope::History).await?;
+  let model = screen_results::Entity::find_by_id(id)
+    .filter(screen_results::Column::UserId.eq(key.user_id))
+    .one(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?
+    .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "History entry not found", None))?;
+  let more_keys: Vec<String> = screen_result_images::Entity::find()
+    .select_only()
+    .column(screen_result_images::Column::FileName)
+    .filter(screen_result_images::Column::ScreenResultId.eq(model.id.as_str()))
+    .order_by_asc(screen_result_images::Column::Position)
+    .into_tuple()
+    .all(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?;
+  let mut more_images = Vec::new();
+  for key in more_keys {
+    more_images.extend(load_image(&state, &key).await);
+  }
+  let follow_ups = screen_result_turns::Entity::find()
+    .filter(screen_result_turns::Column::ScreenResultId.eq(model.id.as_str()))
+    .order_by_asc(screen_result_turns::Column::Position)
+    .all(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?
+    .into_iter()
+    .map(|turn| HistoryFollowUp {
+      question: turn.question,
+      answer: turn.answer,
+      status: turn.status,
+      created_at: turn.c_time,
+    })
+    .collect();
+  let answer = Answer::from_debug(model.debug.as_ref());
+  Ok(Json(HistoryEntry {
+    image: load_image(&state, &model.file_name).await,
+    more_images,
+    follow_ups,
+    id: model.id,
+    status: model.status,
+    model: model.model,
+    credits: model.credits,
+    created_at: model.c_time,
+    prompt: model.prompt,
+    text: answer.text,
+    code: answer.code,
+    error: answer.error,
+  }))
+}
+
+async fn load_image(state: &AppState, key: &str) -> Option<HistoryImage> {
+  if key.is_empty() {
+    return None;
+  }
+  let bytes = match state.images.get(key).await {
+    Ok(bytes) => bytes?,
+    Err(err) => {
+      eprintln!("Failed to load image {key}: {err}");
+      return None;
+    }
+  };
+  Some(HistoryImage {
+    mime: image_store::mime_for(key).to_string(),
+    data: base64::engine::general_purpose::STANDARD.encode(bytes),
+  })
+}
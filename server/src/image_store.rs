@@ -0,0 +1,446 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+  ColumnTrait, Condition, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QuerySelect,
+  QueryTrait,
+};
+use sha2::{Digest, Sha256};
+use tokio::sync::{RwLock, RwLockReadGuard};
+
+use crate::entity::{screen_result_images, screen_results};
+
+/// Held for reading while a capture records its images and for writing while `purge` decides
+/// what to delete, so a purge never deletes a file a new capture was just deduplicated onto.
+static REFERENCES: RwLock<()> = RwLock::const_new(());
+
+/// Where uploaded screenshots are kept. Keys are plain file names such as `{sha256}.png`.
+#[async_trait]
+pub trait ImageStore: Send + Sync {
+  fn name(&self) -> &'static str;
+
+  /// Stores `bytes` under `key`; a key that already exists is left alone.
+  async fn put(&self, key: &str, bytes: &[u8], mime: &str) -> anyhow::Result<()>;
+
+  /// `None` when nothing is stored under `key`.
+  async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+
+  /// Deleting a missing key is not an error.
+  async fn delete(&self, key: &str) -> anyhow::Result<()>;
+}
+
+/// Picks the store from `IMAGE_STORE` (`local`, the default, or `s3`).
+pub fn from_env() -> anyhow::Result<Arc<dyn ImageStore>> {
+  let kind = env::var("IMAGE_STORE").unwrap_or_default();
+  match kind.trim().to_ascii_lowercase().as_str() {
+    "" | "local" => {
+      let dir = env::var("IMAGE_DIR").unwrap_or_else(|_| "data/images".to_string());
+      Ok(Arc::new(LocalImageStore::new(dir.into())))
+    }
+    "s3" => {
+      let var = |name: &str| {
+        env::var(name)
+          .ok()
+          .filter(|value| !value.trim().is_empty())
+          .ok_or_else(|| anyhow::anyhow!("IMAGE_STORE=s3 needs {name}"))
+      };
+      Ok(Arc::new(S3ImageStore::new(S3Config {
+        endpoint: var("S3_ENDPOINT")?,
+        bucket: var("S3_BUCKET")?,
+        region: env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+        access_key: var("S3_ACCESS_KEY")?,
+        secret_key: var("S3_SECRET_KEY")?,
+        prefix: env::var("S3_PREFIX").unwrap_or_default(),
+      })))
+    }
+    other => anyhow::bail!("Unknown IMAGE_STORE `{other}` (expected local or s3)"),
+  }
+}
+
+/// `IMAGE_RETENTION_DAYS`; `None` (the default, or `0`) keeps images forever.
+pub fn retention_from_env() -> anyhow::Result<Option<Duration>> {
+  let Ok(days) = env::var("IMAGE_RETENTION_DAYS") else {
+    return Ok(None);
+  };
+  let days = days.trim();
+  if days.is_empty() {
+    return Ok(None);
+  }
+  // A negative cutoff would lie in the future and purge every image.
+  let days: i64 = days
+    .parse()
+    .ok()
+    .filter(|days| *days >= 0)
+    .ok_or_else(|| anyhow::anyhow!("IMAGE_RETENTION_DAYS must be a number of days"))?;
+  Ok((days > 0).then(|| Duration::days(days)))
+}
+
+/// The content-addressed key of an upload, so identical screenshots are stored once.
+pub fn key_for(bytes: &[u8], mime: &str) -> String {
+  let ext = if mime.contains("png") {
+    "png"
+  } else if mime.contains("jpeg") || mime.contains("jpg") {
+    "jpg"
+  } else if mime.contains("webp") {
+    "webp"
+  } else {
+    "bin"
+  };
+  format!("{}.{ext}", hex::encode(Sha256::digest(bytes)))
+}
+
+pub fn mime_for(key: &str) -> &'static str {
+  match key.rsplit_once('.').map(|(_, ext)| ext) {
+    Some("png") => "image/png",
+    Some("jpg") => "image/jpeg",
+    Some("webp") => "image/webp",
+    _ => "application/octet-stream",
+  }
+}
+
+/// Keys are generated by `key_for` (or were `{uuid}.{ext}` before); refuse anything else so a
+/// key can never leave the store's directory or prefix.
+fn valid_key(key: &str) -> anyhow::Result<&str> {
+  if key.is_empty()
+    || !key
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+    || key.starts_with('.')
+  {
+    anyhow::bail!("Invalid image key `{key}`");
+  }
+  Ok(key)
+}
+
+/// Keeps `purge` from deleting files while the guard is held.
+pub async fn hold_references() -> RwLockReadGuard<'static, ()> {
+  REFERENCES.read().await
+}
+
+/// Drops the images of the screen results matching `filter`, extra images included: their
+/// `file_name` is cleared and each file no other screen result still points to is deleted.
+/// Returns the number of files deleted.
+pub async fn purge(
+  db: &DatabaseConnection,
+  store: &dyn ImageStore,
+  filter: Condition,
+) -> anyhow::Result<usize> {
+  let _purging = REFERENCES.write().await;
+  let owners = screen_results::Entity::find()
+    .select_only()
+    .column(screen_results::Column::Id)
+    .filter(filter.clone())
+    .into_query();
+  let mut keys: BTreeSet<String> = screen_results::Entity::find()
+    .select_only()
+    .column(screen_results::Column::FileName)
+    .distinct()
+    .filter(screen_results::Column::FileName.ne(""))
+    .filter(filter.clone())
+    .into_tuple::<String>()
+    .all(db)
+    .await?
+    .into_iter()
+    .collect();
+  keys.extend(
+    screen_result_images::Entity::find()
+      .select_only()
+      .column(screen_result_images::Column::FileName)
+      .distinct()
+      .filter(screen_result_images::Column::FileName.ne(""))
+      .filter(screen_result_images::Column::ScreenResultId.in_subquery(owners.clone()))
+      .into_tuple::<String>()
+      .all(db)
+      .await?,
+  );
+  if keys.is_empty() {
+    return Ok(0);
+  }
+  screen_result_images::Entity::update_many()
+    .col_expr(screen_result_images::Column::FileName, Expr::value(""))
+    .filter(screen_result_images::Column::FileName.ne(""))
+    .filter(screen_result_images::Column::ScreenResultId.in_subquery(owners))
+    .exec(db)
+    .await?;
+  screen_results::Entity::update_many()
+    .col_expr(screen_results::Column::FileName, Expr::value(""))
+    .filter(screen_results::Column::FileName.ne(""))
+    .filter(filter)
+    .exec(db)
+    .await?;
+  let mut deleted = 0;
+  for key in keys {
+    let still_used = screen_results::Entity::find()
+      .filter(screen_results::Column::FileName.eq(key.as_str()))
+      .count(db)
+      .await?
+      > 0
+      || screen_result_images::Entity::find()
+        .filter(screen_result_images::Column::FileName.eq(key.as_str()))
+        .count(db)
+        .await?
+        > 0;
+    if still_used {
+      continue;
+    }
+    match store.delete(&key).await {
+      Ok(()) => deleted += 1,
+      Err(err) => eprintln!("Failed to delete image {key} from {}: {err}", store.name()),
+    }
+  }
+  Ok(deleted)
+}
+
+/// Purges the images of screen results older than `retention`.
+pub async fn purge_expired(
+  db: &DatabaseConnection,
+  store: &dyn ImageStore,
+  retention: Duration,
+) -> anyhow::Result<usize> {
+  let cutoff = Utc::now() - retention;
+  purge(
+    db,
+    store,
+    Condition::all().add(screen_results::Column::CTime.lt(cutoff)),
+  )
+  .await
+}
+
+/// Runs `purge_expired` once an hour for the lifetime of the server.
+pub fn spawn_retention(db: DatabaseConnection, store: Arc<dyn ImageStore>, retention: Duration) {
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 60));
+    loop {
+      interval.tick().await;
+      match purge_expired(&db, store.as_ref(), retention).await {
+        Ok(0) => {}
+        Ok(deleted) => eprintln!("Purged {deleted} expired images"),
+        Err(err) => eprintln!("Image purge failed: {err}"),
+      }
+    }
+  });
+}
+
+/// Files in a local directory, `data/images` by default.
+pub struct LocalImageStore {
+  dir: PathBuf,
+}
+
+impl LocalImageStore {
+  pub fn new(dir: PathBuf) -> Self {
+    Self { dir }
+  }
+}
+
+#[async_trait]
+impl ImageStore for LocalImageStore {
+  fn name(&self) -> &'static str {
+    "local"
+  }
+
+  async fn put(&self, key: &str, bytes: &[u8], _mime: &str) -> anyhow::Result<()> {
+    let path = self.dir.join(valid_key(key)?);
+    if tokio::fs::try_exists(&path).await? {
+      return Ok(());
+    }
+    tokio::fs::create_dir_all(&self.dir).await?;
+    // Write next to the target and rename, so readers never see half a file.
+    let partial = self.dir.join(format!(".{key}.{}", uuid::Uuid::new_v4()));
+    tokio::fs::write(&partial, bytes).await?;
+    tokio::fs::rename(&partial, &path).await?;
+    Ok(())
+  }
+
+  async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    match tokio::fs::read(self.dir.join(valid_key(key)?)).await {
+      Ok(bytes) => Ok(Some(bytes)),
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+      Err(err) => Err(err.into()),
+    }
+  }
+
+  async fn delete(&self, key: &str) -> anyhow::Result<()> {
+    match tokio::fs::remove_file(self.dir.join(valid_key(key)?)).await {
+      Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+      _ => Ok(()),
+    }
+  }
+}
+
+pub struct S3Config {
+  /// e.g. `https://s3.eu-central-1.amazonaws.com` or `http://localhost:9000` for MinIO.
+  pub endpoint: String,
+  pub bucket: String,
+  pub region: String,
+  pub access_key: String,
+  pub secret_key: String,
+  /// Prepended to every key, e.g. `images/`.
+  pub prefix: String,
+}
+
+/// An S3-compatible bucket addressed path-style (`{endpoint}/{bucket}/{key}`), which works
+/// with AWS as well as MinIO. Requests are signed with AWS Signature Version 4.
+pub struct S3ImageStore {
+  config: S3Config,
+  client: reqwest::Client,
+}
+
+const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+impl S3ImageStore {
+  pub fn new(config: S3Config) -> Self {
+    Self {
+      config,
+      client: reqwest::Client::new(),
+    }
+  }
+
+  fn object_path(&self, key: &str) -> anyhow::Result<String> {
+    Ok(format!(
+      "/{}/{}{}",
+      self.config.bucket,
+      self.config.prefix,
+      valid_key(key)?
+    ))
+  }
+
+  async fn send(
+    &self,
+    method: reqwest::Method,
+    key: &str,
+    body: Option<(&[u8], &str)>,
+  ) -> anyhow::Result<reqwest::Response> {
+    let path = self.object_path(key)?;
+    let url = reqwest::Url::parse(&format!(
+      "{}{path}",
+      self.config.endpoint.trim_end_matches('/')
+    ))?;
+    let host = match (url.host_str(), url.port()) {
+      (Some(host), Some(port)) => format!("{host}:{port}"),
+      (Some(host), None) => host.to_string(),
+      (None, _) => anyhow::bail!("S3_ENDPOINT has no host"),
+    };
+    let payload_hash = body.map_or(EMPTY_SHA256.to_string(), |(bytes, _)| {
+      hex::encode(Sha256::digest(bytes))
+    });
+    let now = Utc::now();
+    let mut headers = BTreeMap::from([
+      ("host".to_string(), host),
+      ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+      (
+        "x-amz-date".to_string(),
+        now.format("%Y%m%dT%H%M%SZ").to_string(),
+      ),
+    ]);
+    if let Some((_, mime)) = body {
+      headers.insert("content-type".to_string(), mime.to_string());
+    }
+    let authorization =
+      self.authorization(method.as_str(), url.path(), &headers, &payload_hash, now);
+
+    let mut request = self
+      .client
+      .request(method, url)
+      .header(reqwest::header::AUTHORIZATION, authorization);
+    for (name, value) in &headers {
+      if name != "host" {
+        request = request.header(name.as_str(), value.as_str());
+      }
+    }
+    if let Some((bytes, _)) = body {
+      request = request.body(bytes.to_vec());
+    }
+    Ok(request.send().await?)
+  }
+
+  /// The `Authorization` header for a request without a query string; `headers` are the
+  /// lower-cased headers to sign and must include `host`.
+  pub(crate) fn authorization(
+    &self,
+    method: &str,
+    path: &str,
+    headers: &BTreeMap<String, String>,
+    payload_hash: &str,
+    now: DateTime<Utc>,
+  ) -> String {
+    let date = now.format("%Y%m%d").to_string();
+    let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let scope = format!("{date}/{}/s3/aws4_request", self.config.region);
+    let signed_headers = headers.keys().cloned().collect::<Vec<_>>().join(";");
+    let canonical_headers: String = headers
+      .iter()
+      .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+      .collect();
+    let canonical_request =
+      format!("{method}\n{path}\n\n{canonical_headers}\n{signed_headers}\n{payload_hash}");
+    let string_to_sign = format!(
+      "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+      hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+    let mut key = format!("AWS4{}", self.config.secret_key).into_bytes();
+    for part in [
+      date.as_str(),
+      self.config.region.as_str(),
+      "s3",
+      "aws4_request",
+    ] {
+      key = hmac_sha256(&key, part.as_bytes());
+    }
+    let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+    format!(
+      "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+      self.config.access_key
+    )
+  }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+  let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+  mac.update(data);
+  mac.finalize().into_bytes().to_vec()
+}
+
+#[async_trait]
+impl ImageStore for S3ImageStore {
+  fn name(&self) -> &'static str {
+    "s3"
+  }
+
+  async fn put(&self, key: &str, bytes: &[u8], mime: &str) -> anyhow::Result<()> {
+    let head = self.send(reqwest::Method::HEAD, key, None).await?;
+    if head.status().is_success() {
+      return Ok(());
+    }
+    let response = self
+      .send(reqwest::Method::PUT, key, Some((bytes, mime)))
+      .await?;
+    if !response.status().is_success() {
+      anyhow::bail!("S3 PUT {key} returned {}", response.status());
+    }
+    Ok(())
+  }
+
+  async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    let response = self.send(reqwest::Method::GET, key, None).await?;
+    match response.status() {
+      reqwest::StatusCode::NOT_FOUND => Ok(None),
+      status if status.is_success() => Ok(Some(response.bytes().await?.to_vec())),
+      status => anyhow::bail!("S3 GET {key} returned {status}"),
+    }
+  }
+
+  async fn delete(&self, key: &str) -> anyhow::Result<()> {
+    let response = self.send(reqwest::Method::DELETE, key, None).await?;
+    let status = response.status();
+    if !status.is_success() && status != reqwest::StatusCode::NOT_FOUND {
+      anyhow::bail!("S3 DELETE {key} returned {status}");
+    }
+    Ok(())
+  }
+}
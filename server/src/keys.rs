@@ -0,0 +1,383 @@
+use axum::{
+  body::Bytes,
+  extract::{Path, State},
+  http::{HeaderMap, StatusCode},
+  Json,
+};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use sea_orm::{
+  ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, Set,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::entity::keys;
+use crate::{
+  bad_request, error_response, forbidden, internal_error, parse_json_body, unauthorized, AppState,
+  ErrorResponse,
+};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+const TOKEN_PREFIX: &str = "fx_";
+/// `fx_` plus 8 hex characters; stored in clear so keys can be found and shown.
+const PREFIX_LEN: usize = 11;
+const DEFAULT_SCOPES: [Scope; 2] = [Scope::Ingest, Scope::History];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+  Ingest,
+  History,
+  Admin,
+}
+
+impl Scope {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Scope::Ingest => "ingest",
+      Scope::History => "history",
+      Scope::Admin => "admin",
+    }
+  }
+
+  pub fn parse(value: &str) -> Option<Self> {
+    match value.trim().to_ascii_lowercase().as_str() {
+      "ingest" => Some(Scope::Ingest),
+      "history" => Some(Scope::History),
+      "admin" => Some(Scope::Admin),
+      _ => None,
+    }
+  }
+}
+
+pub struct ApiKey {
+  pub id: String,
+  pub user_id: String,
+  pub scopes: Vec<Scope>,
+  pub rate_limit: Option<i32>,
+}
+
+impl ApiKey {
+  pub fn has_scope(&self, scope: Scope) -> bool {
+    self.scopes.contains(&scope)
+  }
+}
+
+#[derive(Deserialize, Default)]
+struct CreateKeyRequest {
+  name: Option<String>,
+  scopes: Option<Vec<String>>,
+  expires_in_days: Option<i64>,
+  rate_limit: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct KeyInfo {
+  id: String,
+  name: Option<String>,
+  prefix: Option<String>,
+  scopes: Vec<Scope>,
+  expires_at: Option<DateTime<Utc>>,
+  last_used_at: Option<DateTime<Utc>>,
+  revoked_at: Option<DateTime<Utc>>,
+  created_at: Option<DateTime<Utc>>,
+  rate_limit: Option<i32>,
+  /// The full key, only returned once when it is created.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  key: Option<String>,
+}
+
+impl From<keys::Model> for KeyInfo {
+  fn from(model: keys::Model) -> Self {
+    Self {
+      scopes: parse_scopes(model.scopes.as_deref()),
+      id: model.id,
+      name: model.name,
+      prefix: model.prefix,
+      expires_at: model.expires_at,
+      last_used_at: model.last_used_at,
+      revoked_at: model.revoked_at,
+      created_at: model.c_time,
+      rate_limit: model.rate_limit,
+      key: None,
+    }
+  }
+}
+
+impl KeyInfo {
+  pub fn with_token(model: keys::Model, token: String) -> Self {
+    Self {
+      key: Some(token),
+      ..Self::from(model)
+    }
+  }
+}
+
+/// Resolves the bearer token to a live key, whatever its scopes.
+pub async fn authenticate(
+  db: &DatabaseConnection,
+  headers: &HeaderMap,
+) -> Result<ApiKey, ApiError> {
+  let auth = headers
+    .get(axum::http::header::AUTHORIZATION)
+    .and_then(|v| v.to_str().ok())
+    .unwrap_or("")
+    .trim()
+    .to_string();
+  let token = auth.strip_prefix("Bearer ").unwrap_or(&auth).trim();
+  if token.is_empty() {
+    return Err(unauthorized("API key was not provided", Some(100)));
+  }
+
+  let candidates = keys::Entity::find()
+    .filter(keys::Column::Prefix.eq(prefix_of(token)))
+    .all(db)
+    .await
+    .map_err(internal_error("DB error"))?;
+  let key = candidates.into_iter().find(|key| {
+    key
+      .salt
+      .as_deref()
+      .is_some_and(|salt| constant_time_eq(hash_key(salt, token).as_bytes(), key.key.as_bytes()))
+  });
+  let Some(key) = key else {
+    return Err(unauthorized("Invalid API key", Some(100)));
+  };
+  if key.revoked_at.is_some() {
+    return Err(unauthorized("API key has been revoked", Some(100)));
+  }
+  if key
+    .expires_at
+    .is_some_and(|expires_at| expires_at <= Utc::now())
+  {
+    return Err(unauthorized("API key has expired", Some(100)));
+  }
+  let Some(user_id) = key.user_id.clone() else {
+    return Err(unauthorized("Invalid API key", Some(100)));
+  };
+
+  keys::Entity::update_many()
+    .col_expr(keys::Column::LastUsedAt, Utc::now().into())
+    .filter(keys::Column::Id.eq(key.id.as_str()))
+    .exec(db)
+    .await
+    .map_err(internal_error("DB error"))?;
+
+  Ok(ApiKey {
+    id: key.id,
+    user_id,
+    scopes: parse_scopes(key.scopes.as_deref()),
+    rate_limit: key.rate_limit,
+  })
+}
+
+pub async fn require_scope(
+  db: &DatabaseConnection,
+  headers: &HeaderMap,
+  scope: Scope,
+) -> Result<ApiKey, ApiError> {
+  let key = authenticate(db, headers).await?;
+  if !key.has_scope(scope) {
+    return Err(forbidden(
+      &format!("API key is missing the `{}` scope", scope.as_str()),
+      Some(105),
+    ));
+  }
+  Ok(key)
+}
+
+/// Stores a new key for `user_id` and returns it with the plaintext token. `rate_limit`
+/// caps the key below its package's limit.
+pub async fn issue_key(
+  db: &DatabaseConnection,
+  user_id: &str,
+  name: &str,
+  scopes: &[Scope],
+  expires_at: Option<DateTime<Utc>>,
+  rate_limit: Option<i32>,
+) -> Result<(keys::Model, String), sea_orm::DbErr> {
+  let token = format!("{TOKEN_PREFIX}{}", random_hex(24));
+  let salt = random_hex(16);
+  let model = keys::ActiveModel {
+    id: Set(Uuid::new_v4().to_string()),
+    user_id: Set(Some(user_id.to_string())),
+    name: Set(Some(name.to_string())),
+    key: Set(hash_key(&salt, &token)),
+    prefix: Set(Some(prefix_of(&token))),
+    salt: Set(Some(salt)),
+    scopes: Set(Some(join_scopes(scopes))),
+    expires_at: Set(expires_at),
+    rate_limit: Set(rate_limit),
+    ..Default::default()
+  }
+  .insert(db)
+  .await?;
+  Ok((model, token))
+}
+
+pub async fn create_key(
+  State(state): State<AppState>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Result<(StatusCode, Json<KeyInfo>), ApiError> {
+  let caller = authenticate(&state.db, &headers).await?;
+  let request: CreateKeyRequest = if body.is_empty() {
+    CreateKeyRequest::default()
+  } else {
+    parse_json_body(&body)?
+  };
+
+  let scopes = match request.scopes {
+    Some(names) => {
+      let mut scopes = Vec::new();
+      for name in names {
+        let scope =
+          Scope::parse(&name).ok_or_else(|| bad_request(&format!("Unknown scope `{name}`")))?;
+        if !caller.has_scope(scope) {
+          return Err(forbidden(
+            &format!("Cannot grant the `{}` scope", scope.as_str()),
+            Some(105),
+          ));
+        }
+        if !scopes.contains(&scope) {
+          scopes.push(scope);
+        }
+      }
+      scopes
+    }
+    None => DEFAULT_SCOPES
+      .into_iter()
+      .filter(|scope| caller.has_scope(*scope))
+      .collect(),
+  };
+  if scopes.is_empty() {
+    return Err(bad_request("At least one scope is required"));
+  }
+
+  let expires_at = match request.expires_in_days {
+    Some(days) => Some(
+      chrono::Duration::try_days(days)
+        .filter(|_| days > 0)
+        .and_then(|ttl| Utc::now().checked_add_signed(ttl))
+        .ok_or_else(|| bad_request("`expires_in_days` is out of range"))?,
+    ),
+    None => None,
+  };
+  if request.rate_limit.is_some_and(|limit| limit <= 0) {
+    return Err(bad_request("`rate_limit` must be positive"));
+  }
+  let name: String = request
+    .name
+    .as_deref()
+    .map(str::trim)
+    .filter(|name| !name.is_empty())
+    .unwrap_or("default")
+    .chars()
+    .take(64)
+    .collect();
+
+  let (model, token) = issue_key(
+    &state.db,
+    &caller.user_id,
+    &name,
+    &scopes,
+    expires_at,
+    request.rate_limit,
+  )
+  .await
+  .map_err(internal_error("DB error"))?;
+  Ok((StatusCode::CREATED, Json(KeyInfo::with_token(model, token))))
+}
+
+/// Managing keys needs `history`, so an ingest-only device key cannot see or revoke the
+/// account's other keys.
+const MANAGE_SCOPE: Scope = Scope::History;
+
+pub async fn list_keys(
+  State(state): State<AppState>,
+  headers: HeaderMap,
+) -> Result<Json<Vec<KeyInfo>>, ApiError> {
+  let caller = require_scope(&state.db, &headers, MANAGE_SCOPE).await?;
+  let keys = keys::Entity::find()
+    .filter(keys::Column::UserId.eq(caller.user_id))
+    .order_by_desc(keys::Column::CTime)
+    .all(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?;
+  Ok(Json(keys.into_iter().map(KeyInfo::from).collect()))
+}
+
+pub async fn revoke_key(
+  State(state): State<AppState>,
+  headers: HeaderMap,
+  Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+  let caller = require_scope(&state.db, &headers, MANAGE_SCOPE).await?;
+  let key = keys::Entity::find_by_id(id)
+    .filter(keys::Column::UserId.eq(caller.user_id.as_str()))
+    .one(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?
+    .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "API key not found", None))?;
+  // As with `create_key`, a caller only reaches as far as its own scopes.
+  if let Some(scope) = parse_scopes(key.scopes.as_deref())
+    .into_iter()
+    .find(|scope| !caller.has_scope(*scope))
+  {
+    return Err(forbidden(
+      &format!("Cannot revoke a key with the `{}` scope", scope.as_str()),
+      Some(105),
+    ));
+  }
+  if key.revoked_at.is_none() {
+    let mut active: keys::ActiveModel = key.into();
+    active.revoked_at = Set(Some(Utc::now()));
+    active
+      .update(&state.db)
+      .await
+      .map_err(internal_error("DB error"))?;
+  }
+  Ok(StatusCode::NO_CONTENT)
+}
+
+fn parse_scopes(value: Option<&str>) -> Vec<Scope> {
+  value
+    .unwrap_or("")
+    .split(',')
+    .filter_map(Scope::parse)
+    .collect()
+}
+
+fn join_scopes(scopes: &[Scope]) -> String {
+  scopes
+    .iter()
+    .map(|scope| scope.as_str())
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+fn prefix_of(token: &str) -> String {
+  token.chars().take(PREFIX_LEN).collect()
+}
+
+fn hash_key(salt: &str, token: &str) -> String {
+  hex::encode(
+    Sha256::new()
+      .chain_update(salt)
+      .chain_update(token)
+      .finalize(),
+  )
+}
+
+pub fn random_hex(len: usize) -> String {
+  let mut bytes = vec![0u8; len];
+  rand::thread_rng().fill_bytes(&mut bytes);
+  hex::encode(bytes)
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+  a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::{
+  body::Bytes,
+  extract::State,
+  http::{HeaderMap, StatusCode},
+  Json,
+};
+use rand::Rng;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::entity::{links, users};
+use crate::keys::{authenticate, constant_time_eq, issue_key, random_hex, KeyInfo, Scope};
+use crate::{
+  error_response, internal_error, parse_json_body, unauthorized, AppState, ErrorResponse,
+};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+const MAX_FAILURES: u32 = 5;
+const LOCKOUT: Duration = Duration::from_secs(15 * 60);
+/// Codes whose lockout has passed are pruned once this many are tracked.
+const MAX_TRACKED: usize = 10_000;
+const DEVICE_SCOPES: [Scope; 2] = [Scope::Ingest, Scope::History];
+
+/// Failed PIN attempts per existing link code; a 4-digit PIN must not be guessable.
+#[derive(Default)]
+pub struct LinkAttempts {
+  failures: Mutex<HashMap<String, (u32, Instant)>>,
+}
+
+impl LinkAttempts {
+  /// How long `code` stays locked, if it is.
+  fn locked(&self, code: &str) -> Option<Duration> {
+    let mut failures = self.failures.lock().unwrap_or_else(|err| err.into_inner());
+    match failures.get(code) {
+      Some((_, since)) if since.elapsed() >= LOCKOUT => {
+        failures.remove(code);
+        None
+      }
+      Some((count, since)) if *count >= MAX_FAILURES => Some(LOCKOUT - since.elapsed()),
+      _ => None,
+    }
+  }
+
+  fn fail(&self, code: &str) {
+    let mut failures = self.failures.lock().unwrap_or_else(|err| err.into_inner());
+    if failures.len() >= MAX_TRACKED {
+      failures.retain(|_, (_, since)| since.elapsed() < LOCKOUT);
+    }
+    let entry = failures
+      .entry(code.to_string())
+      .or_insert((0, Instant::now()));
+    entry.0 += 1;
+  }
+
+  fn clear(&self, code: &str) {
+    let mut failures = self.failures.lock().unwrap_or_else(|err| err.into_inner());
+    failures.remove(code);
+  }
+}
+
+#[derive(Deserialize)]
+struct LinkRequest {
+  code: String,
+  pin: String,
+  /// Shown in `GET /keys`, e.g. the device's host name.
+  name: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct LinkResponse {
+  email: String,
+  #[serde(flatten)]
+  key: KeyInfo,
+}
+
+#[derive(Serialize)]
+pub struct LinkCode {
+  code: String,
+  pin: String,
+}
+
+/// Exchanges a link code and PIN for a new device key.
+pub async fn link_device(
+  State(state): State<AppState>,
+  body: Bytes,
+) -> Result<(StatusCode, Json<LinkResponse>), ApiError> {
+  let request: LinkRequest = parse_json_body(&body)?;
+  let code = request.code.trim().to_ascii_uppercase();
+  if let Some(wait) = state.link_attempts.locked(&code) {
+    let mut response = error_response(
+      StatusCode::TOO_MANY_REQUESTS,
+      "Too many failed attempts for this link code, try again later",
+      Some(110),
+    );
+    response.1.error.retry_after = Some(wait.as_secs().max(1));
+    return Err(response);
+  }
+
+  let found = links::Entity::find()
+    .filter(links::Column::Code.eq(code.as_str()))
+    .find_also_related(users::Entity)
+    .one(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?;
+  let invalid = || unauthorized("Invalid link code or PIN", Some(109));
+  // Only codes that exist are tracked, so made-up codes cannot grow the map.
+  let Some((link, user)) = found else {
+    return Err(invalid());
+  };
+  let pin = link.pin.as_deref().unwrap_or("");
+  if pin.is_empty() || !constant_time_eq(pin.as_bytes(), request.pin.trim().as_bytes()) {
+    state.link_attempts.fail(&code);
+    return Err(invalid());
+  }
+  let Some(user) = user else {
+    return Err(invalid());
+  };
+  state.link_attempts.clear(&code);
+
+  let name: String = request
+    .name
+    .as_deref()
+    .map(str::trim)
+    .filter(|name| !name.is_empty())
+    .unwrap_or("device")
+    .chars()
+    .take(64)
+    .collect();
+  let (model, token) = issue_key(&state.db, &user.id, &name, &DEVICE_SCOPES, None, None)
+    .await
+    .map_err(internal_error("DB error"))?;
+  Ok((
+    StatusCode::CREATED,
+    Json(LinkResponse {
+      email: user.email,
+      key: KeyInfo::with_token(model, token),
+    }),
+  ))
+}
+
+/// Returns the caller's link code and PIN, creating them on first use.
+pub async fn get_link_code(
+  State(state): State<AppState>,
+  headers: HeaderMap,
+) -> Result<Json<LinkCode>, ApiError> {
+  let caller = authenticate(&state.db, &headers).await?;
+  let existing = links::Entity::find()
+    .filter(links::Column::UserId.eq(caller.user_id.as_str()))
+    .one(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?;
+  let link = match existing {
+    Some(link) => link,
+    None => links::ActiveModel {
+      id: Set(Uuid::new_v4().to_string()),
+      user_id: Set(Some(caller.user_id)),
+      code: Set(Some(format!("FX-{}", random_hex(5).to_ascii_uppercase()))),
+      pin: Set(Some(format!(
+        "{:04}",
+        rand::thread_rng().gen_range(0..10_000)
+      ))),
+      ..Default::default()
+    }
+    .insert(&state.db)
+    .await
+    .map_err(internal_error("DB error"))?,
+  };
+  Ok(Json(LinkCode {
+    code: link.code.unwrap_or_default(),
+    pin: link.pin.unwrap_or_default(),
+  }))
+}
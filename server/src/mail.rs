@@ -0,0 +1,88 @@
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use uuid::Uuid;
+
+pub struct Mail {
+  pub to: String,
+  pub subject: String,
+  pub body: String,
+}
+
+#[async_trait::async_trait]
+pub trait MailSender: Send + Sync {
+  async fn send(&self, mail: &Mail) -> anyhow::Result<()>;
+}
+
+/// Prints every mail to stdout; the default for local development.
+pub struct StdoutMailer {
+  from: String,
+}
+
+/// Writes every mail as an `.eml` file into `dir`.
+pub struct FileMailer {
+  from: String,
+  dir: PathBuf,
+}
+
+impl FileMailer {
+  pub fn new(from: String, dir: PathBuf) -> Self {
+    Self { from, dir }
+  }
+}
+
+#[async_trait::async_trait]
+impl MailSender for StdoutMailer {
+  async fn send(&self, mail: &Mail) -> anyhow::Result<()> {
+    println!(
+      "----- mail -----\n{}\n----------------",
+      render(&self.from, mail)
+    );
+    Ok(())
+  }
+}
+
+#[async_trait::async_trait]
+impl MailSender for FileMailer {
+  async fn send(&self, mail: &Mail) -> anyhow::Result<()> {
+    tokio::fs::create_dir_all(&self.dir)
+      .await
+      .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+    let name = format!(
+      "{}-{}.eml",
+      chrono::Utc::now().format("%Y%m%d%H%M%S"),
+      Uuid::new_v4()
+    );
+    let path = self.dir.join(name);
+    tokio::fs::write(&path, render(&self.from, mail))
+      .await
+      .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+  }
+}
+
+/// Picks the sender from `MAIL_SENDER` (`stdout` or `file`, written to `MAIL_DIR`).
+pub fn from_env() -> anyhow::Result<Arc<dyn MailSender>> {
+  let from = env::var("MAIL_FROM").unwrap_or_else(|_| "Faux <no-reply@localhost>".to_string());
+  let kind = env::var("MAIL_SENDER").unwrap_or_else(|_| "stdout".to_string());
+  match kind.trim().to_ascii_lowercase().as_str() {
+    "stdout" | "" => Ok(Arc::new(StdoutMailer { from })),
+    "file" => {
+      let dir = env::var("MAIL_DIR").unwrap_or_else(|_| "data/mail".to_string());
+      Ok(Arc::new(FileMailer::new(from, dir.into())))
+    }
+    other => anyhow::bail!("Unknown MAIL_SENDER `{other}` (expected stdout or file)"),
+  }
+}
+
+fn render(from: &str, mail: &Mail) -> String {
+  format!(
+    "From: {from}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+    mail.to,
+    mail.subject,
+    chrono::Utc::now().to_rfc2822(),
+    mail.body
+  )
+}
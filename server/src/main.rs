@@ -1,72 +1,74 @@
 use std::env;
 
 use axum::{
-  extract::{Multipart, State},
+  extract::{DefaultBodyLimit, Multipart, State},
   http::Request,
   middleware::{self, Next},
   http::StatusCode,
   response::Response,
   response::IntoResponse,
-  routing::{get, post},
+  routing::{delete, get, post},
   Json, Router,
 };
 use axum::response::sse::{Event, Sse};
-use base64::Engine as _;
+use sea_orm::sea_query::OnConflict;
 use sea_orm::{
-  ActiveModelTrait, ConnectionTrait, Database, DatabaseBackend, DatabaseConnection, EntityTrait,
-  Set, Statement, Value,
+  ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, Database, DatabaseBackend,
+  DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, Set, TransactionTrait,
 };
 use sea_orm_migration::migrator::MigratorTrait;
 use serde::{Deserialize, Serialize};
-use futures_util::StreamExt;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use uuid::Uuid;
 use std::time::Instant;
 use std::convert::Infallible;
+use std::collections::HashMap;
+use std::sync::Arc;
 
+use keys::Scope;
+use models::{ModelEntry, ModelInfo, ModelRegistry};
+use provider::{Turn, Usage, VisionOutput, VisionRequest};
+
+mod account;
+mod admin;
+mod auth;
+mod credits;
 mod entity;
+mod follow_up;
+mod history;
+mod image_store;
+mod keys;
+mod link;
+mod mail;
+mod mock_upstream;
+mod models;
+mod payments;
+mod provider;
+mod rate_limit;
+mod settings;
+#[cfg(test)]
+mod tests;
+mod upload;
 
 #[derive(Clone)]
 struct AppState {
-  client: reqwest::Client,
-  openai_api_key: String,
-  default_model: ModelChoice,
+  models: Arc<ModelRegistry>,
   system_prompt: String,
   user_prompt: String,
   stream_prompt: String,
+  images: Arc<dyn image_store::ImageStore>,
   db: DatabaseConnection,
+  mailer: Arc<dyn mail::MailSender>,
+  /// Base URL used in links sent by mail.
+  public_url: String,
+  link_attempts: Arc<link::LinkAttempts>,
+  rate_limiter: Arc<rate_limit::RateLimiter>,
+  /// `None` when `PAYMENT_PROVIDER` is unset; the webhook then answers 404.
+  payments: Option<Arc<dyn payments::PaymentProvider>>,
+  upload: upload::UploadConfig,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum ModelChoice {
-  Gpt52,
-  Gpt5Mini,
-  Gpt5Nano,
-  Gpt4oMini,
-}
-
-impl ModelChoice {
-  fn as_str(self) -> &'static str {
-    match self {
-      ModelChoice::Gpt52 => "gpt-5.2",
-      ModelChoice::Gpt5Mini => "gpt-5-mini",
-      ModelChoice::Gpt5Nano => "gpt-5-nano",
-      ModelChoice::Gpt4oMini => "gpt-4o-mini",
-    }
-  }
-
-  fn parse(value: &str) -> Option<Self> {
-    match value.trim().to_lowercase().as_str() {
-      "gpt-5.2" => Some(ModelChoice::Gpt52),
-      "gpt-5-mini" => Some(ModelChoice::Gpt5Mini),
-      "gpt-5-nano" => Some(ModelChoice::Gpt5Nano),
-      "gpt-4o-mini" => Some(ModelChoice::Gpt4oMini),
-      _ => None,
-    }
-  }
-}
-
-#[derive(Serialize)]
+#[derive(Serialize, Debug)]
 struct ErrorResponse {
   error: ErrorDetail,
 }
@@ -75,8 +77,28 @@ struct ErrorResponse {
 struct ErrorDetail {
   code: i32,
   message: String,
+  /// Seconds until a rate-limited request may be retried; mirrored in `Retry-After`.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  retry_after: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct ModelsResponse {
+  default: String,
+  models: Vec<ModelInfo>,
 }
 
+struct ActiveSubscription {
+  id: i64,
+  credits: i64,
+  package: Option<String>,
+  rate_limit: i32,
+  expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Response header with the `screen_results` id of a capture, for `/history/:id/follow_up`.
+const CAPTURE_ID_HEADER: &str = "x-capture-id";
+
 #[derive(Serialize, Deserialize, Clone)]
 struct IngestResponse {
   text: String,
@@ -91,27 +113,6 @@ struct StreamEnvelope {
   error: Option<ErrorDetail>,
 }
 
-#[derive(Deserialize)]
-struct OpenAiResponse {
-  output: Vec<OpenAiOutput>,
-}
-
-#[derive(Deserialize)]
-  struct OpenAiOutput {
-  #[serde(rename = "type")]
-  r#type: String,
-  content: Option<Vec<OpenAiContent>>,
-  name: Option<String>,
-  arguments: Option<String>,
-}
-
-#[derive(Deserialize)]
-struct OpenAiContent {
-  #[serde(rename = "type")]
-  r#type: String,
-  text: Option<String>,
-}
-
 #[derive(Deserialize, Serialize)]
 struct ToolResult {
   text: String,
@@ -128,6 +129,15 @@ async fn main() -> anyhow::Result<()> {
   let run_migrations_only = args.iter().any(|arg| arg == "migrate");
   let run_seed_only = args.iter().any(|arg| arg == "seed");
   let run_reset = args.iter().any(|arg| arg == "reset");
+  let run_purge_images = args.iter().any(|arg| arg == "purge-images");
+
+  if args.iter().any(|arg| arg == "mock-upstream") {
+    let addr = env::var("MOCK_UPSTREAM_ADDR").unwrap_or_else(|_| "127.0.0.1:3999".to_string());
+    let dir = env::var("MOCK_UPSTREAM_FIXTURES")
+      .unwrap_or_else(|_| "server/fixtures/upstream".to_string());
+    let fixtures = mock_upstream::Fixtures::load(std::path::Path::new(&dir))?;
+    return mock_upstream::serve(&addr, fixtures).await;
+  }
 
   let database_url = env::var("DATABASE_URL").unwrap_or_else(|_| {
     let host = env::var("DATABASE_HOST").unwrap_or_else(|_| "localhost".to_string());
@@ -143,11 +153,8 @@ async fn main() -> anyhow::Result<()> {
       format!("mysql://{user}:{password}@{host}:{port}/{name}")
     }
   });
-  let openai_api_key = env::var("OPENAI_API_KEY").unwrap_or_default();
-  let default_model = env::var("OPENAI_MODEL")
-    .ok()
-    .and_then(|value| ModelChoice::parse(&value))
-    .unwrap_or(ModelChoice::Gpt5Mini);
+  let client = reqwest::Client::new();
+  let models = ModelRegistry::load(&client)?;
   let system_prompt = env::var("OPENAI_SYSTEM_PROMPT").unwrap_or_else(|_| {
     "You are a senior software engineer and technical instructor. \
 You explain solutions like a professor: precise, methodical, and highly detailed, \
@@ -185,9 +192,8 @@ Ignore any irrelevant UI like tabs, taskbars, start menus, docks, or unrelated w
     Err(err) => {
       if run_reset && err.to_string().contains("Unknown database") {
         let server_url = database_url
-          .rsplitn(2, '/')
-          .nth(1)
-          .unwrap_or(&database_url)
+          .rsplit_once('/')
+          .map_or(database_url.as_str(), |(head, _)| head)
           .to_string();
         Database::connect(&server_url).await?
       } else {
@@ -196,24 +202,22 @@ Ignore any irrelevant UI like tabs, taskbars, start menus, docks, or unrelated w
     }
   };
   if run_reset {
-    if let Some(name) = db_name.as_deref() {
-      reset_database(&db, name).await?;
-      let db_url = database_url_with_db(&database_url, name);
-      let db_with_name = Database::connect(&db_url).await?;
-      ensure_database_charset(&db_with_name, name).await?;
-      ensure_default_storage_engine(&db_with_name).await?;
-      ensure_migrations_table(&db_with_name).await?;
-      migration::Migrator::up(&db_with_name, None).await?;
+    if db.get_database_backend() == DatabaseBackend::MySql {
+      if let Some(name) = db_name.as_deref() {
+        reset_database(&db, name).await?;
+        let db_url = database_url_with_db(&database_url, name);
+        let db_with_name = Database::connect(&db_url).await?;
+        prepare_database(&db_with_name, Some(name)).await?;
+        migration::Migrator::up(&db_with_name, None).await?;
+      }
+    } else {
+      migration::Migrator::fresh(&db).await?;
     }
     eprintln!("Database reset and migrations applied.");
     return Ok(());
   }
   if run_migrations_only {
-    if let Some(name) = db_name.as_deref() {
-      ensure_database_charset(&db, name).await?;
-    }
-    ensure_default_storage_engine(&db).await?;
-    ensure_migrations_table(&db).await?;
+    prepare_database(&db, db_name.as_deref()).await?;
     migration::Migrator::up(&db, None).await?;
     eprintln!("Migrations applied.");
     return Ok(());
@@ -225,25 +229,35 @@ Ignore any irrelevant UI like tabs, taskbars, start menus, docks, or unrelated w
     eprintln!("Seed data applied.");
     return Ok(());
   }
+  if run_purge_images {
+    let retention = image_store::retention_from_env()?
+      .ok_or_else(|| anyhow::anyhow!("Set IMAGE_RETENTION_DAYS to purge images"))?;
+    let store = image_store::from_env()?;
+    let deleted = image_store::purge_expired(&db, store.as_ref(), retention).await?;
+    eprintln!("Purged {deleted} images from the {} store.", store.name());
+    return Ok(());
+  }
 
   let state = AppState {
-    client: reqwest::Client::new(),
-    openai_api_key,
-    default_model,
+    models: Arc::new(models),
     system_prompt,
     user_prompt,
     stream_prompt,
+    images: image_store::from_env()?,
     db,
+    mailer: mail::from_env()?,
+    public_url: env::var("PUBLIC_URL")
+      .unwrap_or_else(|_| "http://localhost:3005".to_string())
+      .trim_end_matches('/')
+      .to_string(),
+    link_attempts: Arc::default(),
+    rate_limiter: Arc::default(),
+    payments: payments::from_env()?,
+    upload: upload::UploadConfig::from_env()?,
   };
-
-  let app = Router::new()
-    .route("/healthz", get(health))
-    .route("/ingest", post(ingest))
-    .route("/ingest_stream", post(ingest_stream))
-    .fallback(fallback_404)
-    .layer(middleware::from_fn(method_not_allowed))
-    .with_state(state)
-    .layer(middleware::from_fn(log_requests));
+  if let Some(retention) = image_store::retention_from_env()? {
+    image_store::spawn_retention(state.db.clone(), state.images.clone(), retention);
+  }
 
   let addr = env::var("SERVER_ADDR").unwrap_or_else(|_| "0.0.0.0:3005".to_string());
   eprintln!(
@@ -251,20 +265,66 @@ Ignore any irrelevant UI like tabs, taskbars, start menus, docks, or unrelated w
     sanitize_db_url(&database_url)
   );
   let listener = tokio::net::TcpListener::bind(&addr).await?;
-  axum::serve(listener, app).await?;
+  axum::serve(listener, app(state)).await?;
 
   Ok(())
 }
 
+fn app(state: AppState) -> Router {
+  let upload_limit = DefaultBodyLimit::max(state.upload.max_bytes);
+  Router::new()
+    .route("/healthz", get(health))
+    .route("/models", get(list_models))
+    .route("/auth/register", post(auth::register))
+    .route("/auth/login", post(auth::login))
+    .route("/auth/confirm", get(auth::confirm_link).post(auth::confirm))
+    .route("/auth/reset", post(auth::reset))
+    .route("/me", get(account::me).patch(account::update_me))
+    .route("/link", get(link::get_link_code).post(link::link_device))
+    .route("/settings", get(settings::get_settings).put(settings::put_settings))
+    .route("/keys", get(keys::list_keys).post(keys::create_key))
+    .route("/keys/:id", delete(keys::revoke_key))
+    .route("/history", get(history::list_history))
+    .route("/history/:id", get(history::get_history))
+    .route("/history/:id/follow_up", post(follow_up::follow_up))
+    .route("/payments/webhook", post(payments::webhook))
+    .nest("/admin", admin::router(state.clone()))
+    .route("/ingest", post(ingest).layer(upload_limit))
+    .route("/ingest_stream", post(ingest_stream).layer(upload_limit))
+    .fallback(fallback_404)
+    .layer(middleware::from_fn(method_not_allowed))
+    .layer(middleware::from_fn(retry_after_header))
+    .with_state(state)
+    .layer(middleware::from_fn(log_requests))
+}
+
 async fn health() -> impl IntoResponse {
   StatusCode::OK
 }
 
+async fn list_models(
+  State(state): State<AppState>,
+  headers: axum::http::HeaderMap,
+) -> Result<Json<ModelsResponse>, (StatusCode, Json<ErrorResponse>)> {
+  let mut package = None;
+  if headers.contains_key(axum::http::header::AUTHORIZATION) {
+    let key = keys::authenticate(&state.db, &headers).await?;
+    package = active_subscription(&state.db, &key.user_id)
+      .await?
+      .and_then(|subscription| subscription.package);
+  }
+  Ok(Json(ModelsResponse {
+    default: state.models.default_name().to_string(),
+    models: state.models.list(package.as_deref()),
+  }))
+}
+
 async fn fallback_404() -> impl IntoResponse {
   let body = Json(ErrorResponse {
     error: ErrorDetail {
       code: StatusCode::NOT_FOUND.as_u16() as i32,
       message: "HTTP ERROR 404 Not Found".to_string(),
+      retry_after: None,
     },
   });
   (StatusCode::NOT_FOUND, body)
@@ -283,11 +343,33 @@ async fn method_not_allowed(
     error: ErrorDetail {
       code: StatusCode::METHOD_NOT_ALLOWED.as_u16() as i32,
       message: format!("HTTP ERROR 405 Method Not Allowed ({method})"),
+      retry_after: None,
     },
   });
   (StatusCode::METHOD_NOT_ALLOWED, body).into_response()
 }
 
+/// Copies `error.retry_after` of 429 responses into the `Retry-After` header.
+async fn retry_after_header(req: Request<axum::body::Body>, next: Next) -> Response {
+  let response = next.run(req).await;
+  if response.status() != StatusCode::TOO_MANY_REQUESTS {
+    return response;
+  }
+  let (mut parts, body) = response.into_parts();
+  let Ok(bytes) = axum::body::to_bytes(body, 64 * 1024).await else {
+    return (StatusCode::TOO_MANY_REQUESTS, parts.headers).into_response();
+  };
+  let retry_after = serde_json::from_slice::<serde_json::Value>(&bytes)
+    .ok()
+    .and_then(|body| body["error"]["retry_after"].as_u64());
+  if let Some(seconds) = retry_after {
+    parts
+      .headers
+      .insert(axum::http::header::RETRY_AFTER, seconds.into());
+  }
+  Response::from_parts(parts, axum::body::Body::from(bytes))
+}
+
 async fn log_requests(req: Request<axum::body::Body>, next: Next) -> Response {
   let method = req.method().clone();
   let uri = req.uri().clone();
@@ -308,66 +390,59 @@ async fn ingest(
   State(state): State<AppState>,
   headers: axum::http::HeaderMap,
   mut multipart: Multipart,
-) -> Result<Json<IngestResponse>, (StatusCode, Json<ErrorResponse>)> {
-  let mut image_bytes: Option<Vec<u8>> = None;
-  let mut image_mime = "image/png".to_string();
-
-  while let Some(field) = multipart
-    .next_field()
-    .await
-    .map_err(internal_error("Failed to read multipart"))?
-  {
-    if field.name() == Some("file") {
-      if let Some(content_type) = field.content_type() {
-        image_mime = content_type.to_string();
-      }
-      let data = field
-        .bytes()
-        .await
-        .map_err(internal_error("Failed to read upload bytes"))?;
-      image_bytes = Some(data.to_vec());
-      break;
-    }
-  }
-
-  let image_bytes =
-    image_bytes.ok_or_else(|| bad_request("Missing `file` field in multipart"))?;
+) -> Result<([(&'static str, String); 1], Json<IngestResponse>), (StatusCode, Json<ErrorResponse>)>
+{
+  let upload::IngestForm { images, prompt } = upload::read_form(&mut multipart).await?;
 
-  let user_id = require_user_id(&state.db, &headers).await?;
-  let subscription_id = require_subscription(&state.db, &user_id).await?;
-  let model = select_model(&headers, &state);
+  let key = keys::require_scope(&state.db, &headers, Scope::Ingest).await?;
+  let user_id = key.user_id.clone();
+  let subscription = require_subscription(&state.db, &user_id).await?;
+  state.rate_limiter.check(&key, subscription.rate_limit)?;
+  let model = select_model(&headers, &state, &subscription)?;
+  let uploads = normalize_uploads(&state, &model, images).await?;
+  let reservation =
+    credits::reserve(&state.db, &user_id, subscription.id, model.spec.cost).await?;
 
   eprintln!(
-    "Ingest start user_id={} bytes={} mime={}",
+    "Ingest start user_id={} images={} bytes={}",
     user_id,
-    image_bytes.len(),
-    image_mime
+    uploads.len(),
+    uploads.iter().map(|upload| upload.bytes.len()).sum::<usize>()
   );
 
-  let file_name = save_image(&image_bytes, &image_mime).map_err(internal_error("Save image failed"))?;
-  let record_id = insert_screen_result(&state.db, Some(&user_id), &file_name).await;
+  let SavedCapture {
+    id: record_id,
+    file_names,
+    reservation,
+  } = save_capture(&state, &user_id, &uploads, prompt.as_deref(), &model.spec.name, reservation)
+    .await?;
 
-  match call_openai(&state, &image_bytes, &image_mime, model.as_str()).await {
-    Ok((response, raw_output)) => {
+  match call_model(&state, &uploads, prompt.as_deref(), &model).await {
+    Ok((response, raw_output, usage)) => {
       let debug_json = serde_json::json!({
         "response": response.clone(),
         "raw": raw_output
       });
-      decrement_subscription(&state.db, subscription_id).await?;
+      let charged = charge_credits(reservation, &record_id, &model, usage.as_ref()).await;
       update_screen_result(
         &state.db,
         &record_id,
         "DONE",
         &debug_json,
+        usage,
+        charged,
       )
       .await;
       eprintln!(
-        "Ingest success user_id={} record_id={} file_name={}",
-        user_id, record_id, file_name
+        "Ingest success user_id={} record_id={} file_names={:?}",
+        user_id, record_id, file_names
       );
-      Ok(Json(response))
+      Ok(([(CAPTURE_ID_HEADER, record_id)], Json(response)))
     }
     Err((status, body)) => {
+      if let Err(err) = reservation.refund(Some(&record_id), &body.error.message).await {
+        eprintln!("Failed to refund credits record_id={record_id}: {err}");
+      }
       let debug_json = serde_json::json!({
         "status": status.as_u16(),
         "error": body.error.clone()
@@ -377,6 +452,8 @@ async fn ingest(
         &record_id,
         "ERROR",
         &debug_json,
+        None,
+        None,
       )
       .await;
       eprintln!(
@@ -395,87 +472,78 @@ async fn ingest_stream(
   State(state): State<AppState>,
   headers: axum::http::HeaderMap,
   mut multipart: Multipart,
-) -> Result<Sse<UnboundedReceiverStream<Result<Event, Infallible>>>, (StatusCode, Json<ErrorResponse>)>
-{
-  let mut image_bytes: Option<Vec<u8>> = None;
-  let mut image_mime = "image/png".to_string();
-
-  while let Some(field) = multipart
-    .next_field()
-    .await
-    .map_err(internal_error("Failed to read multipart"))?
-  {
-    if field.name() == Some("file") {
-      if let Some(content_type) = field.content_type() {
-        image_mime = content_type.to_string();
-      }
-      let data = field
-        .bytes()
-        .await
-        .map_err(internal_error("Failed to read upload bytes"))?;
-      image_bytes = Some(data.to_vec());
-      break;
-    }
+) -> Result<
+  ([(&'static str, String); 1], Sse<UnboundedReceiverStream<Result<Event, Infallible>>>),
+  (StatusCode, Json<ErrorResponse>),
+> {
+  let upload::IngestForm { images, prompt } = upload::read_form(&mut multipart).await?;
+
+  let key = keys::require_scope(&state.db, &headers, Scope::Ingest).await?;
+  let user_id = key.user_id.clone();
+  let subscription = require_subscription(&state.db, &user_id).await?;
+  state.rate_limiter.check(&key, subscription.rate_limit)?;
+  let model = select_model(&headers, &state, &subscription)?;
+  if !model.spec.streaming {
+    return Err(bad_request_code(
+      &format!("Model `{}` does not support streaming", model.spec.name),
+      104,
+    ));
   }
-
-  let image_bytes =
-    image_bytes.ok_or_else(|| bad_request("Missing `file` field in multipart"))?;
-
-  let user_id = require_user_id(&state.db, &headers).await?;
-  let subscription_id = require_subscription(&state.db, &user_id).await?;
-  let model = select_model(&headers, &state);
-
-  let file_name = save_image(&image_bytes, &image_mime).map_err(internal_error("Save image failed"))?;
-  let record_id = insert_screen_result(&state.db, Some(&user_id), &file_name).await;
+  let uploads = normalize_uploads(&state, &model, images).await?;
+  let reservation =
+    credits::reserve(&state.db, &user_id, subscription.id, model.spec.cost).await?;
+
+  let SavedCapture {
+    id: record_id,
+    reservation,
+    ..
+  } = save_capture(&state, &user_id, &uploads, prompt.as_deref(), &model.spec.name, reservation)
+    .await?;
 
   let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<Result<Event, Infallible>>();
   let state_clone = state.clone();
+  let capture_id = record_id.clone();
 
   tokio::spawn(async move {
     let mut full_text = String::new();
-    let stream_result = call_openai_stream(
+    let answer = call_model_stream(
       &state_clone,
-      &image_bytes,
-      &image_mime,
-      model.as_str(),
+      &uploads,
+      prompt.as_deref(),
+      &[],
+      &model,
       |delta| {
       full_text.push_str(delta);
-      let payload = StreamEnvelope {
-        kind: "delta".to_string(),
-        data: Some(delta.to_string()),
-        error: None,
-      };
-      let _ =
-        tx.send(Ok(Event::default().data(serde_json::to_string(&payload).unwrap_or_default())));
-    })
-    .await;
+      send_event(&tx, "delta", Some(delta.to_string()), None);
+    });
+    // Stop the upstream call once the client hangs up, so the capture is refunded.
+    let stream_result = tokio::select! {
+      result = answer => result,
+      () = tx.closed() => Err(client_closed()),
+    };
 
     match stream_result {
-      Ok(()) => {
+      Ok(usage) => {
         let final_text = sanitize_stream_text(&full_text);
-        let response = IngestResponse {
-          text: final_text.clone(),
-          code: String::new(),
-        };
         let debug_json = serde_json::json!({
-          "text": final_text
+          "text": final_text.clone()
         });
-        let _ = decrement_subscription(&state_clone.db, subscription_id).await;
+        let charged = charge_credits(reservation, &record_id, &model, usage.as_ref()).await;
         update_screen_result(
           &state_clone.db,
           &record_id,
           "DONE",
           &debug_json,
+          usage,
+          charged,
         )
         .await;
-        let payload = StreamEnvelope {
-          kind: "done".to_string(),
-          data: Some(response.text.clone()),
-          error: None,
-        };
-        let _ = tx.send(Ok(Event::default().data(serde_json::to_string(&payload).unwrap_or_default())));
+        send_event(&tx, "done", Some(final_text), None);
       }
       Err((status, body)) => {
+        if let Err(err) = reservation.refund(Some(&record_id), &body.error.message).await {
+          eprintln!("Failed to refund credits record_id={record_id}: {err}");
+        }
         let debug_json = serde_json::json!({
           "status": status.as_u16(),
           "error": body.error.clone()
@@ -485,122 +553,92 @@ async fn ingest_stream(
           &record_id,
           "ERROR",
           &debug_json,
+          None,
+          None,
         )
         .await;
-        let payload = StreamEnvelope {
-          kind: "error".to_string(),
-          data: None,
-          error: Some(body.error.clone()),
-        };
-        let _ = tx.send(Ok(Event::default().data(serde_json::to_string(&payload).unwrap_or_default())));
+        send_event(&tx, "error", None, Some(body.0.error));
       }
     }
   });
 
   let stream = UnboundedReceiverStream::new(rx);
-  Ok(Sse::new(stream))
+  Ok(([(CAPTURE_ID_HEADER, capture_id)], Sse::new(stream)))
 }
 
-async fn call_openai(
-  state: &AppState,
-  image_bytes: &[u8],
-  image_mime: &str,
-  model: &str,
-) -> Result<(IngestResponse, String), (StatusCode, Json<ErrorResponse>)> {
-  let encoded = base64::engine::general_purpose::STANDARD.encode(image_bytes);
-  let image_url = format!("data:{image_mime};base64,{encoded}");
-
-  let body = serde_json::json!({
-    "model": model,
-    "input": [
-      {
-        "role": "system",
-        "content": [
-          { "type": "input_text", "text": state.system_prompt }
-        ]
-      },
-      {
-        "role": "user",
-        "content": [
-          { "type": "input_text", "text": state.user_prompt },
-          { "type": "input_image", "image_url": image_url }
-        ]
-      }
-    ],
-    "tools": [
-      {
-        "type": "function",
-        "name": "submit_solution",
-        "description": "Return the final solution for the screenshot as structured data.",
-        "parameters": {
-          "type": "object",
-          "additionalProperties": false,
-          "properties": {
-            "language": { "type": "string" },
-            "text": { "type": "string", "description": "Markdown explanation with step-by-step solution." },
-            "code": { "type": "string", "description": "Code snippet(s) without markdown fences." }
-          },
-          "required": ["language", "text", "code"]
-        },
-        "strict": true
-      }
-    ],
-    "tool_choice": { "type": "function", "name": "submit_solution" }
-  });
+/// The client hung up before the answer was complete (499, as nginx logs it).
+fn client_closed() -> (StatusCode, Json<ErrorResponse>) {
+  let status = StatusCode::from_u16(499).unwrap_or(StatusCode::BAD_REQUEST);
+  error_response(status, "Client closed the request", None)
+}
 
-  let response = state
-    .client
-    .post("https://api.openai.com/v1/responses")
-    .bearer_auth(&state.openai_api_key)
-    .json(&body)
-    .send()
-    .await
-    .map_err(internal_error("OpenAI request failed"))?;
+/// Sends one `StreamEnvelope` of `kind` down an SSE stream; a client that left is ignored.
+fn send_event(
+  tx: &tokio::sync::mpsc::UnboundedSender<Result<Event, Infallible>>,
+  kind: &str,
+  data: Option<String>,
+  error: Option<ErrorDetail>,
+) {
+  let payload = StreamEnvelope {
+    kind: kind.to_string(),
+    data,
+    error,
+  };
+  let _ = tx.send(Ok(
+    Event::default().data(serde_json::to_string(&payload).unwrap_or_default()),
+  ));
+}
 
-  if !response.status().is_success() {
-    let status = response.status();
-    let body = response.text().await.unwrap_or_default();
-    return Err(error_response(
-      StatusCode::BAD_GATEWAY,
-      &format!("OpenAI error: {status} {body}"),
-      None,
-    ));
+async fn call_model(
+  state: &AppState,
+  images: &[upload::Upload],
+  note: Option<&str>,
+  model: &ModelEntry,
+) -> Result<(IngestResponse, String, Option<Usage>), (StatusCode, Json<ErrorResponse>)> {
+  if !model.spec.tools {
+    return call_model_markdown(state, images, note, model).await;
   }
+  let request = VisionRequest {
+    model: model.spec.upstream_name(),
+    system_prompt: &state.system_prompt,
+    user_prompt: &state.user_prompt,
+    user_note: note,
+    images,
+    turns: &[],
+  };
 
-  let api: OpenAiResponse = response
-    .json()
-    .await
-    .map_err(internal_error("Invalid OpenAI JSON response"))?;
-  if let Some(tool) = extract_tool_call(&api) {
-    let raw = serde_json::to_string(&tool).unwrap_or_default();
-    let mut parsed = IngestResponse {
-      text: tool.text,
-      code: tool.code,
-    };
-    let mut lang = tool.language.trim().to_string();
-    if lang.is_empty() {
-      if let Some(found) = extract_fenced_language(&parsed.text) {
-        lang = found;
+  let (output, usage) = model.provider.solve(&request).await?;
+  let output_text = match output {
+    VisionOutput::Tool(tool) => {
+      let raw = serde_json::to_string(&tool).unwrap_or_default();
+      let mut parsed = IngestResponse {
+        text: tool.text,
+        code: tool.code,
+      };
+      let mut lang = tool.language.trim().to_string();
+      if lang.is_empty() {
+        if let Some(found) = extract_fenced_language(&parsed.text) {
+          lang = found;
+        }
       }
+      if lang.is_empty() {
+        lang = infer_language(&parsed.code, &parsed.text).to_string();
+      }
+      if lang.is_empty() {
+        lang = "text".to_string();
+      }
+      if !lang.is_empty() {
+        parsed.text = ensure_fenced_language(&parsed.text, &lang);
+        parsed.text = ensure_fenced_block(&parsed.text, &parsed.code, &lang);
+      } else {
+        parsed.text = ensure_fenced_block(&parsed.text, &parsed.code, "text");
+      }
+      normalize_response(&mut parsed);
+      return Ok((parsed, raw, usage));
     }
-    if lang.is_empty() {
-      lang = infer_language(&parsed.code, &parsed.text).to_string();
-    }
-    if lang.is_empty() {
-      lang = "text".to_string();
-    }
-    if !lang.is_empty() {
-      parsed.text = ensure_fenced_language(&parsed.text, &lang);
-      parsed.text = ensure_fenced_block(&parsed.text, &parsed.code, &lang);
-    } else {
-      parsed.text = ensure_fenced_block(&parsed.text, &parsed.code, "text");
-    }
-    normalize_response(&mut parsed);
-    return Ok((parsed, raw));
-  }
+    VisionOutput::Text(text) => text,
+  };
 
-  let output_text =
-    extract_output_text(&api).ok_or_else(|| bad_gateway("Missing OpenAI output"))?;
   let mut parsed = serde_json::from_str::<IngestResponse>(&output_text).map_err(|err| {
     error_response(
       StatusCode::BAD_GATEWAY,
@@ -610,130 +648,55 @@ async fn call_openai(
   })?;
 
   normalize_response(&mut parsed);
-  Ok((parsed, output_text))
+  Ok((parsed, output_text, usage))
 }
 
-async fn call_openai_stream<F>(
+/// Models without tool support answer in Markdown; this streams the answer and
+/// converts it into an `IngestResponse` the same way `/ingest_stream` does.
+async fn call_model_markdown(
   state: &AppState,
-  image_bytes: &[u8],
-  image_mime: &str,
-  model: &str,
-  mut on_delta: F,
-) -> Result<(), (StatusCode, Json<ErrorResponse>)>
-where
-  F: FnMut(&str),
-{
-  let encoded = base64::engine::general_purpose::STANDARD.encode(image_bytes);
-  let image_url = format!("data:{image_mime};base64,{encoded}");
-
-  let body = serde_json::json!({
-    "model": model,
-    "stream": true,
-    "input": [
-      {
-        "role": "system",
-        "content": [
-          { "type": "input_text", "text": state.system_prompt }
-        ]
-      },
-      {
-        "role": "user",
-        "content": [
-          { "type": "input_text", "text": state.stream_prompt },
-          { "type": "input_image", "image_url": image_url }
-        ]
-      }
-    ]
-  });
-
-  let response = state
-    .client
-    .post("https://api.openai.com/v1/responses")
-    .bearer_auth(&state.openai_api_key)
-    .json(&body)
-    .send()
-    .await
-    .map_err(internal_error("OpenAI request failed"))?;
-
-  if !response.status().is_success() {
-    let status = response.status();
-    let body = response.text().await.unwrap_or_default();
-    return Err(error_response(
-      StatusCode::BAD_GATEWAY,
-      &format!("OpenAI error: {status} {body}"),
-      None,
-    ));
+  images: &[upload::Upload],
+  note: Option<&str>,
+  model: &ModelEntry,
+) -> Result<(IngestResponse, String, Option<Usage>), (StatusCode, Json<ErrorResponse>)> {
+  let mut full_text = String::new();
+  let usage = call_model_stream(state, images, note, &[], model, |delta| {
+    full_text.push_str(delta);
+  })
+  .await?;
+  if full_text.trim().is_empty() {
+    return Err(bad_gateway("Missing model output"));
   }
-
-  let mut buffer = String::new();
-  let mut stream = response.bytes_stream();
-  while let Some(chunk) = stream.next().await {
-    let chunk = match chunk {
-      Ok(bytes) => bytes,
-      Err(err) => return Err(internal_error("OpenAI stream failed")(err)),
-    };
-    buffer.push_str(&String::from_utf8_lossy(&chunk));
-    while let Some(pos) = buffer.find('\n') {
-      let line = buffer[..pos].trim_end().to_string();
-      buffer = buffer[pos + 1..].to_string();
-      if line.is_empty() {
-        continue;
-      }
-      let Some(data) = line.strip_prefix("data:") else {
-        continue;
-      };
-      let payload = data.trim();
-      if payload.is_empty() || payload == "[DONE]" {
-        continue;
-      }
-      if let Ok(value) = serde_json::from_str::<serde_json::Value>(payload) {
-        if value.get("type").and_then(|v| v.as_str()) == Some("response.output_text.delta") {
-          if let Some(delta) = value.get("delta").and_then(|v| v.as_str()) {
-            if !delta.is_empty() {
-              on_delta(delta);
-            }
-          }
-        }
-      }
-    }
-  }
-
-  Ok(())
-}
-
-fn extract_output_text(api: &OpenAiResponse) -> Option<String> {
-  for item in &api.output {
-    if item.r#type != "message" {
-      continue;
-    }
-    if let Some(content) = &item.content {
-      for part in content {
-        if part.r#type == "output_text" {
-          if let Some(text) = &part.text {
-            return Some(text.clone());
-          }
-        }
-      }
-    }
-  }
-  None
+  let mut parsed = IngestResponse {
+    text: sanitize_stream_text(&full_text),
+    code: String::new(),
+  };
+  normalize_response(&mut parsed);
+  Ok((parsed, full_text, usage))
 }
 
-fn extract_tool_call(api: &OpenAiResponse) -> Option<ToolResult> {
-  for item in &api.output {
-    if item.r#type != "function_call" {
-      continue;
-    }
-    if item.name.as_deref() != Some("submit_solution") {
-      continue;
-    }
-    if let Some(args) = &item.arguments {
-      if let Ok(parsed) = serde_json::from_str::<ToolResult>(args) {
-        return Some(parsed);
-      }
-    }
-  }
-  None
+/// Streams the answer to `images` and the user's `note`, or to the last of `turns` when
+/// following up on it.
+async fn call_model_stream<F>(
+  state: &AppState,
+  images: &[upload::Upload],
+  note: Option<&str>,
+  turns: &[Turn],
+  model: &ModelEntry,
+  mut on_delta: F,
+) -> Result<Option<Usage>, (StatusCode, Json<ErrorResponse>)>
+where
+  F: FnMut(&str) + Send,
+{
+  let request = VisionRequest {
+    model: model.spec.upstream_name(),
+    system_prompt: &state.system_prompt,
+    user_prompt: &state.stream_prompt,
+    user_note: note,
+    images,
+    turns,
+  };
+  model.provider.stream(&request, &mut on_delta).await
 }
 
 fn ensure_fenced_language(text: &str, language: &str) -> String {
@@ -876,35 +839,76 @@ fn wrap_code_block(text: &str, language: &str) -> String {
   out
 }
 
-fn select_model(headers: &axum::http::HeaderMap, state: &AppState) -> ModelChoice {
+fn select_model(
+  headers: &axum::http::HeaderMap,
+  state: &AppState,
+  subscription: &ActiveSubscription,
+) -> Result<ModelEntry, (StatusCode, Json<ErrorResponse>)> {
   let header = headers
     .get("x-model")
     .and_then(|val| val.to_str().ok())
     .unwrap_or("")
     .trim()
     .to_string();
-  if header.is_empty() {
-    return state.default_model;
+  let entry = if header.is_empty() {
+    state.models.default_entry()
+  } else {
+    state.models.get(&header)
+  };
+  let Some(entry) = entry else {
+    return Err(bad_request_code(&format!("Unknown model `{header}`"), 102));
+  };
+  if !entry.spec.available_for(subscription.package.as_deref()) {
+    return Err(forbidden(
+      &format!("Model `{}` is not available on your package", entry.spec.name),
+      Some(103),
+    ));
   }
-  ModelChoice::parse(&header).unwrap_or(state.default_model)
+  if subscription.credits < entry.spec.cost {
+    return Err(forbidden("No credits available", Some(101)));
+  }
+  Ok(entry.clone())
 }
 
+/// Records a running capture; the first of `file_names` goes into `screen_results`, the rest
+/// into `screen_result_images`.
 async fn insert_screen_result(
   db: &DatabaseConnection,
   user_id: Option<&str>,
-  file_name: &str,
-) -> String {
-  use entity::screen_results;
+  file_names: &[String],
+  prompt: Option<&str>,
+  model: &str,
+) -> Result<String, sea_orm::DbErr> {
+  use entity::{screen_result_images, screen_results};
   let id = Uuid::new_v4().to_string();
-  let active = screen_results::ActiveModel {
+  let txn = db.begin().await?;
+  screen_results::ActiveModel {
     id: Set(id.clone()),
     user_id: Set(user_id.map(|s| s.to_string())),
-    file_name: Set(file_name.to_string()),
+    file_name: Set(file_names.first().cloned().unwrap_or_default()),
     status: Set("RUNNING".to_string()),
+    model: Set(Some(model.to_string())),
+    prompt: Set(prompt.map(str::to_string)),
     ..Default::default()
-  };
-  let _ = active.insert(db).await;
-  id
+  }
+  .insert(&txn)
+  .await?;
+  let more: Vec<_> = file_names
+    .iter()
+    .enumerate()
+    .skip(1)
+    .map(|(position, file_name)| screen_result_images::ActiveModel {
+      screen_result_id: Set(id.clone()),
+      position: Set(position as i32),
+      file_name: Set(file_name.clone()),
+      ..Default::default()
+    })
+    .collect();
+  if !more.is_empty() {
+    screen_result_images::Entity::insert_many(more).exec(&txn).await?;
+  }
+  txn.commit().await?;
+  Ok(id)
 }
 
 fn normalize_response(response: &mut IngestResponse) {
@@ -983,34 +987,82 @@ async fn update_screen_result(
   id: &str,
   status: &str,
   debug_json: &serde_json::Value,
+  usage: Option<Usage>,
+  credits: Option<i32>,
 ) {
   use entity::screen_results;
   if let Ok(Some(model)) = screen_results::Entity::find_by_id(id.to_string()).one(db).await {
     let mut active: screen_results::ActiveModel = model.into();
     active.status = Set(status.to_string());
     active.debug = Set(Some(debug_json.clone()));
+    if let Some(usage) = usage {
+      active.input_tokens = Set(i64::try_from(usage.input_tokens).ok());
+      active.output_tokens = Set(i64::try_from(usage.output_tokens).ok());
+    }
+    active.credits = Set(credits);
     let _ = active.update(db).await;
   }
 }
 
-async fn init_db(db: &DatabaseConnection, db_name: Option<&str>) -> Result<(), sea_orm::DbErr> {
-  use entity::screen_results;
-  let exists = screen_results::Entity::find().one(db).await?;
-  if exists.is_none() {
-    if let Some(name) = db_name {
-      ensure_database_charset(db, name).await?;
+/// Settles a successful call at the model's price for the reported usage.
+async fn charge_credits(
+  reservation: credits::Reservation,
+  record_id: &str,
+  model: &ModelEntry,
+  usage: Option<&Usage>,
+) -> Option<i32> {
+  let note = usage.map(|usage| {
+    format!(
+      "{} input + {} output tokens",
+      usage.input_tokens, usage.output_tokens
+    )
+  });
+  match reservation
+    .commit(record_id, model.spec.charge(usage), note.as_deref())
+    .await
+  {
+    Ok(charged) => Some(charged),
+    Err(err) => {
+      eprintln!("Failed to commit credits record_id={record_id}: {err}");
+      None
     }
-    ensure_default_storage_engine(db).await?;
-    ensure_migrations_table(db).await?;
-    migration::Migrator::up(db, None).await?;
   }
-  Ok(())
+}
+
+async fn init_db(db: &DatabaseConnection, db_name: Option<&str>) -> Result<(), sea_orm::DbErr> {
+  prepare_database(db, db_name).await?;
+  migration::Migrator::up(db, None).await
+}
+
+/// MySQL-only setup that has to happen before the first migration runs.
+async fn prepare_database(
+  db: &DatabaseConnection,
+  db_name: Option<&str>,
+) -> Result<(), sea_orm::DbErr> {
+  if db.get_database_backend() != DatabaseBackend::MySql {
+    return Ok(());
+  }
+  if let Some(name) = db_name {
+    ensure_database_charset(db, name).await?;
+  }
+  ensure_default_storage_engine(db).await?;
+  ensure_migrations_table(db).await
+}
+
+fn parse_json_body<T: serde::de::DeserializeOwned>(
+  body: &[u8],
+) -> Result<T, (StatusCode, Json<ErrorResponse>)> {
+  serde_json::from_slice(body).map_err(|err| bad_request(&format!("Invalid JSON body: {err}")))
 }
 
 fn bad_request(message: &str) -> (StatusCode, Json<ErrorResponse>) {
   error_response(StatusCode::BAD_REQUEST, message, None)
 }
 
+fn bad_request_code(message: &str, code: i32) -> (StatusCode, Json<ErrorResponse>) {
+  error_response(StatusCode::BAD_REQUEST, message, Some(code))
+}
+
 fn unauthorized(message: &str, code: Option<i32>) -> (StatusCode, Json<ErrorResponse>) {
   error_response(StatusCode::UNAUTHORIZED, message, code)
 }
@@ -1034,6 +1086,7 @@ fn error_response(
       error: ErrorDetail {
         code: code.unwrap_or(status.as_u16() as i32),
         message: message.to_string(),
+        retry_after: None,
       },
     }),
   )
@@ -1049,103 +1102,143 @@ fn internal_error<E: std::fmt::Display>(
         error: ErrorDetail {
           code: StatusCode::INTERNAL_SERVER_ERROR.as_u16() as i32,
           message: format!("{message}: {err}"),
+          retry_after: None,
         },
       }),
     )
   }
 }
 
-fn save_image(bytes: &[u8], mime: &str) -> Result<String, std::io::Error> {
-  let ext = if mime.contains("png") {
-    "png"
-  } else if mime.contains("jpeg") || mime.contains("jpg") {
-    "jpg"
-  } else {
-    "bin"
-  };
-  let file_name = format!("{}.{}", Uuid::new_v4(), ext);
-  let dir = std::path::Path::new("data/images");
-  std::fs::create_dir_all(dir)?;
-  let path = dir.join(&file_name);
-  std::fs::write(path, bytes)?;
-  Ok(file_name)
+/// Validates and resizes the uploads off the async runtime.
+async fn normalize_uploads(
+  state: &AppState,
+  model: &ModelEntry,
+  images: Vec<Vec<u8>>,
+) -> Result<Vec<upload::Upload>, (StatusCode, Json<ErrorResponse>)> {
+  let max_side = model.spec.max_image_side.unwrap_or(state.upload.max_side);
+  let format = state.upload.format;
+  tokio::task::spawn_blocking(move || {
+    images
+      .into_iter()
+      .map(|bytes| upload::normalize(bytes, max_side, format))
+      .collect()
+  })
+  .await
+  .map_err(internal_error("Image processing failed"))?
 }
 
-async fn require_user_id(
-  db: &DatabaseConnection,
-  headers: &axum::http::HeaderMap,
-) -> Result<String, (StatusCode, Json<ErrorResponse>)> {
-  let auth = headers
-    .get(axum::http::header::AUTHORIZATION)
-    .and_then(|v| v.to_str().ok())
-    .unwrap_or("")
-    .trim()
-    .to_string();
-  let token = auth.strip_prefix("Bearer ").unwrap_or(&auth).trim();
-  if token.is_empty() {
-    return Err(unauthorized("API key was not provided", Some(100)));
-  }
+/// A capture recorded as running, with the credits held for its upstream call.
+struct SavedCapture {
+  id: String,
+  /// Image store keys in upload order; empty when the user opted out of storing images.
+  file_names: Vec<String>,
+  reservation: credits::Reservation,
+}
 
-  let stmt = Statement::from_sql_and_values(
-    DatabaseBackend::MySql,
-    "SELECT user_id FROM `keys` WHERE `key` = ? LIMIT 1",
-    vec![Value::from(token.to_string())],
-  );
-  let row = db
-    .query_one(stmt)
+/// Records a running capture and keeps its uploads unless the user opted out. The row is
+/// written before the files, so a purge running meanwhile counts a deduplicated file as used.
+/// On failure the reservation is refunded and the capture, if recorded, marked as failed.
+async fn save_capture(
+  state: &AppState,
+  user_id: &str,
+  uploads: &[upload::Upload],
+  prompt: Option<&str>,
+  model: &str,
+  reservation: credits::Reservation,
+) -> Result<SavedCapture, (StatusCode, Json<ErrorResponse>)> {
+  let user = entity::users::Entity::find_by_id(user_id)
+    .one(&state.db)
     .await
     .map_err(internal_error("DB error"))?;
-  if let Some(row) = row {
-    if let Ok(Some(user_id)) = row.try_get::<Option<String>>("", "user_id") {
-      return Ok(user_id);
+  let keys: Vec<String> = if user.is_some_and(|user| user.store_images) {
+    uploads
+      .iter()
+      .map(|upload| image_store::key_for(&upload.bytes, upload.mime))
+      .collect()
+  } else {
+    Vec::new()
+  };
+  let _references = image_store::hold_references().await;
+  let record_id = match insert_screen_result(&state.db, Some(user_id), &keys, prompt, model).await
+  {
+    Ok(record_id) => record_id,
+    Err(err) => {
+      if let Err(err) = reservation.refund(None, "Capture not saved").await {
+        eprintln!("Failed to refund credits user_id={user_id}: {err}");
+      }
+      return Err(internal_error("DB error")(err));
+    }
+  };
+  for (upload, key) in uploads.iter().zip(&keys) {
+    if let Err(err) = state.images.put(key, &upload.bytes, upload.mime).await {
+      if let Err(err) = reservation.refund(Some(&record_id), "Save image failed").await {
+        eprintln!("Failed to refund credits record_id={record_id}: {err}");
+      }
+      let debug_json = serde_json::json!({ "error": { "message": "Save image failed" } });
+      update_screen_result(&state.db, &record_id, "ERROR", &debug_json, None, None).await;
+      return Err(internal_error("Save image failed")(err));
     }
   }
-  Err(unauthorized("Invalid API key", Some(100)))
+  Ok(SavedCapture {
+    id: record_id,
+    file_names: keys,
+    reservation,
+  })
 }
 
 async fn require_subscription(
   db: &DatabaseConnection,
   user_id: &str,
-) -> Result<i64, (StatusCode, Json<ErrorResponse>)> {
-  let stmt = Statement::from_sql_and_values(
-    DatabaseBackend::MySql,
-    "SELECT id, credits FROM subscriptions \
-     WHERE user_id = ? AND (expires_at IS NULL OR expires_at > NOW()) \
-     ORDER BY expires_at DESC LIMIT 1",
-    vec![Value::from(user_id.to_string())],
-  );
-  let row = db
-    .query_one(stmt)
-    .await
-    .map_err(internal_error("DB error"))?;
-  let Some(row) = row else {
+) -> Result<ActiveSubscription, (StatusCode, Json<ErrorResponse>)> {
+  let Some(subscription) = active_subscription(db, user_id).await? else {
     return Err(forbidden("No active subscription", None));
   };
-  let id: i64 = row.try_get("", "id").unwrap_or(0);
-  let credits: i64 = row.try_get("", "credits").unwrap_or(0);
-  if credits <= 0 {
+  if subscription.credits <= 0 {
     return Err(forbidden("No credits available", Some(101)));
   }
-  Ok(id)
+  Ok(subscription)
 }
 
-async fn decrement_subscription(
+async fn active_subscription(
   db: &DatabaseConnection,
-  subscription_id: i64,
-) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
-  let stmt = Statement::from_sql_and_values(
-    DatabaseBackend::MySql,
-    "UPDATE subscriptions SET credits = credits - 1 WHERE id = ? AND credits > 0",
-    vec![Value::from(subscription_id)],
-  );
-  let result = db
-    .execute(stmt)
+  user_id: &str,
+) -> Result<Option<ActiveSubscription>, (StatusCode, Json<ErrorResponse>)> {
+  Ok(active_subscriptions(db, &[user_id.to_string()]).await?.remove(user_id))
+}
+
+/// The active subscription of each of `user_ids` that has one, loaded in one query.
+async fn active_subscriptions(
+  db: &DatabaseConnection,
+  user_ids: &[String],
+) -> Result<HashMap<String, ActiveSubscription>, (StatusCode, Json<ErrorResponse>)> {
+  use entity::{packages, subscriptions};
+  let found = subscriptions::Entity::find()
+    .find_also_related(packages::Entity)
+    .filter(subscriptions::Column::UserId.is_in(user_ids.iter().cloned()))
+    .filter(
+      Condition::any()
+        .add(subscriptions::Column::ExpiresAt.is_null())
+        .add(subscriptions::Column::ExpiresAt.gt(chrono::Utc::now())),
+    )
+    .order_by_desc(subscriptions::Column::ExpiresAt)
+    .all(db)
     .await
     .map_err(internal_error("DB error"))?;
-  if result.rows_affected() == 0 {
-    return Err(forbidden("No credits available", Some(101)));
+  let mut active = HashMap::new();
+  for (subscription, package) in found {
+    let Some(user_id) = subscription.user_id else {
+      continue;
+    };
+    // Rows come latest expiry first; keep the first one per user.
+    active.entry(user_id).or_insert_with(|| ActiveSubscription {
+      id: subscription.id,
+      credits: i64::from(subscription.credits),
+      rate_limit: package.as_ref().map_or(0, |package| package.rate_limit),
+      expires_at: subscription.expires_at,
+      package: package.map(|package| package.name),
+    });
   }
-  Ok(())
+  Ok(active)
 }
 
 async fn seed_db(db: &DatabaseConnection) -> Result<(), sea_orm::DbErr> {
@@ -1163,9 +1256,7 @@ async fn seed_db(db: &DatabaseConnection) -> Result<(), sea_orm::DbErr> {
       "1234",
       "3e1f3f5a-2f1b-4e06-9e2a-7a43d2c5c101",
       "4f2a5e0e-6f92-4b8e-a7f8-873d0a1a1101",
-      "5b0e5a1b-4b18-4a6e-bb7c-5f95c9c5b101",
       "6a2b5c3d-7e8f-4a9b-8c0d-1e2f3a4b5101",
-      3,
       10,
     ),
     (
@@ -1181,9 +1272,7 @@ async fn seed_db(db: &DatabaseConnection) -> Result<(), sea_orm::DbErr> {
       "5678",
       "7b3c6d4e-8f90-4a1b-9c2d-3e4f5a6b5202",
       "8c4d7e5f-9012-4b3c-ad4e-5f6a7b8c6202",
-      "9d5e8f60-0123-4c5d-be6f-7a8b9c0d7202",
       "ae6f9071-1234-4d6e-cf70-8b9c0d1e8202",
-      1,
       200,
     ),
     (
@@ -1199,23 +1288,15 @@ async fn seed_db(db: &DatabaseConnection) -> Result<(), sea_orm::DbErr> {
       "2468",
       "bf70a182-2345-4e7f-d081-9c0d1e2f9303",
       "c081b293-3456-4f80-e192-ad1e2f3a0403",
-      "d192c3a4-4567-4081-f2a3-be2f3a4b1403",
       "e2a3d4b5-5678-4182-03b4-cf3a4b5c2403",
-      2,
       50,
     ),
   ];
 
-  let insert_free = sea_orm::Statement::from_string(
-    sea_orm::DatabaseBackend::MySql,
-    "INSERT IGNORE INTO packages (id, name, rate_limit) VALUES (1, 'Free', 60)".to_string(),
-  );
-  let insert_pro = sea_orm::Statement::from_string(
-    sea_orm::DatabaseBackend::MySql,
-    "INSERT IGNORE INTO packages (id, name, rate_limit) VALUES (2, 'Pro', 600)".to_string(),
-  );
-  db.execute(insert_free).await?;
-  db.execute(insert_pro).await?;
+  use entity::{keys, links, roles, settings, subscriptions, users};
+
+  let free_id = ensure_package(db, "Free", 60, 10, None).await?;
+  let pro_id = ensure_package(db, "Pro", 600, 500, Some("9.99")).await?;
 
   for (
     id,
@@ -1230,77 +1311,150 @@ async fn seed_db(db: &DatabaseConnection) -> Result<(), sea_orm::DbErr> {
     pin,
     role_id,
     link_id,
-    key_id,
     settings_id,
-    subscription_id,
     credits,
   ) in users
   {
-    let insert_user = sea_orm::Statement::from_string(
-      sea_orm::DatabaseBackend::MySql,
-      format!(
-        "INSERT IGNORE INTO users (id, email, password, first_name, last_name, confirmd) \
-         VALUES ('{id}', '{email}', '{password}', '{first_name}', '{last_name}', {confirmd})"
-      ),
-    );
-    db.execute(insert_user).await?;
-
-    let insert_role = sea_orm::Statement::from_string(
-      sea_orm::DatabaseBackend::MySql,
-      format!(
-        "INSERT IGNORE INTO roles (id, user_id, name, elevation) \
-         VALUES ('{role_id}', '{id}', '{role_name}', {elevation})"
-      ),
-    );
-    db.execute(insert_role).await?;
-
-    let insert_link = sea_orm::Statement::from_string(
-      sea_orm::DatabaseBackend::MySql,
-      format!(
-        "INSERT IGNORE INTO links (id, user_id, code, pin) \
-         VALUES ('{link_id}', '{id}', '{code}', '{pin}')"
-      ),
-    );
-    db.execute(insert_link).await?;
-
-    let api_key = format!("jwt-{id}");
-    let insert_key = sea_orm::Statement::from_string(
-      sea_orm::DatabaseBackend::MySql,
-      format!(
-        "INSERT IGNORE INTO `keys` (id, user_id, name, `key`) \
-         VALUES ('{key_id}', '{id}', 'default', '{api_key}')"
-      ),
-    );
-    db.execute(insert_key).await?;
+    let password =
+      auth::hash_password(password).map_err(|err| sea_orm::DbErr::Custom(err.to_string()))?;
+    users::Entity::insert(users::ActiveModel {
+      id: Set(id.to_string()),
+      email: Set(email.to_string()),
+      password: Set(password),
+      first_name: Set(Some(first_name.to_string())),
+      last_name: Set(Some(last_name.to_string())),
+      confirmd: Set(confirmd == 1),
+      ..Default::default()
+    })
+    .on_conflict(ignore_duplicate(users::Column::Id))
+    .do_nothing()
+    .exec(db)
+    .await?;
+
+    roles::Entity::insert(roles::ActiveModel {
+      id: Set(role_id.to_string()),
+      user_id: Set(Some(id.to_string())),
+      name: Set(Some(role_name.to_string())),
+      elevation: Set(Some(elevation)),
+      ..Default::default()
+    })
+    .on_conflict(ignore_duplicate(roles::Column::Id))
+    .do_nothing()
+    .exec(db)
+    .await?;
+
+    links::Entity::insert(links::ActiveModel {
+      id: Set(link_id.to_string()),
+      user_id: Set(Some(id.to_string())),
+      code: Set(Some(code.to_string())),
+      pin: Set(Some(pin.to_string())),
+      ..Default::default()
+    })
+    .on_conflict(ignore_duplicate(links::Column::Id))
+    .do_nothing()
+    .exec(db)
+    .await?;
+
+    let has_key = keys::Entity::find()
+      .filter(keys::Column::UserId.eq(id))
+      .one(db)
+      .await?
+      .is_some();
+    if !has_key {
+      let scopes: &[Scope] = if elevation >= admin::MODERATOR {
+        &[Scope::Ingest, Scope::History, Scope::Admin]
+      } else {
+        &[Scope::Ingest, Scope::History]
+      };
+      let (_, token) = crate::keys::issue_key(db, id, "default", scopes, None, None).await?;
+      eprintln!("API key for {email}: {token}");
+    }
 
     let config_json = serde_json::json!({
       "test": true,
-      "main_position": { "x": 1113.0, "y": 284.0 },
-      "api_key": ""
+      "theme": "dark"
     });
-    let insert_settings = sea_orm::Statement::from_string(
-      sea_orm::DatabaseBackend::MySql,
-      format!(
-        "INSERT IGNORE INTO settings (id, user_id, name, config) \
-         VALUES ('{settings_id}', '{id}', 'default', '{}')",
-        config_json.to_string().replace('\'', "''")
-      ),
-    );
-    db.execute(insert_settings).await?;
-
-    let package_id = if role_name == "admin" { 2 } else { 1 };
-    let insert_subscription = sea_orm::Statement::from_string(
-      sea_orm::DatabaseBackend::MySql,
-      format!(
-        "INSERT IGNORE INTO subscriptions (id, user_id, package_id, expires_at, credits) \
-         VALUES ({subscription_id}, '{id}', {package_id}, DATE_ADD(NOW(), INTERVAL 30 DAY), {credits})"
-      ),
-    );
-    db.execute(insert_subscription).await?;
+    settings::Entity::insert(settings::ActiveModel {
+      id: Set(settings_id.to_string()),
+      user_id: Set(Some(id.to_string())),
+      name: Set(Some("default".to_string())),
+      config: Set(Some(config_json)),
+      ..Default::default()
+    })
+    .on_conflict(ignore_duplicate(settings::Column::Id))
+    .do_nothing()
+    .exec(db)
+    .await?;
+
+    // Subscription ids come from the sequence (Postgres would not advance it for
+    // explicit ids), so only the first seed creates one.
+    let has_subscription = subscriptions::Entity::find()
+      .filter(subscriptions::Column::UserId.eq(id))
+      .one(db)
+      .await?
+      .is_some();
+    if !has_subscription {
+      let package_id = if role_name == "admin" { pro_id } else { free_id };
+      let subscription = subscriptions::ActiveModel {
+        user_id: Set(Some(id.to_string())),
+        package_id: Set(Some(package_id)),
+        expires_at: Set(Some(chrono::Utc::now() + chrono::Duration::days(30))),
+        credits: Set(0),
+        ..Default::default()
+      }
+      .insert(db)
+      .await?;
+      credits::adjust(
+        db,
+        subscription.id,
+        Some(id),
+        credits::GRANT,
+        credits,
+        Some("seed"),
+      )
+      .await?;
+    }
   }
   Ok(())
 }
 
+async fn ensure_package(
+  db: &DatabaseConnection,
+  name: &str,
+  rate_limit: i32,
+  credits: i32,
+  price: Option<&str>,
+) -> Result<i64, sea_orm::DbErr> {
+  use entity::packages;
+  if let Some(package) = packages::Entity::find()
+    .filter(packages::Column::Name.eq(name))
+    .one(db)
+    .await?
+  {
+    return Ok(package.id);
+  }
+  let package = packages::ActiveModel {
+    name: Set(name.to_string()),
+    rate_limit: Set(rate_limit),
+    credits: Set(credits),
+    duration_days: Set(30),
+    price: Set(price.and_then(|price| price.parse().ok())),
+    currency: Set(price.map(|_| "EUR".to_string())),
+    ..Default::default()
+  }
+  .insert(db)
+  .await?;
+  Ok(package.id)
+}
+
+/// `INSERT IGNORE` that works on every backend.
+fn ignore_duplicate<C>(column: C) -> OnConflict
+where
+  C: sea_orm::sea_query::IntoIden + Copy,
+{
+  OnConflict::column(column).do_nothing_on([column]).to_owned()
+}
+
 fn sanitize_db_url(url: &str) -> String {
   let Some(scheme_idx) = url.find("://") else {
     return url.to_string();
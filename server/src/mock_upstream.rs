@@ -0,0 +1,170 @@
+//! Local stand-in for an OpenAI-style upstream that replays recorded fixtures.
+//!
+//! Fixtures live in one directory and are picked by the `model` field of the request:
+//! `<model>.sse` answers streaming requests, `<model>.json` answers everything else and
+//! `default.*` is used when no fixture matches. A status code can be put in the file name
+//! (`overloaded.500.json`). In `.sse` files a `!disconnect` line drops the connection and a
+//! `!stall` line holds the next event back for `STALL`.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+  body::{Body, Bytes},
+  extract::State,
+  http::{header, StatusCode},
+  response::{IntoResponse, Response},
+  routing::post,
+  Json, Router,
+};
+use futures_util::StreamExt;
+
+const DISCONNECT: &str = "!disconnect";
+const STALL: &str = "!stall";
+/// Long enough for a client to hang up in the middle of a stream.
+const STALL_DELAY: Duration = Duration::from_millis(500);
+/// Pause between SSE events so each one is flushed before the next (or a disconnect).
+const EVENT_DELAY: Duration = Duration::from_millis(5);
+
+#[derive(Clone)]
+pub struct Fixture {
+  pub status: StatusCode,
+  pub body: String,
+}
+
+#[derive(Clone, Default)]
+pub struct Fixtures {
+  json: HashMap<String, Fixture>,
+  sse: HashMap<String, Fixture>,
+}
+
+impl Fixtures {
+  pub fn load(dir: &Path) -> std::io::Result<Self> {
+    let mut fixtures = Self::default();
+    for entry in std::fs::read_dir(dir)? {
+      let path = entry?.path();
+      let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+        continue;
+      };
+      let Some((stem, ext)) = file_name.rsplit_once('.') else {
+        continue;
+      };
+      let (name, status) = match stem.rsplit_once('.') {
+        Some((name, code)) => match code
+          .parse::<u16>()
+          .ok()
+          .and_then(|c| StatusCode::from_u16(c).ok())
+        {
+          Some(status) => (name, status),
+          None => (stem, StatusCode::OK),
+        },
+        None => (stem, StatusCode::OK),
+      };
+      let body = std::fs::read_to_string(&path)?;
+      match ext {
+        "json" => fixtures.insert_json(name, status, body),
+        "sse" => fixtures.insert_sse(name, status, body),
+        _ => {}
+      }
+    }
+    Ok(fixtures)
+  }
+
+  pub fn insert_json(&mut self, name: &str, status: StatusCode, body: impl Into<String>) {
+    let body = body.into();
+    self.json.insert(name.to_string(), Fixture { status, body });
+  }
+
+  pub fn insert_sse(&mut self, name: &str, status: StatusCode, body: impl Into<String>) {
+    let body = body.into();
+    self.sse.insert(name.to_string(), Fixture { status, body });
+  }
+
+  fn find(&self, model: &str, stream: bool) -> Option<(&Fixture, bool)> {
+    // An exact match in either format wins over the defaults, so `overloaded.500.json`
+    // also answers streaming requests.
+    [model, "default"].into_iter().find_map(|name| {
+      let sse = self.sse.get(name).filter(|_| stream);
+      sse
+        .map(|fixture| (fixture, true))
+        .or_else(|| self.json.get(name).map(|fixture| (fixture, false)))
+    })
+  }
+}
+
+pub fn router(fixtures: Fixtures) -> Router {
+  let fixtures = Arc::new(fixtures);
+  Router::new()
+    .route("/v1/responses", post(replay))
+    .route("/responses", post(replay))
+    .with_state(fixtures)
+}
+
+pub async fn serve(addr: &str, fixtures: Fixtures) -> anyhow::Result<()> {
+  let listener = tokio::net::TcpListener::bind(addr).await?;
+  eprintln!("Mock upstream running on http://{addr}/v1");
+  axum::serve(listener, router(fixtures)).await?;
+  Ok(())
+}
+
+async fn replay(
+  State(fixtures): State<Arc<Fixtures>>,
+  Json(request): Json<serde_json::Value>,
+) -> Response {
+  let model = request.get("model").and_then(|v| v.as_str()).unwrap_or("");
+  let stream = request
+    .get("stream")
+    .and_then(|v| v.as_bool())
+    .unwrap_or(false);
+  let Some((fixture, is_sse)) = fixtures.find(model, stream) else {
+    let body = serde_json::json!({
+      "error": { "message": format!("No fixture for model `{model}`") }
+    });
+    return (StatusCode::NOT_FOUND, Json(body)).into_response();
+  };
+
+  if !is_sse {
+    return (
+      fixture.status,
+      [(header::CONTENT_TYPE, "application/json")],
+      fixture.body.clone(),
+    )
+      .into_response();
+  }
+
+  let mut chunks: Vec<(Duration, Result<Bytes, std::io::Error>)> = Vec::new();
+  let mut delay = EVENT_DELAY;
+  for event in fixture.body.split("\n\n") {
+    if event.trim() == DISCONNECT {
+      chunks.push((
+        delay,
+        Err(std::io::Error::new(
+          std::io::ErrorKind::ConnectionAborted,
+          "fixture disconnect",
+        )),
+      ));
+      break;
+    }
+    if event.trim() == STALL {
+      delay += STALL_DELAY;
+      continue;
+    }
+    if event.trim().is_empty() {
+      continue;
+    }
+    chunks.push((delay, Ok(Bytes::from(format!("{}\n\n", event.trim_end())))));
+    delay = EVENT_DELAY;
+  }
+  let events = futures_util::stream::iter(chunks).then(|(delay, chunk)| async move {
+    tokio::time::sleep(delay).await;
+    chunk
+  });
+  let body = Body::from_stream(events);
+  Response::builder()
+    .status(fixture.status)
+    .header(header::CONTENT_TYPE, "text/event-stream")
+    .body(body)
+    .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
@@ -0,0 +1,228 @@
+use std::env;
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+
+use crate::provider::{self, ProviderKind, Usage, VisionProvider};
+
+const BUILTIN_MODELS: &str = include_str!("../models.json");
+
+#[derive(Deserialize)]
+struct ModelsFile {
+  default: String,
+  models: Vec<ModelSpec>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelSpec {
+  pub name: String,
+  #[serde(default)]
+  pub label: Option<String>,
+  pub provider: String,
+  /// Model id sent upstream when it differs from `name` (e.g. `llava:13b` on Ollama).
+  #[serde(default)]
+  pub upstream_model: Option<String>,
+  #[serde(default)]
+  pub base_url: Option<String>,
+  /// Environment variable holding the API key; defaults to the provider's `*_API_KEY`.
+  #[serde(default)]
+  pub api_key_env: Option<String>,
+  /// Credits reserved before each call, and the charge when there is no `price` or the
+  /// upstream reports no usage.
+  #[serde(default = "default_cost")]
+  pub cost: i64,
+  #[serde(default)]
+  pub price: Option<Price>,
+  #[serde(default = "default_true")]
+  pub streaming: bool,
+  #[serde(default = "default_true")]
+  pub tools: bool,
+  /// Longest image edge sent to this model; larger screenshots are scaled down. Defaults to
+  /// `IMAGE_MAX_SIDE`.
+  #[serde(default)]
+  pub max_image_side: Option<u32>,
+  /// Package names that may use this model; empty means every package.
+  #[serde(default)]
+  pub packages: Vec<String>,
+}
+
+/// Credits per million tokens.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct Price {
+  pub input: f64,
+  pub output: f64,
+}
+
+fn default_cost() -> i64 {
+  1
+}
+
+fn default_true() -> bool {
+  true
+}
+
+impl ModelSpec {
+  pub fn upstream_name(&self) -> &str {
+    self.upstream_model.as_deref().unwrap_or(&self.name)
+  }
+
+  /// Credits for a finished call; a priced call costs at least one credit.
+  pub fn charge(&self, usage: Option<&Usage>) -> i64 {
+    let (Some(price), Some(usage)) = (self.price, usage) else {
+      return self.cost;
+    };
+    let credits = (usage.input_tokens as f64 * price.input
+      + usage.output_tokens as f64 * price.output)
+      / 1_000_000.0;
+    (credits.ceil() as i64).max(1)
+  }
+
+  pub fn available_for(&self, package: Option<&str>) -> bool {
+    if self.packages.is_empty() {
+      return true;
+    }
+    let Some(package) = package else {
+      return false;
+    };
+    self
+      .packages
+      .iter()
+      .any(|allowed| allowed.eq_ignore_ascii_case(package))
+  }
+}
+
+#[derive(Clone)]
+pub struct ModelEntry {
+  pub spec: ModelSpec,
+  pub kind: ProviderKind,
+  pub provider: Arc<dyn VisionProvider>,
+}
+
+impl ModelEntry {
+  pub fn from_spec(spec: ModelSpec, client: &reqwest::Client) -> anyhow::Result<Self> {
+    let kind = ProviderKind::parse(&spec.provider).with_context(|| {
+      format!("Unknown provider `{}` for model `{}`", spec.provider, spec.name)
+    })?;
+    let prefix = kind.env_prefix();
+    let api_key_env = spec
+      .api_key_env
+      .clone()
+      .unwrap_or_else(|| format!("{prefix}_API_KEY"));
+    let api_key = env::var(api_key_env).unwrap_or_default();
+    let base_url = spec
+      .base_url
+      .clone()
+      .or_else(|| env::var(format!("{prefix}_BASE_URL")).ok());
+    let provider = provider::build_provider(kind, client.clone(), base_url, api_key);
+    Ok(Self {
+      spec,
+      kind,
+      provider,
+    })
+  }
+}
+
+#[derive(Serialize)]
+pub struct ModelInfo {
+  pub name: String,
+  pub label: String,
+  pub provider: String,
+  pub cost: i64,
+  pub price: Option<Price>,
+  pub streaming: bool,
+  pub tools: bool,
+  pub packages: Vec<String>,
+  pub available: bool,
+}
+
+#[derive(Clone)]
+pub struct ModelRegistry {
+  default_model: String,
+  models: Vec<ModelEntry>,
+}
+
+impl ModelRegistry {
+  pub fn new(default_model: String, models: Vec<ModelEntry>) -> Self {
+    Self {
+      default_model,
+      models,
+    }
+  }
+
+  /// Loads `MODELS_CONFIG` (or `server/models.json`), falling back to the built-in list.
+  pub fn load(client: &reqwest::Client) -> anyhow::Result<Self> {
+    let path = env::var("MODELS_CONFIG").ok().or_else(|| {
+      ["server/models.json", "models.json"]
+        .into_iter()
+        .find(|path| std::path::Path::new(path).exists())
+        .map(str::to_string)
+    });
+    let contents = match &path {
+      Some(path) => {
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {path}"))?
+      }
+      None => BUILTIN_MODELS.to_string(),
+    };
+    let file: ModelsFile = serde_json::from_str(&contents).with_context(|| {
+      format!(
+        "Invalid models config {}",
+        path.as_deref().unwrap_or("(built-in)")
+      )
+    })?;
+
+    let models = file
+      .models
+      .into_iter()
+      .map(|spec| ModelEntry::from_spec(spec, client))
+      .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let default_model = env::var("OPENAI_MODEL")
+      .ok()
+      .filter(|name| models.iter().any(|entry| entry.spec.name.eq_ignore_ascii_case(name)))
+      .unwrap_or(file.default);
+    let registry = Self::new(default_model, models);
+    if registry.default_entry().is_none() {
+      anyhow::bail!("Default model `{}` is not defined", registry.default_model);
+    }
+    Ok(registry)
+  }
+
+  pub fn get(&self, name: &str) -> Option<&ModelEntry> {
+    let name = name.trim();
+    self
+      .models
+      .iter()
+      .find(|entry| entry.spec.name.eq_ignore_ascii_case(name))
+  }
+
+  pub fn default_entry(&self) -> Option<&ModelEntry> {
+    self.get(&self.default_model)
+  }
+
+  pub fn default_name(&self) -> &str {
+    &self.default_model
+  }
+
+  pub fn list(&self, package: Option<&str>) -> Vec<ModelInfo> {
+    self
+      .models
+      .iter()
+      .map(|entry| ModelInfo {
+        name: entry.spec.name.clone(),
+        label: entry
+          .spec
+          .label
+          .clone()
+          .unwrap_or_else(|| entry.spec.name.clone()),
+        provider: entry.kind.as_str().to_string(),
+        cost: entry.spec.cost,
+        price: entry.spec.price,
+        streaming: entry.spec.streaming,
+        tools: entry.spec.tools,
+        packages: entry.spec.packages.clone(),
+        available: entry.spec.available_for(package),
+      })
+      .collect()
+  }
+}
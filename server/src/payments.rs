@@ -0,0 +1,410 @@
+use std::env;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::{
+  body::Bytes,
+  extract::State,
+  http::{HeaderMap, StatusCode},
+  Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use sea_orm::prelude::Decimal;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+  ActiveModelTrait, ColumnTrait, DatabaseTransaction, EntityTrait, QueryFilter, QueryOrder, Set,
+  SqlErr, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::entity::{packages, payments, subscriptions, users};
+use crate::keys::constant_time_eq;
+use crate::{
+  bad_request, credits, error_response, internal_error, parse_json_body, unauthorized, AppState,
+  ErrorResponse,
+};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+pub const PAID: &str = "PAID";
+pub const REFUNDED: &str = "REFUNDED";
+
+/// What a provider's webhook reported, in provider-neutral terms.
+pub enum PaymentEvent {
+  /// A purchase or a renewal; both extend the user's subscription to the package.
+  Paid(PaidEvent),
+  /// The payment with this transaction id was refunded.
+  Refunded { transaction_id: String },
+}
+
+pub struct PaidEvent {
+  pub transaction_id: String,
+  pub user_id: String,
+  /// `packages.name` of what was bought.
+  pub package: String,
+  pub price: Option<Decimal>,
+  pub currency: Option<String>,
+}
+
+/// A payment service that tells the server about payments through a webhook.
+pub trait PaymentProvider: Send + Sync {
+  /// Stored in `payments.provider`.
+  fn name(&self) -> &'static str;
+
+  /// Checks that the webhook really comes from the provider and parses it.
+  fn parse_webhook(&self, headers: &HeaderMap, body: &[u8]) -> Result<PaymentEvent, ApiError>;
+}
+
+/// Local stand-in for a payment service. Webhooks are JSON signed with
+/// `x-fake-signature: hex(hmac_sha256(secret, body))`.
+pub struct FakePaymentProvider {
+  secret: String,
+}
+
+#[derive(Deserialize)]
+struct FakeWebhook {
+  #[serde(rename = "type")]
+  kind: String,
+  transaction_id: String,
+  user_id: Option<String>,
+  package: Option<String>,
+  amount: Option<String>,
+  currency: Option<String>,
+}
+
+impl FakePaymentProvider {
+  pub fn new(secret: impl Into<String>) -> Self {
+    Self {
+      secret: secret.into(),
+    }
+  }
+
+  pub fn sign(&self, body: &[u8]) -> String {
+    let mut mac = self.mac();
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+  }
+
+  fn mac(&self) -> Hmac<Sha256> {
+    Hmac::<Sha256>::new_from_slice(self.secret.as_bytes()).expect("HMAC accepts any key length")
+  }
+}
+
+impl PaymentProvider for FakePaymentProvider {
+  fn name(&self) -> &'static str {
+    "fake"
+  }
+
+  fn parse_webhook(&self, headers: &HeaderMap, body: &[u8]) -> Result<PaymentEvent, ApiError> {
+    let signature = headers
+      .get("x-fake-signature")
+      .and_then(|value| value.to_str().ok())
+      .ok_or_else(|| unauthorized("Missing webhook signature", None))?;
+    let expected = self.sign(body);
+    if !constant_time_eq(
+      signature.trim().to_ascii_lowercase().as_bytes(),
+      expected.as_bytes(),
+    ) {
+      return Err(unauthorized("Invalid webhook signature", None));
+    }
+
+    let webhook: FakeWebhook = parse_json_body(body)?;
+    if webhook.transaction_id.trim().is_empty() {
+      return Err(bad_request("`transaction_id` is required"));
+    }
+    match webhook.kind.as_str() {
+      "purchase" | "renewal" => {
+        let (Some(user_id), Some(package)) = (webhook.user_id, webhook.package) else {
+          return Err(bad_request("`user_id` and `package` are required"));
+        };
+        let price = webhook
+          .amount
+          .map(|amount| Decimal::from_str(amount.trim()))
+          .transpose()
+          .map_err(|_| bad_request("`amount` must be a decimal"))?;
+        Ok(PaymentEvent::Paid(PaidEvent {
+          transaction_id: webhook.transaction_id,
+          user_id,
+          package,
+          price,
+          currency: webhook.currency,
+        }))
+      }
+      "refund" => Ok(PaymentEvent::Refunded {
+        transaction_id: webhook.transaction_id,
+      }),
+      other => Err(bad_request(&format!("Unknown event type `{other}`"))),
+    }
+  }
+}
+
+/// Picks the provider from `PAYMENT_PROVIDER`; unset means payments are disabled.
+pub fn from_env() -> anyhow::Result<Option<Arc<dyn PaymentProvider>>> {
+  let kind = env::var("PAYMENT_PROVIDER").unwrap_or_default();
+  match kind.trim().to_ascii_lowercase().as_str() {
+    "" | "none" => Ok(None),
+    "fake" => {
+      let secret = env::var("PAYMENT_WEBHOOK_SECRET").unwrap_or_default();
+      if secret.trim().is_empty() {
+        anyhow::bail!("PAYMENT_PROVIDER=fake needs PAYMENT_WEBHOOK_SECRET");
+      }
+      Ok(Some(Arc::new(FakePaymentProvider::new(secret))))
+    }
+    other => anyhow::bail!("Unknown PAYMENT_PROVIDER `{other}` (expected fake)"),
+  }
+}
+
+#[derive(Serialize)]
+pub struct PaymentResponse {
+  id: String,
+  transaction_id: Option<String>,
+  status: String,
+  subscription_id: Option<i64>,
+  expires_at: Option<DateTime<Utc>>,
+  credits: Option<i32>,
+}
+
+impl PaymentResponse {
+  fn new(payment: payments::Model, subscription: Option<subscriptions::Model>) -> Self {
+    Self {
+      id: payment.id,
+      transaction_id: payment.transaction_id,
+      status: payment.status,
+      subscription_id: subscription.as_ref().map(|subscription| subscription.id),
+      expires_at: subscription
+        .as_ref()
+        .and_then(|subscription| subscription.expires_at),
+      credits: subscription.map(|subscription| subscription.credits),
+    }
+  }
+}
+
+/// POST /payments/webhook. Deliveries are idempotent by transaction id, so the provider can
+/// retry them freely.
+pub async fn webhook(
+  State(state): State<AppState>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Result<Json<PaymentResponse>, ApiError> {
+  let Some(provider) = state.payments.clone() else {
+    return Err(error_response(
+      StatusCode::NOT_FOUND,
+      "Payments are not configured",
+      None,
+    ));
+  };
+  let event = provider.parse_webhook(&headers, &body)?;
+  let txn = state.db.begin().await.map_err(internal_error("DB error"))?;
+  let response = match event {
+    PaymentEvent::Paid(paid) => record_payment(&txn, provider.name(), paid).await?,
+    PaymentEvent::Refunded { transaction_id } => refund_payment(&txn, &transaction_id).await?,
+  };
+  txn.commit().await.map_err(internal_error("DB error"))?;
+  Ok(Json(response))
+}
+
+async fn record_payment(
+  txn: &DatabaseTransaction,
+  provider: &str,
+  paid: PaidEvent,
+) -> Result<PaymentResponse, ApiError> {
+  if let Some(payment) = find_payment(txn, &paid.transaction_id).await? {
+    let subscription = latest_subscription(txn, &payment).await?;
+    return Ok(PaymentResponse::new(payment, subscription));
+  }
+  users::Entity::find_by_id(paid.user_id.clone())
+    .one(txn)
+    .await
+    .map_err(internal_error("DB error"))?
+    .ok_or_else(|| bad_request("Unknown user"))?;
+  let package = packages::Entity::find()
+    .filter(packages::Column::Name.eq(paid.package.trim()))
+    .one(txn)
+    .await
+    .map_err(internal_error("DB error"))?
+    .ok_or_else(|| bad_request(&format!("Unknown package `{}`", paid.package)))?;
+
+  let inserted = payments::ActiveModel {
+    id: Set(Uuid::new_v4().to_string()),
+    transaction_id: Set(Some(paid.transaction_id.clone())),
+    currency: Set(paid.currency.or_else(|| package.currency.clone())),
+    price: Set(paid.price.or(package.price)),
+    user_id: Set(Some(paid.user_id.clone())),
+    package_id: Set(Some(package.id)),
+    provider: Set(Some(provider.to_string())),
+    status: Set(PAID.to_string()),
+    ..Default::default()
+  }
+  .insert(txn)
+  .await;
+  let payment = match inserted {
+    Ok(payment) => payment,
+    // A concurrent delivery of the same webhook won the race.
+    Err(err) if matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+      return Err(error_response(
+        StatusCode::CONFLICT,
+        "Payment is already being processed",
+        None,
+      ));
+    }
+    Err(err) => return Err(internal_error("DB error")(err)),
+  };
+
+  let now = Utc::now();
+  let duration = Duration::days(i64::from(package.duration_days));
+  let existing = subscriptions::Entity::find()
+    .filter(subscriptions::Column::UserId.eq(paid.user_id.as_str()))
+    .filter(subscriptions::Column::PackageId.eq(package.id))
+    .order_by_desc(subscriptions::Column::Id)
+    .one(txn)
+    .await
+    .map_err(internal_error("DB error"))?;
+  let subscription = match existing {
+    // A renewal extends from the current expiry, or from now once it lapsed.
+    Some(subscription) => {
+      let expires_at = subscription
+        .expires_at
+        .map(|expires_at| expires_at.max(now) + duration);
+      let mut active: subscriptions::ActiveModel = subscription.into();
+      active.expires_at = Set(expires_at);
+      active.payment_id = Set(Some(payment.id.clone()));
+      active.update(txn).await
+    }
+    None => {
+      subscriptions::ActiveModel {
+        user_id: Set(Some(paid.user_id.clone())),
+        payment_id: Set(Some(payment.id.clone())),
+        package_id: Set(Some(package.id)),
+        expires_at: Set(Some(now + duration)),
+        credits: Set(0),
+        ..Default::default()
+      }
+      .insert(txn)
+      .await
+    }
+  }
+  .map_err(internal_error("DB error"))?;
+  if package.credits > 0 {
+    credits::adjust(
+      txn,
+      subscription.id,
+      Some(&paid.user_id),
+      credits::GRANT,
+      package.credits,
+      Some(&format!("payment {}", paid.transaction_id)),
+    )
+    .await
+    .map_err(internal_error("DB error"))?;
+  }
+  let subscription = reload(txn, subscription.id).await?;
+  Ok(PaymentResponse::new(payment, subscription))
+}
+
+/// Takes back the credits and time the payment added, as far as they are left.
+async fn refund_payment(
+  txn: &DatabaseTransaction,
+  transaction_id: &str,
+) -> Result<PaymentResponse, ApiError> {
+  let payment = find_payment(txn, transaction_id)
+    .await?
+    .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "Unknown transaction", None))?;
+  let subscription = latest_subscription(txn, &payment).await?;
+  if payment.status == REFUNDED {
+    return Ok(PaymentResponse::new(payment, subscription));
+  }
+  let updated = payments::Entity::update_many()
+    .col_expr(payments::Column::Status, Expr::value(REFUNDED))
+    .col_expr(payments::Column::RefundedAt, Expr::value(Utc::now()))
+    .filter(payments::Column::Id.eq(payment.id.as_str()))
+    .filter(payments::Column::Status.eq(PAID))
+    .exec(txn)
+    .await
+    .map_err(internal_error("DB error"))?;
+  let package = match payment.package_id {
+    Some(package_id) => packages::Entity::find_by_id(package_id)
+      .one(txn)
+      .await
+      .map_err(internal_error("DB error"))?,
+    None => None,
+  };
+  let payment = payments::Entity::find_by_id(payment.id.clone())
+    .one(txn)
+    .await
+    .map_err(internal_error("DB error"))?
+    .unwrap_or(payment);
+  let (Some(subscription), Some(package)) = (subscription, package) else {
+    return Ok(PaymentResponse::new(payment, None));
+  };
+  if updated.rows_affected == 0 {
+    return Ok(PaymentResponse::new(payment, Some(subscription)));
+  }
+
+  let revoked = package.credits.min(subscription.credits);
+  if revoked > 0 {
+    credits::adjust(
+      txn,
+      subscription.id,
+      payment.user_id.as_deref(),
+      credits::REVOKE,
+      -revoked,
+      Some(&format!("refund {transaction_id}")),
+    )
+    .await
+    .map_err(internal_error("DB error"))?;
+  }
+  if let Some(expires_at) = subscription.expires_at {
+    let duration = Duration::days(i64::from(package.duration_days));
+    subscriptions::Entity::update_many()
+      .col_expr(
+        subscriptions::Column::ExpiresAt,
+        Expr::value((expires_at - duration).max(Utc::now())),
+      )
+      .filter(subscriptions::Column::Id.eq(subscription.id))
+      .exec(txn)
+      .await
+      .map_err(internal_error("DB error"))?;
+  }
+  let subscription = reload(txn, subscription.id).await?;
+  Ok(PaymentResponse::new(payment, subscription))
+}
+
+async fn find_payment(
+  txn: &DatabaseTransaction,
+  transaction_id: &str,
+) -> Result<Option<payments::Model>, ApiError> {
+  payments::Entity::find()
+    .filter(payments::Column::TransactionId.eq(transaction_id))
+    .one(txn)
+    .await
+    .map_err(internal_error("DB error"))
+}
+
+/// The subscription a payment went to; later renewals may have moved `payment_id` on.
+async fn latest_subscription(
+  txn: &DatabaseTransaction,
+  payment: &payments::Model,
+) -> Result<Option<subscriptions::Model>, ApiError> {
+  let (Some(user_id), Some(package_id)) = (&payment.user_id, payment.package_id) else {
+    return Ok(None);
+  };
+  subscriptions::Entity::find()
+    .filter(subscriptions::Column::UserId.eq(user_id.as_str()))
+    .filter(subscriptions::Column::PackageId.eq(package_id))
+    .order_by_desc(subscriptions::Column::Id)
+    .one(txn)
+    .await
+    .map_err(internal_error("DB error"))
+}
+
+async fn reload(
+  txn: &DatabaseTransaction,
+  subscription_id: i64,
+) -> Result<Option<subscriptions::Model>, ApiError> {
+  subscriptions::Entity::find_by_id(subscription_id)
+    .one(txn)
+    .await
+    .map_err(internal_error("DB error"))
+}
@@ -0,0 +1,196 @@
+use serde::Deserialize;
+
+use crate::{bad_gateway, internal_error, ToolResult};
+
+use super::{
+  image_base64, read_sse_data, send_request, submit_solution_parameters, turn_messages,
+  ProviderError, Usage, VisionOutput, VisionProvider, VisionRequest, SUBMIT_SOLUTION,
+  SUBMIT_SOLUTION_DESCRIPTION,
+};
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const MAX_TOKENS: u32 = 8192;
+
+/// Anthropic-style Messages API (`POST {base_url}/messages`).
+pub struct AnthropicProvider {
+  client: reqwest::Client,
+  base_url: String,
+  api_key: String,
+}
+
+#[derive(Deserialize)]
+struct MessagesResponse {
+  content: Vec<ContentBlock>,
+  usage: Option<MessagesUsage>,
+}
+
+#[derive(Deserialize)]
+struct MessagesUsage {
+  #[serde(default)]
+  input_tokens: u64,
+  #[serde(default)]
+  output_tokens: u64,
+}
+
+#[derive(Deserialize)]
+struct ContentBlock {
+  #[serde(rename = "type")]
+  r#type: String,
+  text: Option<String>,
+  name: Option<String>,
+  input: Option<serde_json::Value>,
+}
+
+impl AnthropicProvider {
+  pub fn new(client: reqwest::Client, base_url: String, api_key: String) -> Self {
+    Self {
+      client,
+      base_url,
+      api_key,
+    }
+  }
+
+  fn messages(request: &VisionRequest<'_>) -> serde_json::Value {
+    let mut content: Vec<_> = request
+      .images
+      .iter()
+      .map(|image| {
+        serde_json::json!({
+          "type": "image",
+          "source": {
+            "type": "base64",
+            "media_type": image.mime,
+            "data": image_base64(image)
+          }
+        })
+      })
+      .collect();
+    content.push(serde_json::json!({ "type": "text", "text": request.user_text() }));
+    let mut messages = vec![serde_json::json!({
+      "role": "user",
+      "content": content
+    })];
+    messages.extend(turn_messages(request.turns));
+    serde_json::Value::Array(messages)
+  }
+
+  async fn post(&self, body: &serde_json::Value) -> Result<reqwest::Response, ProviderError> {
+    let request = self
+      .client
+      .post(format!("{}/messages", self.base_url))
+      .header("x-api-key", &self.api_key)
+      .header("anthropic-version", ANTHROPIC_VERSION)
+      .json(body);
+    send_request("Anthropic", request).await
+  }
+}
+
+#[async_trait::async_trait]
+impl VisionProvider for AnthropicProvider {
+  async fn solve(
+    &self,
+    request: &VisionRequest<'_>,
+  ) -> Result<(VisionOutput, Option<Usage>), ProviderError> {
+    let body = serde_json::json!({
+      "model": request.model,
+      "max_tokens": MAX_TOKENS,
+      "system": request.system_prompt,
+      "messages": Self::messages(request),
+      "tools": [
+        {
+          "name": SUBMIT_SOLUTION,
+          "description": SUBMIT_SOLUTION_DESCRIPTION,
+          "input_schema": submit_solution_parameters()
+        }
+      ],
+      "tool_choice": { "type": "tool", "name": SUBMIT_SOLUTION }
+    });
+
+    let api: MessagesResponse = self
+      .post(&body)
+      .await?
+      .json()
+      .await
+      .map_err(internal_error("Invalid Anthropic JSON response"))?;
+    let usage = api.usage.as_ref().map(|usage| Usage {
+      input_tokens: usage.input_tokens,
+      output_tokens: usage.output_tokens,
+    });
+    for block in &api.content {
+      if block.r#type != "tool_use" || block.name.as_deref() != Some(SUBMIT_SOLUTION) {
+        continue;
+      }
+      if let Some(input) = &block.input {
+        if let Ok(tool) = serde_json::from_value::<ToolResult>(input.clone()) {
+          return Ok((VisionOutput::Tool(tool), usage));
+        }
+      }
+    }
+    api
+      .content
+      .iter()
+      .filter(|block| block.r#type == "text")
+      .find_map(|block| block.text.clone())
+      .map(|text| (VisionOutput::Text(text), usage))
+      .ok_or_else(|| bad_gateway("Missing Anthropic output"))
+  }
+
+  async fn stream(
+    &self,
+    request: &VisionRequest<'_>,
+    on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+  ) -> Result<Option<Usage>, ProviderError> {
+    let body = serde_json::json!({
+      "model": request.model,
+      "max_tokens": MAX_TOKENS,
+      "stream": true,
+      "system": request.system_prompt,
+      "messages": Self::messages(request)
+    });
+
+    let response = self.post(&body).await?;
+    let mut usage: Option<Usage> = None;
+    read_sse_data(response, |payload| {
+      let Ok(value) = serde_json::from_str::<serde_json::Value>(payload) else {
+        return Ok(false);
+      };
+      match value.get("type").and_then(|v| v.as_str()) {
+        // Input tokens arrive with `message_start`, the output count with `message_delta`.
+        Some("message_start") | Some("message_delta") => {
+          let reported = value
+            .pointer("/message/usage")
+            .or_else(|| value.get("usage"))
+            .and_then(|v| serde_json::from_value::<MessagesUsage>(v.clone()).ok());
+          if let Some(reported) = reported {
+            let usage = usage.get_or_insert_with(Usage::default);
+            usage.input_tokens = usage.input_tokens.max(reported.input_tokens);
+            usage.output_tokens = usage.output_tokens.max(reported.output_tokens);
+          }
+          Ok(false)
+        }
+        Some("content_block_delta") => {
+          let delta = value.get("delta");
+          if delta.and_then(|d| d.get("type")).and_then(|v| v.as_str()) == Some("text_delta") {
+            if let Some(text) = delta.and_then(|d| d.get("text")).and_then(|v| v.as_str()) {
+              if !text.is_empty() {
+                on_delta(text);
+              }
+            }
+          }
+          Ok(false)
+        }
+        Some("message_stop") => Ok(true),
+        Some("error") => {
+          let message = value
+            .pointer("/error/message")
+            .and_then(|v| v.as_str())
+            .unwrap_or("unknown error");
+          Err(bad_gateway(&format!("Anthropic stream error: {message}")))
+        }
+        _ => Ok(false),
+      }
+    })
+    .await?;
+    Ok(usage)
+  }
+}
@@ -0,0 +1,202 @@
+use serde::Deserialize;
+
+use crate::{bad_gateway, internal_error};
+
+use super::{
+  image_data_url, parse_tool_arguments, read_sse_data, send_request, submit_solution_parameters,
+  turn_messages, ProviderError, Usage, VisionOutput, VisionProvider, VisionRequest,
+  SUBMIT_SOLUTION, SUBMIT_SOLUTION_DESCRIPTION,
+};
+
+/// OpenAI Responses API (`POST {base_url}/responses`).
+pub struct OpenAiProvider {
+  client: reqwest::Client,
+  base_url: String,
+  api_key: String,
+}
+
+#[derive(Deserialize)]
+struct OpenAiResponse {
+  output: Vec<OpenAiOutput>,
+  usage: Option<OpenAiUsage>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiUsage {
+  input_tokens: u64,
+  output_tokens: u64,
+}
+
+impl From<OpenAiUsage> for Usage {
+  fn from(usage: OpenAiUsage) -> Self {
+    Usage {
+      input_tokens: usage.input_tokens,
+      output_tokens: usage.output_tokens,
+    }
+  }
+}
+
+#[derive(Deserialize)]
+struct OpenAiOutput {
+  #[serde(rename = "type")]
+  r#type: String,
+  content: Option<Vec<OpenAiContent>>,
+  name: Option<String>,
+  arguments: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiContent {
+  #[serde(rename = "type")]
+  r#type: String,
+  text: Option<String>,
+}
+
+impl OpenAiProvider {
+  pub fn new(client: reqwest::Client, base_url: String, api_key: String) -> Self {
+    Self {
+      client,
+      base_url,
+      api_key,
+    }
+  }
+
+  fn input(request: &VisionRequest<'_>) -> serde_json::Value {
+    let mut content = vec![serde_json::json!({ "type": "input_text", "text": request.user_text() })];
+    content.extend(request.images.iter().map(|image| {
+      serde_json::json!({ "type": "input_image", "image_url": image_data_url(image) })
+    }));
+    let mut input = vec![
+      serde_json::json!({
+        "role": "system",
+        "content": [
+          { "type": "input_text", "text": request.system_prompt }
+        ]
+      }),
+      serde_json::json!({
+        "role": "user",
+        "content": content
+      }),
+    ];
+    input.extend(turn_messages(request.turns));
+    serde_json::Value::Array(input)
+  }
+
+  async fn post(&self, body: &serde_json::Value) -> Result<reqwest::Response, ProviderError> {
+    let request = self
+      .client
+      .post(format!("{}/responses", self.base_url))
+      .bearer_auth(&self.api_key)
+      .json(body);
+    send_request("OpenAI", request).await
+  }
+}
+
+#[async_trait::async_trait]
+impl VisionProvider for OpenAiProvider {
+  async fn solve(
+    &self,
+    request: &VisionRequest<'_>,
+  ) -> Result<(VisionOutput, Option<Usage>), ProviderError> {
+    let body = serde_json::json!({
+      "model": request.model,
+      "input": Self::input(request),
+      "tools": [
+        {
+          "type": "function",
+          "name": SUBMIT_SOLUTION,
+          "description": SUBMIT_SOLUTION_DESCRIPTION,
+          "parameters": submit_solution_parameters(),
+          "strict": true
+        }
+      ],
+      "tool_choice": { "type": "function", "name": SUBMIT_SOLUTION }
+    });
+
+    let mut api: OpenAiResponse = self
+      .post(&body)
+      .await?
+      .json()
+      .await
+      .map_err(internal_error("Invalid OpenAI JSON response"))?;
+    let usage = api.usage.take().map(Usage::from);
+    if let Some(tool) = extract_tool_call(&api) {
+      return Ok((VisionOutput::Tool(tool), usage));
+    }
+    extract_output_text(&api)
+      .map(|text| (VisionOutput::Text(text), usage))
+      .ok_or_else(|| bad_gateway("Missing OpenAI output"))
+  }
+
+  async fn stream(
+    &self,
+    request: &VisionRequest<'_>,
+    on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+  ) -> Result<Option<Usage>, ProviderError> {
+    let body = serde_json::json!({
+      "model": request.model,
+      "stream": true,
+      "input": Self::input(request)
+    });
+
+    let response = self.post(&body).await?;
+    let mut usage = None;
+    read_sse_data(response, |payload| {
+      if let Ok(value) = serde_json::from_str::<serde_json::Value>(payload) {
+        match value.get("type").and_then(|v| v.as_str()) {
+          Some("response.output_text.delta") => {
+            if let Some(delta) = value.get("delta").and_then(|v| v.as_str()) {
+              if !delta.is_empty() {
+                on_delta(delta);
+              }
+            }
+          }
+          Some("response.completed") => {
+            usage = value
+              .pointer("/response/usage")
+              .and_then(|v| serde_json::from_value::<OpenAiUsage>(v.clone()).ok())
+              .map(Usage::from);
+            return Ok(true);
+          }
+          _ => {}
+        }
+      }
+      Ok(false)
+    })
+    .await?;
+    Ok(usage)
+  }
+}
+
+fn extract_output_text(api: &OpenAiResponse) -> Option<String> {
+  for item in &api.output {
+    if item.r#type != "message" {
+      continue;
+    }
+    if let Some(content) = &item.content {
+      for part in content {
+        if part.r#type == "output_text" {
+          if let Some(text) = &part.text {
+            return Some(text.clone());
+          }
+        }
+      }
+    }
+  }
+  None
+}
+
+fn extract_tool_call(api: &OpenAiResponse) -> Option<crate::ToolResult> {
+  for item in &api.output {
+    if item.r#type != "function_call" {
+      continue;
+    }
+    if item.name.as_deref() != Some(SUBMIT_SOLUTION) {
+      continue;
+    }
+    if let Some(tool) = item.arguments.as_deref().and_then(parse_tool_arguments) {
+      return Some(tool);
+    }
+  }
+  None
+}
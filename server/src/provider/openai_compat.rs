@@ -0,0 +1,186 @@
+use serde::Deserialize;
+
+use crate::{bad_gateway, internal_error};
+
+use super::{
+  image_data_url, parse_tool_arguments, read_sse_data, send_request, submit_solution_parameters,
+  turn_messages, ProviderError, Usage, VisionOutput, VisionProvider, VisionRequest,
+  SUBMIT_SOLUTION, SUBMIT_SOLUTION_DESCRIPTION,
+};
+
+/// OpenAI-compatible Chat Completions API (`POST {base_url}/chat/completions`),
+/// as served by Ollama, vLLM and the llama.cpp server.
+pub struct OpenAiCompatProvider {
+  client: reqwest::Client,
+  base_url: String,
+  api_key: String,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+  choices: Vec<ChatChoice>,
+  usage: Option<ChatUsage>,
+}
+
+#[derive(Deserialize)]
+struct ChatUsage {
+  prompt_tokens: u64,
+  completion_tokens: u64,
+}
+
+impl From<ChatUsage> for Usage {
+  fn from(usage: ChatUsage) -> Self {
+    Usage {
+      input_tokens: usage.prompt_tokens,
+      output_tokens: usage.completion_tokens,
+    }
+  }
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+  message: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatMessage {
+  content: Option<String>,
+  tool_calls: Option<Vec<ChatToolCall>>,
+}
+
+#[derive(Deserialize)]
+struct ChatToolCall {
+  function: ChatFunction,
+}
+
+#[derive(Deserialize)]
+struct ChatFunction {
+  name: String,
+  arguments: String,
+}
+
+impl OpenAiCompatProvider {
+  pub fn new(client: reqwest::Client, base_url: String, api_key: String) -> Self {
+    Self {
+      client,
+      base_url,
+      api_key,
+    }
+  }
+
+  fn messages(request: &VisionRequest<'_>) -> serde_json::Value {
+    let mut content = vec![serde_json::json!({ "type": "text", "text": request.user_text() })];
+    content.extend(request.images.iter().map(|image| {
+      serde_json::json!({ "type": "image_url", "image_url": { "url": image_data_url(image) } })
+    }));
+    let mut messages = vec![
+      serde_json::json!({ "role": "system", "content": request.system_prompt }),
+      serde_json::json!({
+        "role": "user",
+        "content": content
+      }),
+    ];
+    messages.extend(turn_messages(request.turns));
+    serde_json::Value::Array(messages)
+  }
+
+  async fn post(&self, body: &serde_json::Value) -> Result<reqwest::Response, ProviderError> {
+    let mut request = self
+      .client
+      .post(format!("{}/chat/completions", self.base_url))
+      .json(body);
+    // Local model servers usually run without authentication.
+    if !self.api_key.is_empty() {
+      request = request.bearer_auth(&self.api_key);
+    }
+    send_request("Model server", request).await
+  }
+}
+
+#[async_trait::async_trait]
+impl VisionProvider for OpenAiCompatProvider {
+  async fn solve(
+    &self,
+    request: &VisionRequest<'_>,
+  ) -> Result<(VisionOutput, Option<Usage>), ProviderError> {
+    let body = serde_json::json!({
+      "model": request.model,
+      "messages": Self::messages(request),
+      "tools": [
+        {
+          "type": "function",
+          "function": {
+            "name": SUBMIT_SOLUTION,
+            "description": SUBMIT_SOLUTION_DESCRIPTION,
+            "parameters": submit_solution_parameters()
+          }
+        }
+      ],
+      "tool_choice": { "type": "function", "function": { "name": SUBMIT_SOLUTION } }
+    });
+
+    let api: ChatResponse = self
+      .post(&body)
+      .await?
+      .json()
+      .await
+      .map_err(internal_error("Invalid model server JSON response"))?;
+    let usage = api.usage.map(Usage::from);
+    let message = api
+      .choices
+      .into_iter()
+      .next()
+      .map(|choice| choice.message)
+      .ok_or_else(|| bad_gateway("Missing model server output"))?;
+    let tool = message
+      .tool_calls
+      .unwrap_or_default()
+      .into_iter()
+      .filter(|call| call.function.name == SUBMIT_SOLUTION)
+      .find_map(|call| parse_tool_arguments(&call.function.arguments));
+    if let Some(tool) = tool {
+      return Ok((VisionOutput::Tool(tool), usage));
+    }
+    message
+      .content
+      .filter(|content| !content.trim().is_empty())
+      .map(|content| (VisionOutput::Text(content), usage))
+      .ok_or_else(|| bad_gateway("Missing model server output"))
+  }
+
+  async fn stream(
+    &self,
+    request: &VisionRequest<'_>,
+    on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+  ) -> Result<Option<Usage>, ProviderError> {
+    let body = serde_json::json!({
+      "model": request.model,
+      "stream": true,
+      "stream_options": { "include_usage": true },
+      "messages": Self::messages(request)
+    });
+
+    let response = self.post(&body).await?;
+    let mut usage = None;
+    read_sse_data(response, |payload| {
+      if let Ok(value) = serde_json::from_str::<serde_json::Value>(payload) {
+        if let Some(delta) = value.pointer("/choices/0/delta/content").and_then(|v| v.as_str()) {
+          if !delta.is_empty() {
+            on_delta(delta);
+          }
+        }
+        // With `include_usage` the last chunk carries the totals and no choices.
+        if let Some(reported) = value
+          .get("usage")
+          .and_then(|v| serde_json::from_value::<ChatUsage>(v.clone()).ok())
+        {
+          usage = Some(Usage::from(reported));
+        }
+      }
+      // Chat completions streams end with `[DONE]`, which `read_sse_data` looks for.
+      Ok(false)
+    })
+    .await?;
+    Ok(usage)
+  }
+}
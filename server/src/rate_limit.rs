@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::{http::StatusCode, Json};
+use serde::Serialize;
+
+use crate::keys::ApiKey;
+use crate::{error_response, ErrorResponse};
+
+/// `packages.rate_limit` and `keys.rate_limit` count requests per this window.
+const WINDOW: Duration = Duration::from_secs(60);
+/// Buckets are pruned once this many are tracked; full buckets carry no state.
+const MAX_BUCKETS: usize = 10_000;
+
+/// Token buckets that hold `limit` requests and refill `limit` per `WINDOW`.
+#[derive(Default)]
+pub struct RateLimiter {
+  buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+struct Bucket {
+  tokens: f64,
+  limit: f64,
+  updated: Instant,
+}
+
+impl Bucket {
+  fn refill(&mut self, limit: f64, now: Instant) {
+    let elapsed = now.duration_since(self.updated).as_secs_f64();
+    self.limit = limit;
+    self.tokens = (self.tokens + elapsed * limit / WINDOW.as_secs_f64()).min(limit);
+    self.updated = now;
+  }
+
+  fn wait(&self) -> Duration {
+    let missing = (1.0 - self.tokens).max(0.0);
+    Duration::from_secs_f64(missing * WINDOW.as_secs_f64() / self.limit)
+  }
+}
+
+impl RateLimiter {
+  /// Takes one request from every `(bucket, limit)` or none; on failure returns how long
+  /// until all of them allow one again.
+  fn acquire(&self, limits: &[(String, u32)]) -> Result<(), Duration> {
+    let now = Instant::now();
+    let mut buckets = self.buckets.lock().unwrap_or_else(|err| err.into_inner());
+    if buckets.len() > MAX_BUCKETS {
+      buckets.retain(|_, bucket| {
+        bucket.refill(bucket.limit, now);
+        bucket.tokens < bucket.limit
+      });
+    }
+
+    let mut wait = Duration::ZERO;
+    for (name, limit) in limits {
+      let limit = f64::from(*limit);
+      let bucket = buckets.entry(name.clone()).or_insert(Bucket {
+        tokens: limit,
+        limit,
+        updated: now,
+      });
+      bucket.refill(limit, now);
+      wait = wait.max(bucket.wait());
+    }
+    if !wait.is_zero() {
+      return Err(wait);
+    }
+    for (name, _) in limits {
+      if let Some(bucket) = buckets.get_mut(name) {
+        bucket.tokens -= 1.0;
+      }
+    }
+    Ok(())
+  }
+
+  /// The requests `limits` still allow right now, without taking one.
+  fn available(&self, limits: &[(String, u32)]) -> u32 {
+    let now = Instant::now();
+    let mut buckets = self.buckets.lock().unwrap_or_else(|err| err.into_inner());
+    limits
+      .iter()
+      .map(|(name, limit)| match buckets.get_mut(name) {
+        Some(bucket) => {
+          bucket.refill(f64::from(*limit), now);
+          bucket.tokens.floor() as u32
+        }
+        None => *limit,
+      })
+      .min()
+      .unwrap_or(0)
+  }
+
+  /// Applies the package limit to the user and the (possibly lower) key limit to the key.
+  /// A package limit of zero or less disables rate limiting.
+  pub fn check(
+    &self,
+    key: &ApiKey,
+    package_limit: i32,
+  ) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let Some(limits) = limits(key, package_limit) else {
+      return Ok(());
+    };
+    self.acquire(&limits).map_err(|wait| {
+      let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+      let mut response = error_response(
+        StatusCode::TOO_MANY_REQUESTS,
+        &format!("Rate limit exceeded, retry in {retry_after}s"),
+        Some(112),
+      );
+      response.1.error.retry_after = Some(retry_after.max(1));
+      response
+    })
+  }
+
+  /// The window `key` is limited by, or `None` when rate limiting is off.
+  pub fn window(&self, key: &ApiKey, package_limit: i32) -> Option<RateWindow> {
+    let limits = limits(key, package_limit)?;
+    Some(RateWindow {
+      limit: limits.iter().map(|(_, limit)| *limit).min().unwrap_or(0),
+      window_seconds: WINDOW.as_secs(),
+      remaining: self.available(&limits),
+    })
+  }
+}
+
+#[derive(Serialize)]
+pub struct RateWindow {
+  /// Requests allowed per window for this key.
+  pub limit: u32,
+  pub window_seconds: u64,
+  /// Requests that would be allowed right now.
+  pub remaining: u32,
+}
+
+/// The user and key buckets for `key`, or `None` when the package has no limit.
+fn limits(key: &ApiKey, package_limit: i32) -> Option<[(String, u32); 2]> {
+  let package_limit = u32::try_from(package_limit).ok().filter(|limit| *limit > 0)?;
+  let key_limit = key
+    .rate_limit
+    .and_then(|limit| u32::try_from(limit).ok())
+    .filter(|limit| *limit > 0)
+    .map_or(package_limit, |limit| limit.min(package_limit));
+  Some([
+    (format!("user:{}", key.user_id), package_limit),
+    (format!("key:{}", key.id), key_limit),
+  ])
+}
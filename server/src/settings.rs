@@ -0,0 +1,139 @@
+use axum::{
+  body::Bytes,
+  extract::State,
+  http::{HeaderMap, StatusCode},
+  Json,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::entity::settings;
+use crate::keys::authenticate;
+use crate::{
+  bad_request, error_response, internal_error, parse_json_body, AppState, ErrorResponse,
+};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+const SETTINGS_NAME: &str = "default";
+const MAX_CONFIG_BYTES: usize = 64 * 1024;
+/// Client fields that belong to one machine and never leave it.
+const LOCAL_ONLY: [&str; 2] = ["api_key", "main_position"];
+
+#[derive(Serialize)]
+pub struct SettingsResponse {
+  config: serde_json::Value,
+  updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+struct PutSettingsRequest {
+  config: serde_json::Value,
+  /// When the client last changed these settings; older than the stored copy is a conflict.
+  updated_at: Option<DateTime<Utc>>,
+}
+
+impl From<Option<settings::Model>> for SettingsResponse {
+  fn from(model: Option<settings::Model>) -> Self {
+    match model {
+      Some(model) => Self {
+        config: strip_local(model.config.unwrap_or_else(empty_config)),
+        updated_at: model.e_time.or(model.c_time),
+      },
+      None => Self {
+        config: empty_config(),
+        updated_at: None,
+      },
+    }
+  }
+}
+
+pub async fn get_settings(
+  State(state): State<AppState>,
+  headers: HeaderMap,
+) -> Result<Json<SettingsResponse>, ApiError> {
+  let caller = authenticate(&state.db, &headers).await?;
+  let stored = find_settings(&state.db, &caller.user_id).await?;
+  Ok(Json(SettingsResponse::from(stored)))
+}
+
+pub async fn put_settings(
+  State(state): State<AppState>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Result<Json<SettingsResponse>, ApiError> {
+  let caller = authenticate(&state.db, &headers).await?;
+  if body.len() > MAX_CONFIG_BYTES {
+    return Err(error_response(
+      StatusCode::PAYLOAD_TOO_LARGE,
+      "Settings are too large",
+      None,
+    ));
+  }
+  let request: PutSettingsRequest = parse_json_body(&body)?;
+  if !request.config.is_object() {
+    return Err(bad_request("`config` must be a JSON object"));
+  }
+  let now = Utc::now();
+  let updated_at = request.updated_at.map_or(now, |at| at.min(now));
+  let config = strip_local(request.config);
+
+  let stored = find_settings(&state.db, &caller.user_id).await?;
+  let saved = match stored {
+    Some(model) => {
+      let stored_at = model.e_time.or(model.c_time);
+      if stored_at.is_some_and(|stored_at| stored_at > updated_at) {
+        return Err(error_response(
+          StatusCode::CONFLICT,
+          "Settings were changed on another device",
+          Some(111),
+        ));
+      }
+      let mut active: settings::ActiveModel = model.into();
+      active.config = Set(Some(config));
+      active.e_time = Set(Some(updated_at));
+      active.update(&state.db).await
+    }
+    None => {
+      settings::ActiveModel {
+        id: Set(Uuid::new_v4().to_string()),
+        user_id: Set(Some(caller.user_id)),
+        name: Set(Some(SETTINGS_NAME.to_string())),
+        config: Set(Some(config)),
+        e_time: Set(Some(updated_at)),
+        ..Default::default()
+      }
+      .insert(&state.db)
+      .await
+    }
+  }
+  .map_err(internal_error("DB error"))?;
+  Ok(Json(SettingsResponse::from(Some(saved))))
+}
+
+async fn find_settings(
+  db: &DatabaseConnection,
+  user_id: &str,
+) -> Result<Option<settings::Model>, ApiError> {
+  settings::Entity::find()
+    .filter(settings::Column::UserId.eq(user_id))
+    .filter(settings::Column::Name.eq(SETTINGS_NAME))
+    .one(db)
+    .await
+    .map_err(internal_error("DB error"))
+}
+
+fn strip_local(mut config: serde_json::Value) -> serde_json::Value {
+  if let Some(object) = config.as_object_mut() {
+    for key in LOCAL_ONLY {
+      object.remove(key);
+    }
+  }
+  config
+}
+
+fn empty_config() -> serde_json::Value {
+  serde_json::Value::Object(Default::default())
+}
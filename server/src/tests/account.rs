@@ -0,0 +1,54 @@
+use reqwest::{Method, StatusCode};
+use sea_orm::ConnectionTrait;
+use serde_json::{json, Value};
+
+use super::TestServer;
+
+async fn me(server: &TestServer) -> Value {
+  let response = server
+    .request(Method::GET, "/me", None)
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  response.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn me_shows_profile_and_balance() {
+  let server = TestServer::start(5).await;
+  let body = me(&server).await;
+  assert_eq!(body["user"]["email"], "user@example.com");
+  assert_eq!(body["user"]["confirmed"], true);
+  assert_eq!(body["package"], "Free");
+  assert_eq!(body["credits"], 5);
+  assert_eq!(body["expires_at"], Value::Null);
+  assert_eq!(body["rate_limit"], Value::Null);
+
+  let response = server
+    .client
+    .get(format!("{}/me", server.url))
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn me_tracks_spending_and_rate_window() {
+  let server = TestServer::start(5).await;
+  server
+    .db
+    .execute_unprepared("UPDATE packages SET rate_limit = 3")
+    .await
+    .unwrap();
+  assert_eq!(me(&server).await["rate_limit"]["remaining"], 3);
+
+  assert_eq!(server.ingest("default").await.status(), StatusCode::OK);
+  let body = me(&server).await;
+  assert_eq!(body["credits"], 4);
+  assert_eq!(
+    body["rate_limit"],
+    json!({ "limit": 3, "window_seconds": 60, "remaining": 2 })
+  );
+}
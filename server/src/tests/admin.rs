@@ -0,0 +1,249 @@
+use reqwest::{Method, StatusCode};
+use sea_orm::{ConnectionTrait, EntityTrait, QueryOrder};
+use serde_json::{json, Value};
+
+use super::{TestServer, USER_ID};
+use crate::entity::{admin_audit, credit_ledger, keys};
+use crate::keys::Scope;
+
+/// Adds a user with a role of `elevation` and returns the id and token of a key with the
+/// `admin` scope.
+async fn staff_key(server: &TestServer, name: &str, elevation: i32) -> (String, String) {
+  let id = uuid::Uuid::new_v4().to_string();
+  server
+    .db
+    .execute_unprepared(&format!(
+      "INSERT INTO users (id, email, password, confirmd) VALUES ('{id}', '{name}@example.com', 'x', 1);
+       INSERT INTO roles (id, user_id, name, elevation) VALUES ('{}', '{id}', '{name}', {elevation});",
+      uuid::Uuid::new_v4()
+    ))
+    .await
+    .unwrap();
+  let (key, token) = crate::keys::issue_key(&server.db, &id, "admin", &[Scope::Admin], None, None)
+    .await
+    .unwrap();
+  (key.id, token)
+}
+
+async fn actions(server: &TestServer) -> Vec<String> {
+  admin_audit::Entity::find()
+    .order_by_asc(admin_audit::Column::Id)
+    .all(&server.db)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|entry| entry.action)
+    .collect()
+}
+
+#[tokio::test]
+async fn requires_admin_scope_and_staff_role() {
+  let server = TestServer::start(5).await;
+  let response = server
+    .request(Method::GET, "/admin/users", None)
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::FORBIDDEN);
+  let body: Value = response.json().await.unwrap();
+  assert_eq!(body["error"]["code"], 105);
+
+  let (_, plain) = staff_key(&server, "plain", 10).await;
+  let response = server
+    .request(Method::GET, "/admin/users", Some(&plain))
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::FORBIDDEN);
+  let body: Value = response.json().await.unwrap();
+  assert_eq!(body["error"]["code"], 113);
+  assert!(actions(&server).await.is_empty());
+}
+
+#[tokio::test]
+async fn user_list_pairs_each_user_with_their_own_subscription() {
+  let server = TestServer::start(5).await;
+  let (_, moderator) = staff_key(&server, "mod", 50).await;
+
+  let users: Value = server
+    .request(Method::GET, "/admin/users", Some(&moderator))
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+  let users = users.as_array().unwrap();
+  let user = users.iter().find(|user| user["id"] == USER_ID).unwrap();
+  assert_eq!(user["package"], "Free");
+  assert_eq!(user["credits"], 5);
+  let staff = users
+    .iter()
+    .find(|user| user["email"] == "mod@example.com")
+    .unwrap();
+  assert!(staff["package"].is_null());
+  assert!(staff["credits"].is_null());
+}
+
+#[tokio::test]
+async fn moderator_browses_and_revokes_but_cannot_grant() {
+  let server = TestServer::start(5).await;
+  let (_, moderator) = staff_key(&server, "mod", 50).await;
+  server.ingest("default").await;
+
+  let users: Value = server
+    .request(Method::GET, "/admin/users?search=user@", Some(&moderator))
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+  assert_eq!(users.as_array().unwrap().len(), 1);
+  assert_eq!(users[0]["id"], USER_ID);
+  assert_eq!(users[0]["package"], "Free");
+  assert_eq!(users[0]["credits"], 4);
+
+  let results: Value = server
+    .request(
+      Method::GET,
+      &format!("/admin/screen_results?user_id={USER_ID}"),
+      Some(&moderator),
+    )
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+  assert_eq!(results[0]["status"], "DONE");
+  assert!(results[0].get("debug").is_none());
+
+  let response = server
+    .request(
+      Method::POST,
+      &format!("/admin/users/{USER_ID}/credits"),
+      Some(&moderator),
+    )
+    .json(&json!({ "amount": 100 }))
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::FORBIDDEN);
+  let body: Value = response.json().await.unwrap();
+  assert_eq!(body["error"]["code"], 113);
+
+  let key = keys::Entity::find()
+    .all(&server.db)
+    .await
+    .unwrap()
+    .into_iter()
+    .find(|key| key.user_id.as_deref() == Some(USER_ID))
+    .unwrap();
+  let response = server
+    .request(
+      Method::DELETE,
+      &format!("/admin/keys/{}", key.id),
+      Some(&moderator),
+    )
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NO_CONTENT);
+  assert_eq!(
+    server.ingest("default").await.status(),
+    StatusCode::UNAUTHORIZED
+  );
+
+  let (admin_key, _) = staff_key(&server, "admin", 100).await;
+  let response = server
+    .request(
+      Method::DELETE,
+      &format!("/admin/keys/{admin_key}"),
+      Some(&moderator),
+    )
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+  assert_eq!(
+    actions(&server).await,
+    ["users.list", "screen_results.list", "keys.revoke"]
+  );
+}
+
+#[tokio::test]
+async fn admin_grants_credits_and_changes_package() {
+  let server = TestServer::start(5).await;
+  let (_, admin) = staff_key(&server, "admin", 100).await;
+  server
+    .db
+    .execute_unprepared("INSERT INTO packages (id, name, rate_limit) VALUES (2, 'Pro', 600)")
+    .await
+    .unwrap();
+
+  let response = server
+    .request(
+      Method::POST,
+      &format!("/admin/users/{USER_ID}/credits"),
+      Some(&admin),
+    )
+    .json(&json!({ "amount": 20, "note": "support ticket 7" }))
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let body: Value = response.json().await.unwrap();
+  assert_eq!(body["credits"], 25);
+  assert_eq!(server.credits().await, 25);
+
+  let response = server
+    .request(
+      Method::POST,
+      &format!("/admin/users/{USER_ID}/credits"),
+      Some(&admin),
+    )
+    .json(&json!({ "amount": -100 }))
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+  assert_eq!(server.credits().await, 25);
+
+  let entries = credit_ledger::Entity::find().all(&server.db).await.unwrap();
+  assert_eq!(entries.len(), 1);
+  assert_eq!(entries[0].kind, crate::credits::ADJUST);
+  assert_eq!(entries[0].note.as_deref(), Some("support ticket 7"));
+
+  let response = server
+    .request(
+      Method::PUT,
+      &format!("/admin/users/{USER_ID}/package"),
+      Some(&admin),
+    )
+    .json(&json!({ "package": "Pro" }))
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let body: Value = response.json().await.unwrap();
+  assert_eq!(body["package"], "Pro");
+  assert_eq!(body["credits"], 25);
+
+  let audit: Value = server
+    .request(Method::GET, "/admin/audit", Some(&admin))
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+  assert_eq!(audit[0]["action"], "package.change");
+  assert_eq!(audit[0]["target"], USER_ID);
+  assert_eq!(audit[0]["details"]["from"], "Free");
+  assert_eq!(audit[0]["details"]["to"], "Pro");
+  assert_eq!(audit[1]["action"], "credits.adjust");
+  assert_eq!(audit[1]["details"]["amount"], 20);
+  assert_eq!(audit.as_array().unwrap().len(), 2);
+}
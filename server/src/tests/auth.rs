@@ -0,0 +1,196 @@
+use reqwest::{Method, StatusCode};
+use sea_orm::EntityTrait;
+use serde_json::{json, Value};
+
+use super::{TestServer, USER_ID};
+use crate::entity::users;
+
+/// The hex token that follows `marker` in the one mail containing it.
+fn token_from_mail(server: &TestServer, marker: &str) -> String {
+  let mails: Vec<_> = server
+    .mails()
+    .into_iter()
+    .filter(|mail| mail.contains(marker))
+    .collect();
+  assert_eq!(mails.len(), 1, "expected one mail containing {marker}");
+  let start = mails[0].find(marker).unwrap() + marker.len();
+  mails[0][start..]
+    .chars()
+    .take_while(char::is_ascii_hexdigit)
+    .collect()
+}
+
+async fn send(server: &TestServer, path: &str, body: Value) -> (StatusCode, Value) {
+  let response = server
+    .client
+    .post(format!("{}{path}", server.url))
+    .json(&body)
+    .send()
+    .await
+    .unwrap();
+  let status = response.status();
+  (status, response.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn register_confirm_and_login() {
+  let server = TestServer::start(5).await;
+  let credentials = json!({ "email": "New@Example.com", "password": "correct horse" });
+
+  let (status, user) = send(&server, "/auth/register", credentials.clone()).await;
+  assert_eq!(status, StatusCode::CREATED);
+  assert_eq!(user["email"], "new@example.com");
+  assert_eq!(user["confirmed"], false);
+  let stored = users::Entity::find_by_id(user["id"].as_str().unwrap())
+    .one(&server.db)
+    .await
+    .unwrap()
+    .unwrap();
+  assert!(stored.password.starts_with("$argon2"));
+
+  let (status, body) = send(&server, "/auth/register", credentials.clone()).await;
+  assert_eq!(status, StatusCode::CONFLICT);
+  assert_eq!(body["error"]["code"], 106);
+
+  let (status, body) = send(&server, "/auth/login", credentials.clone()).await;
+  assert_eq!(status, StatusCode::FORBIDDEN);
+  assert_eq!(body["error"]["code"], 107);
+
+  let token = token_from_mail(&server, "http://faux.test/auth/confirm?token=");
+  let confirm = format!("{}/auth/confirm?token={token}", server.url);
+  let response = server.client.get(&confirm).send().await.unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let response = server.client.get(&confirm).send().await.unwrap();
+  assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+  let body: Value = response.json().await.unwrap();
+  assert_eq!(body["error"]["code"], 108);
+
+  let (status, body) = send(
+    &server,
+    "/auth/login",
+    json!({ "email": "new@example.com", "password": "wrong horse" }),
+  )
+  .await;
+  assert_eq!(status, StatusCode::UNAUTHORIZED);
+  assert_eq!(body["error"]["code"], 100);
+
+  let (status, session) = send(&server, "/auth/login", credentials).await;
+  assert_eq!(status, StatusCode::OK);
+  assert!(session["expires_at"].is_string());
+  let keys: Value = server
+    .request(Method::GET, "/keys", session["token"].as_str())
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+  assert_eq!(keys[0]["name"], "session");
+  assert_eq!(keys[0]["scopes"], json!(["ingest", "history"]));
+}
+
+#[tokio::test]
+async fn register_keeps_nothing_when_the_mail_fails() {
+  let server = TestServer::start(5).await;
+  let credentials = json!({ "email": "retry@example.com", "password": "correct horse" });
+  // A file where the mail directory should be makes every send fail.
+  let mail_dir = server.image_dir.join("mail");
+  std::fs::create_dir_all(&server.image_dir).unwrap();
+  std::fs::write(&mail_dir, "").unwrap();
+
+  let (status, _) = send(&server, "/auth/register", credentials.clone()).await;
+  assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+  std::fs::remove_file(&mail_dir).unwrap();
+  let (status, user) = send(&server, "/auth/register", credentials).await;
+  assert_eq!(status, StatusCode::CREATED);
+  assert_eq!(user["email"], "retry@example.com");
+  token_from_mail(&server, "http://faux.test/auth/confirm?token=");
+}
+
+#[tokio::test]
+async fn reset_password_revokes_sessions() {
+  let server = TestServer::start(5).await;
+
+  // The seeded user still has a plaintext password; logging in upgrades it.
+  let (status, session) = send(
+    &server,
+    "/auth/login",
+    json!({ "email": "user@example.com", "password": "x" }),
+  )
+  .await;
+  assert_eq!(status, StatusCode::OK);
+  let stored = users::Entity::find_by_id(USER_ID)
+    .one(&server.db)
+    .await
+    .unwrap()
+    .unwrap();
+  assert!(stored.password.starts_with("$argon2"));
+
+  let (status, _) = send(
+    &server,
+    "/auth/reset",
+    json!({ "email": "nobody@example.com" }),
+  )
+  .await;
+  assert_eq!(status, StatusCode::ACCEPTED);
+  assert!(server.mails().is_empty());
+
+  let (status, _) = send(
+    &server,
+    "/auth/reset",
+    json!({ "email": "user@example.com" }),
+  )
+  .await;
+  assert_eq!(status, StatusCode::ACCEPTED);
+  let token = token_from_mail(&server, "Reset token: ");
+
+  let reset = json!({ "token": token, "password": "brand new password" });
+  let (status, _) = send(&server, "/auth/reset", reset.clone()).await;
+  assert_eq!(status, StatusCode::OK);
+  let (status, body) = send(&server, "/auth/reset", reset).await;
+  assert_eq!(status, StatusCode::BAD_REQUEST);
+  assert_eq!(body["error"]["code"], 108);
+
+  let response = server
+    .request(Method::GET, "/keys", session["token"].as_str())
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+  let response = server
+    .request(Method::GET, "/keys", None)
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+
+  let (status, _) = send(
+    &server,
+    "/auth/login",
+    json!({ "email": "user@example.com", "password": "x" }),
+  )
+  .await;
+  assert_eq!(status, StatusCode::UNAUTHORIZED);
+  let (status, _) = send(
+    &server,
+    "/auth/login",
+    json!({ "email": "user@example.com", "password": "brand new password" }),
+  )
+  .await;
+  assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn register_rejects_bad_input() {
+  let server = TestServer::start(5).await;
+  for body in [
+    json!({ "email": "not-an-email", "password": "long enough" }),
+    json!({ "email": "a@example.com", "password": "short" }),
+    json!({ "email": "a@example.com" }),
+  ] {
+    let (status, _) = send(&server, "/auth/register", body).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+  }
+  assert!(server.mails().is_empty());
+}
@@ -0,0 +1,73 @@
+use reqwest::StatusCode;
+use sea_orm::{ConnectionTrait, Statement};
+
+use super::TestServer;
+
+async fn ledger(server: &TestServer) -> Vec<(String, i32, i32)> {
+  let backend = server.db.get_database_backend();
+  server
+    .db
+    .query_all(Statement::from_string(
+      backend,
+      "SELECT kind, amount, balance FROM credit_ledger ORDER BY id",
+    ))
+    .await
+    .expect("query ledger")
+    .iter()
+    .map(|row| {
+      (
+        row.try_get("", "kind").unwrap(),
+        row.try_get("", "amount").unwrap(),
+        row.try_get("", "balance").unwrap(),
+      )
+    })
+    .collect()
+}
+
+#[tokio::test]
+async fn successful_ingest_reserves_then_commits() {
+  let server = TestServer::start(5).await;
+  assert_eq!(server.ingest("default").await.status(), StatusCode::OK);
+
+  assert_eq!(server.credits().await, 4);
+  assert_eq!(
+    ledger(&server).await,
+    [("RESERVE".to_string(), -1, 4), ("COMMIT".to_string(), 0, 4)]
+  );
+}
+
+#[tokio::test]
+async fn failed_ingest_is_refunded() {
+  let server = TestServer::start(5).await;
+  assert_eq!(
+    server.ingest("overloaded").await.status(),
+    StatusCode::BAD_GATEWAY
+  );
+
+  assert_eq!(server.credits().await, 5);
+  assert_eq!(
+    ledger(&server).await,
+    [("RESERVE".to_string(), -1, 4), ("REFUND".to_string(), 1, 5)]
+  );
+}
+
+#[tokio::test]
+async fn concurrent_ingests_never_overdraw() {
+  let server = TestServer::start(3).await;
+  let responses = futures_util::future::join_all((0..8).map(|_| server.ingest("default"))).await;
+
+  let ok = responses
+    .iter()
+    .filter(|response| response.status() == StatusCode::OK)
+    .count();
+  let forbidden = responses
+    .iter()
+    .filter(|response| response.status() == StatusCode::FORBIDDEN)
+    .count();
+  assert_eq!((ok, forbidden), (3, 5));
+  assert_eq!(server.credits().await, 0);
+  assert!(ledger(&server)
+    .await
+    .iter()
+    .all(|(_, _, balance)| *balance >= 0));
+}
@@ -0,0 +1,154 @@
+use sea_orm::{
+  ActiveModelTrait, ColumnTrait, ConnectionTrait, Database, EntityTrait, IntoActiveModel,
+  ModelTrait, PaginatorTrait, QueryFilter, Set,
+};
+use sea_orm_migration::MigratorTrait;
+
+use crate::entity::{keys, packages, roles, subscriptions, users};
+
+#[tokio::test]
+async fn seed_is_idempotent() {
+  let db = Database::connect("sqlite::memory:").await.unwrap();
+  crate::init_db(&db, None).await.unwrap();
+  crate::seed_db(&db).await.unwrap();
+  crate::seed_db(&db).await.unwrap();
+
+  assert_eq!(users::Entity::find().count(&db).await.unwrap(), 3);
+  assert_eq!(keys::Entity::find().count(&db).await.unwrap(), 3);
+  assert_eq!(subscriptions::Entity::find().count(&db).await.unwrap(), 3);
+  let admin = users::Entity::find()
+    .filter(users::Column::Email.eq("admin@example.com"))
+    .one(&db)
+    .await
+    .unwrap()
+    .unwrap();
+  let admin_key = admin
+    .find_related(keys::Entity)
+    .one(&db)
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(admin_key.scopes.as_deref(), Some("ingest,history,admin"));
+  assert_ne!(admin_key.key, format!("jwt-{}", admin.id));
+  let user_id = admin.id;
+  let subscription = crate::active_subscription(&db, &user_id)
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(subscription.package.as_deref(), Some("Pro"));
+  assert_eq!(subscription.credits, 200);
+}
+
+#[tokio::test]
+async fn relations_follow_foreign_keys() {
+  let db = Database::connect("sqlite::memory:").await.unwrap();
+  crate::init_db(&db, None).await.unwrap();
+  crate::seed_db(&db).await.unwrap();
+
+  let admin = users::Entity::find()
+    .filter(users::Column::Email.eq("admin@example.com"))
+    .one(&db)
+    .await
+    .unwrap()
+    .unwrap();
+  let admin_keys = admin.find_related(keys::Entity).all(&db).await.unwrap();
+  assert_eq!(admin_keys.len(), 1);
+  let role = admin
+    .find_related(roles::Entity)
+    .one(&db)
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(role.elevation, Some(100));
+
+  let pro = packages::Entity::find()
+    .filter(packages::Column::Name.eq("Pro"))
+    .one(&db)
+    .await
+    .unwrap()
+    .unwrap();
+  let subscribers: Vec<_> = pro
+    .find_related(subscriptions::Entity)
+    .find_also_related(users::Entity)
+    .all(&db)
+    .await
+    .unwrap()
+    .into_iter()
+    .filter_map(|(_, user)| user.map(|user| user.email))
+    .collect();
+  assert_eq!(subscribers, ["admin@example.com"]);
+}
+
+#[tokio::test]
+async fn saving_an_entity_sets_its_edit_time() {
+  let db = Database::connect("sqlite::memory:").await.unwrap();
+  crate::init_db(&db, None).await.unwrap();
+  crate::seed_db(&db).await.unwrap();
+  db.execute_unprepared("UPDATE users SET e_date = NULL; UPDATE keys SET e_time = NULL;")
+    .await
+    .unwrap();
+
+  let user = users::Entity::find().one(&db).await.unwrap().unwrap();
+  let mut active = user.into_active_model();
+  active.first_name = Set(Some("Renamed".to_string()));
+  assert!(active.update(&db).await.unwrap().e_date.is_some());
+
+  let key = keys::Entity::find().one(&db).await.unwrap().unwrap();
+  let mut active = key.into_active_model();
+  active.name = Set(Some("renamed".to_string()));
+  assert!(active.update(&db).await.unwrap().e_time.is_some());
+}
+
+#[tokio::test]
+async fn migrations_roll_back_and_reapply() {
+  let db = Database::connect("sqlite::memory:").await.unwrap();
+  migration::Migrator::up(&db, None).await.unwrap();
+  migration::Migrator::refresh(&db).await.unwrap();
+  assert!(migration::Migrator::get_pending_migrations(&db)
+    .await
+    .unwrap()
+    .is_empty());
+}
+
+#[tokio::test]
+async fn plaintext_keys_are_hashed_by_migration() {
+  let db = Database::connect("sqlite::memory:").await.unwrap();
+  migration::Migrator::up(&db, Some(3)).await.unwrap();
+  db.execute_unprepared(
+    "INSERT INTO users (id, email, password) VALUES ('u1', 'legacy@example.com', 'x');
+     INSERT INTO users (id, email, password) VALUES ('u2', 'staff@example.com', 'x');
+     INSERT INTO roles (id, user_id, name, elevation) VALUES ('r2', 'u2', 'mod', 50);
+     INSERT INTO `keys` (id, user_id, name, `key`) VALUES ('k1', 'u1', 'default', 'jwt-u1');
+     INSERT INTO `keys` (id, user_id, name, `key`) VALUES ('k2', 'u2', 'default', 'jwt-u2');",
+  )
+  .await
+  .unwrap();
+  migration::Migrator::up(&db, None).await.unwrap();
+
+  let stored = keys::Entity::find_by_id("k1")
+    .one(&db)
+    .await
+    .unwrap()
+    .unwrap();
+  assert_ne!(stored.key, "jwt-u1");
+  assert_eq!(stored.prefix.as_deref(), Some("jwt-u1"));
+  let key = crate::keys::authenticate(&db, &bearer("jwt-u1"))
+    .await
+    .unwrap();
+  assert_eq!(key.user_id, "u1");
+  assert!(key.has_scope(crate::keys::Scope::Ingest));
+  assert!(!key.has_scope(crate::keys::Scope::Admin));
+  let staff = crate::keys::authenticate(&db, &bearer("jwt-u2"))
+    .await
+    .unwrap();
+  assert!(staff.has_scope(crate::keys::Scope::Admin));
+}
+
+fn bearer(token: &str) -> axum::http::HeaderMap {
+  let mut headers = axum::http::HeaderMap::new();
+  headers.insert(
+    axum::http::header::AUTHORIZATION,
+    format!("Bearer {token}").parse().unwrap(),
+  );
+  headers
+}
@@ -0,0 +1,153 @@
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+
+use super::{envelopes, TestServer};
+
+async fn latest_id(server: &TestServer) -> String {
+  let history: Value = server
+    .request(Method::GET, "/history", None)
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+  history[0]["id"].as_str().unwrap().to_string()
+}
+
+async fn follow_up(
+  server: &TestServer,
+  id: &str,
+  model: &str,
+  question: &str,
+) -> reqwest::Response {
+  server
+    .request(Method::POST, &format!("/history/{id}/follow_up"), None)
+    .header("x-model", model)
+    .json(&json!({ "question": question }))
+    .send()
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn follow_ups_stream_and_are_kept_in_order() {
+  let server = TestServer::start(5).await;
+  let response = server.ingest("default").await;
+  assert_eq!(response.status(), StatusCode::OK);
+  let id = response.headers()["x-capture-id"].to_str().unwrap().to_string();
+  assert_eq!(id, latest_id(&server).await);
+
+  let events = envelopes(follow_up(&server, &id, "default", "Explain step 3").await).await;
+  let last = events.last().expect("events");
+  assert_eq!(last["type"], "done");
+  assert!(last["data"].as_str().unwrap().contains("print(items[::-1])"));
+  let events = envelopes(follow_up(&server, &id, "default", "Convert to Rust").await).await;
+  assert_eq!(events.last().expect("events")["type"], "done");
+  assert_eq!(server.credits().await, 2);
+
+  let entry: Value = server
+    .request(Method::GET, &format!("/history/{id}"), None)
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+  let follow_ups = entry["follow_ups"].as_array().unwrap();
+  assert_eq!(follow_ups.len(), 2);
+  assert_eq!(follow_ups[0]["question"], "Explain step 3");
+  assert_eq!(follow_ups[1]["question"], "Convert to Rust");
+  assert_eq!(follow_ups[1]["status"], "DONE");
+  assert!(follow_ups[1]["answer"].as_str().unwrap().contains("print(items"));
+}
+
+#[tokio::test]
+async fn failed_follow_ups_are_refunded() {
+  let server = TestServer::start(5).await;
+  assert_eq!(server.ingest("default").await.status(), StatusCode::OK);
+  let id = latest_id(&server).await;
+
+  let events = envelopes(follow_up(&server, &id, "overloaded", "Why?").await).await;
+  assert_eq!(events.len(), 1);
+  assert_eq!(events[0]["type"], "error");
+  assert_eq!(server.credits().await, 4);
+
+  let entry: Value = server
+    .request(Method::GET, &format!("/history/{id}"), None)
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+  assert_eq!(entry["follow_ups"][0]["status"], "ERROR");
+  assert_eq!(entry["follow_ups"][0]["answer"], Value::Null);
+}
+
+#[tokio::test]
+async fn follow_up_hang_up_stops_the_call_and_refunds() {
+  let server = TestServer::start(5).await;
+  assert_eq!(server.ingest("default").await.status(), StatusCode::OK);
+  let id = latest_id(&server).await;
+
+  let mut response = follow_up(&server, &id, "slow", "Explain step 3").await;
+  assert!(response.chunk().await.unwrap().is_some());
+  drop(response);
+
+  let mut status = Value::Null;
+  for _ in 0..50 {
+    let entry: Value = server
+      .request(Method::GET, &format!("/history/{id}"), None)
+      .send()
+      .await
+      .unwrap()
+      .json()
+      .await
+      .unwrap();
+    status = entry["follow_ups"][0]["status"].clone();
+    if status != "RUNNING" {
+      break;
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+  }
+  assert_eq!(status, "ERROR");
+  assert_eq!(server.credits().await, 4);
+}
+
+#[tokio::test]
+async fn follow_ups_need_a_finished_answer_and_a_question() {
+  let server = TestServer::start(5).await;
+  assert_eq!(server.ingest("prose").await.status(), StatusCode::BAD_GATEWAY);
+  let failed = latest_id(&server).await;
+  let response = follow_up(&server, &failed, "default", "Try again").await;
+  assert_eq!(response.status(), StatusCode::CONFLICT);
+
+  assert_eq!(server.ingest("default").await.status(), StatusCode::OK);
+  let id = latest_id(&server).await;
+  let response = follow_up(&server, &id, "default", "   ").await;
+  assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+  let response = follow_up(&server, "missing", "default", "Why?").await;
+  assert_eq!(response.status(), StatusCode::NOT_FOUND);
+  assert_eq!(server.credits().await, 4);
+}
+
+#[tokio::test]
+async fn follow_up_positions_are_unique_per_answer() {
+  use sea_orm::{ActiveModelTrait, Set, SqlErr};
+
+  let server = TestServer::start(5).await;
+  assert_eq!(server.ingest("default").await.status(), StatusCode::OK);
+  let id = latest_id(&server).await;
+  let turn = |question: &str| crate::entity::screen_result_turns::ActiveModel {
+    screen_result_id: Set(id.clone()),
+    position: Set(1),
+    question: Set(question.to_string()),
+    status: Set("RUNNING".to_string()),
+    ..Default::default()
+  };
+  turn("First").insert(&server.db).await.unwrap();
+  // What a second follow-up racing the first would do.
+  let err = turn("Second").insert(&server.db).await.unwrap_err();
+  assert!(matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))));
+}
@@ -0,0 +1,84 @@
+use base64::Engine as _;
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+
+use super::{TestServer, PNG};
+use crate::keys::Scope;
+
+async fn get(server: &TestServer, path: &str, api_key: Option<&str>) -> reqwest::Response {
+  server
+    .request(Method::GET, path, api_key)
+    .send()
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn lists_and_filters_own_captures() {
+  let server = TestServer::start(5).await;
+  assert_eq!(server.ingest("default").await.status(), StatusCode::OK);
+  assert_eq!(
+    server.ingest("prose").await.status(),
+    StatusCode::BAD_GATEWAY
+  );
+
+  let all: Value = get(&server, "/history", None).await.json().await.unwrap();
+  assert_eq!(all.as_array().unwrap().len(), 2);
+  let done: Value = get(&server, "/history?status=done", None)
+    .await
+    .json()
+    .await
+    .unwrap();
+  assert_eq!(done.as_array().unwrap().len(), 1);
+  assert_eq!(done[0]["status"], "DONE");
+  assert_eq!(done[0]["model"], "default");
+  assert!(done[0]["preview"].as_str().unwrap().contains("print(items"));
+
+  let errors: Value = get(&server, "/history?status=ERROR&limit=1", None)
+    .await
+    .json()
+    .await
+    .unwrap();
+  assert_eq!(errors.as_array().unwrap().len(), 1);
+  assert!(!errors[0]["preview"].as_str().unwrap().is_empty());
+
+  let later: Value = get(&server, "/history?from=2999-01-01T00:00:00Z", None)
+    .await
+    .json()
+    .await
+    .unwrap();
+  assert_eq!(later, Value::Array(Vec::new()));
+}
+
+#[tokio::test]
+async fn returns_answer_and_image_without_charging() {
+  let server = TestServer::start(5).await;
+  server.ingest("default").await;
+  let listed: Value = get(&server, "/history", None).await.json().await.unwrap();
+  let path = format!("/history/{}", listed[0]["id"].as_str().unwrap());
+
+  let response = get(&server, &path, None).await;
+  assert_eq!(response.status(), StatusCode::OK);
+  let entry: Value = response.json().await.unwrap();
+  assert_eq!(entry["code"], "print(items[::-1])");
+  assert!(entry["text"].as_str().unwrap().contains("```py"));
+  assert_eq!(entry["image"]["mime"], "image/png");
+  let image = base64::engine::general_purpose::STANDARD
+    .decode(entry["image"]["data"].as_str().unwrap())
+    .unwrap();
+  assert_eq!(image, PNG);
+  assert_eq!(server.credits().await, 4);
+
+  let (_, ingest_only) =
+    crate::keys::issue_key(&server.db, super::USER_ID, "ingest", &[Scope::Ingest], None, None)
+      .await
+      .unwrap();
+  assert_eq!(
+    get(&server, &path, Some(&ingest_only)).await.status(),
+    StatusCode::FORBIDDEN
+  );
+  assert_eq!(
+    get(&server, "/history/missing", None).await.status(),
+    StatusCode::NOT_FOUND
+  );
+}
@@ -0,0 +1,99 @@
+use reqwest::StatusCode;
+use serde_json::Value;
+
+use super::TestServer;
+
+#[tokio::test]
+async fn returns_tool_call_solution_and_charges() {
+  let server = TestServer::start(5).await;
+  let response = server.ingest("default").await;
+  assert_eq!(response.status(), StatusCode::OK);
+  let body: Value = response.json().await.unwrap();
+  assert_eq!(body["code"], "print(items[::-1])");
+  assert!(body["text"]
+    .as_str()
+    .unwrap()
+    .contains("```py\nprint(items[::-1])"));
+
+  assert_eq!(server.credits().await, 4);
+  assert_eq!(server.statuses().await, ["DONE"]);
+  assert_eq!(std::fs::read_dir(&server.image_dir).unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn falls_back_to_output_text_when_tool_call_is_missing() {
+  let server = TestServer::start(5).await;
+  let response = server.ingest("no_tool_call").await;
+  assert_eq!(response.status(), StatusCode::OK);
+  let body: Value = response.json().await.unwrap();
+  assert_eq!(body["code"], "count = len(items)");
+  assert_eq!(server.credits().await, 4);
+}
+
+#[tokio::test]
+async fn rejects_output_text_that_is_not_json() {
+  let server = TestServer::start(5).await;
+  let response = server.ingest("prose").await;
+  assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+  assert_eq!(server.credits().await, 5);
+  assert_eq!(server.statuses().await, ["ERROR"]);
+}
+
+#[tokio::test]
+async fn malformed_upstream_json_is_an_internal_error() {
+  let server = TestServer::start(5).await;
+  let response = server.ingest("malformed").await;
+  assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+  let body: Value = response.json().await.unwrap();
+  assert!(body["error"]["message"]
+    .as_str()
+    .unwrap()
+    .starts_with("Invalid OpenAI JSON response"));
+  assert_eq!(server.credits().await, 5);
+  assert_eq!(server.statuses().await, ["ERROR"]);
+}
+
+#[tokio::test]
+async fn upstream_500_is_a_bad_gateway_and_not_charged() {
+  let server = TestServer::start(5).await;
+  let response = server.ingest("overloaded").await;
+  assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+  let body: Value = response.json().await.unwrap();
+  assert!(body["error"]["message"].as_str().unwrap().contains("500"));
+  assert_eq!(server.credits().await, 5);
+  assert_eq!(server.statuses().await, ["ERROR"]);
+}
+
+#[tokio::test]
+async fn missing_api_key_is_unauthorized() {
+  let server = TestServer::start(5).await;
+  let response = server.post("/ingest", "default", None).await;
+  assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+  let body: Value = response.json().await.unwrap();
+  assert_eq!(body["error"]["code"], 100);
+
+  let response = server.post("/ingest", "default", Some("wrong")).await;
+  assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+  assert!(server.statuses().await.is_empty());
+}
+
+#[tokio::test]
+async fn unknown_model_is_rejected() {
+  let server = TestServer::start(5).await;
+  let response = server
+    .post("/ingest", "gpt-nope", Some(&server.api_key))
+    .await;
+  assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+  let body: Value = response.json().await.unwrap();
+  assert_eq!(body["error"]["code"], 102);
+}
+
+#[tokio::test]
+async fn no_credits_is_forbidden() {
+  let server = TestServer::start(0).await;
+  let response = server.ingest("default").await;
+  assert_eq!(response.status(), StatusCode::FORBIDDEN);
+  let body: Value = response.json().await.unwrap();
+  assert_eq!(body["error"]["code"], 101);
+  assert!(server.statuses().await.is_empty());
+}
@@ -0,0 +1,84 @@
+use super::TestServer;
+
+fn deltas(events: &[serde_json::Value]) -> String {
+  events
+    .iter()
+    .filter(|event| event["type"] == "delta")
+    .filter_map(|event| event["data"].as_str())
+    .collect()
+}
+
+#[tokio::test]
+async fn streams_deltas_then_done() {
+  let server = TestServer::start(5).await;
+  let events = server.ingest_stream("default").await;
+  let last = events.last().expect("events");
+  assert_eq!(last["type"], "done");
+  assert_eq!(
+    deltas(&events),
+    "Reverse the list with slicing.\n\n```py\nprint(items[::-1])\n```"
+  );
+  assert!(last["data"]
+    .as_str()
+    .unwrap()
+    .contains("print(items[::-1])"));
+
+  assert_eq!(server.credits().await, 4);
+  assert_eq!(server.statuses().await, ["DONE"]);
+}
+
+#[tokio::test]
+async fn upstream_500_sends_an_error_event() {
+  let server = TestServer::start(5).await;
+  let events = server.ingest_stream("overloaded").await;
+  assert_eq!(events.len(), 1);
+  assert_eq!(events[0]["type"], "error");
+  assert_eq!(events[0]["error"]["code"], 502);
+  assert_eq!(server.credits().await, 5);
+  assert_eq!(server.statuses().await, ["ERROR"]);
+}
+
+#[tokio::test]
+async fn mid_stream_disconnect_sends_an_error_event() {
+  let server = TestServer::start(5).await;
+  let events = server.ingest_stream("disconnect").await;
+  assert_eq!(deltas(&events), "Reverse the list with slicing.\n\n");
+  let last = events.last().expect("events");
+  assert_eq!(last["type"], "error");
+  assert!(events.iter().all(|event| event["type"] != "done"));
+  assert_eq!(server.credits().await, 5);
+  assert_eq!(server.statuses().await, ["ERROR"]);
+}
+
+#[tokio::test]
+async fn stream_without_its_final_event_is_refunded() {
+  let server = TestServer::start(5).await;
+  let events = server.ingest_stream("truncated").await;
+  assert!(events.iter().any(|event| event["type"] == "delta"));
+  let last = events.last().expect("events");
+  assert_eq!(last["type"], "error");
+  assert!(events.iter().all(|event| event["type"] != "done"));
+  assert_eq!(server.credits().await, 5);
+  assert_eq!(server.statuses().await, ["ERROR"]);
+}
+
+#[tokio::test]
+async fn client_hang_up_stops_the_call_and_refunds() {
+  let server = TestServer::start(5).await;
+  let mut response = server
+    .post("/ingest_stream", "slow", Some(&server.api_key))
+    .await;
+  assert!(response.chunk().await.unwrap().is_some());
+  drop(response);
+
+  let mut statuses = Vec::new();
+  for _ in 0..50 {
+    statuses = server.statuses().await;
+    if statuses != ["RUNNING"] {
+      break;
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+  }
+  assert_eq!(statuses, ["ERROR"]);
+  assert_eq!(server.credits().await, 5);
+}
@@ -0,0 +1,223 @@
+use reqwest::{Method, StatusCode};
+use sea_orm::{ActiveModelTrait, EntityTrait, Set};
+use serde_json::{json, Value};
+
+use super::TestServer;
+use crate::entity::keys;
+
+#[tokio::test]
+async fn create_list_and_revoke() {
+  let server = TestServer::start(5).await;
+  let response = server
+    .request(Method::POST, "/keys", None)
+    .json(&json!({ "name": "laptop", "scopes": ["ingest"], "expires_in_days": 30 }))
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::CREATED);
+  let created: Value = response.json().await.unwrap();
+  let token = created["key"].as_str().unwrap().to_string();
+  assert!(token.starts_with(created["prefix"].as_str().unwrap()));
+  assert_eq!(created["scopes"], json!(["ingest"]));
+  assert!(created["expires_at"].is_string());
+
+  let stored = keys::Entity::find_by_id(created["id"].as_str().unwrap())
+    .one(&server.db)
+    .await
+    .unwrap()
+    .unwrap();
+  assert!(!stored
+    .key
+    .contains(&token[created["prefix"].as_str().unwrap().len()..]));
+
+  let listed: Value = server
+    .request(Method::GET, "/keys", None)
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+  let listed = listed.as_array().unwrap();
+  assert_eq!(listed.len(), 2);
+  assert!(listed.iter().all(|key| key.get("key").is_none()));
+
+  assert_eq!(
+    server
+      .post("/ingest", "default", Some(&token))
+      .await
+      .status(),
+    StatusCode::OK
+  );
+  let stored = keys::Entity::find_by_id(created["id"].as_str().unwrap())
+    .one(&server.db)
+    .await
+    .unwrap()
+    .unwrap();
+  assert!(stored.last_used_at.is_some());
+
+  let path = format!("/keys/{}", created["id"].as_str().unwrap());
+  let response = server
+    .request(Method::DELETE, &path, None)
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NO_CONTENT);
+  let response = server.post("/ingest", "default", Some(&token)).await;
+  assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+  let body: Value = response.json().await.unwrap();
+  assert_eq!(body["error"]["message"], "API key has been revoked");
+}
+
+#[tokio::test]
+async fn scopes_limit_access_and_grants() {
+  let server = TestServer::start(5).await;
+  let created: Value = server
+    .request(Method::POST, "/keys", None)
+    .json(&json!({ "scopes": ["history"] }))
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+  let token = created["key"].as_str().unwrap();
+
+  let response = server.post("/ingest", "default", Some(token)).await;
+  assert_eq!(response.status(), StatusCode::FORBIDDEN);
+  let body: Value = response.json().await.unwrap();
+  assert_eq!(body["error"]["code"], 105);
+
+  let response = server
+    .request(Method::POST, "/keys", Some(token))
+    .json(&json!({ "scopes": ["ingest"] }))
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+  let response = server
+    .request(Method::POST, "/keys", None)
+    .json(&json!({ "scopes": ["root"] }))
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn key_management_stays_within_the_callers_scopes() {
+  let server = TestServer::start(5).await;
+  let (ingest, ingest_token) = crate::keys::issue_key(
+    &server.db,
+    super::USER_ID,
+    "device",
+    &[crate::keys::Scope::Ingest],
+    None,
+    None,
+  )
+  .await
+  .unwrap();
+  let (admin, _) = crate::keys::issue_key(
+    &server.db,
+    super::USER_ID,
+    "admin",
+    &[crate::keys::Scope::History, crate::keys::Scope::Admin],
+    None,
+    None,
+  )
+  .await
+  .unwrap();
+
+  let response = server
+    .request(Method::GET, "/keys", Some(&ingest_token))
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::FORBIDDEN);
+  let path = format!("/keys/{}", admin.id);
+  let response = server
+    .request(Method::DELETE, &path, Some(&ingest_token))
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+  // The ingest and history key can revoke the device key, but not the admin one.
+  let response = server
+    .request(Method::DELETE, &path, None)
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::FORBIDDEN);
+  let body: Value = response.json().await.unwrap();
+  assert_eq!(body["error"]["code"], 105);
+  let response = server
+    .request(Method::DELETE, &format!("/keys/{}", ingest.id), None)
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn expired_keys_are_rejected() {
+  let server = TestServer::start(5).await;
+  let (model, token) = crate::keys::issue_key(
+    &server.db,
+    super::USER_ID,
+    "old",
+    &[crate::keys::Scope::Ingest],
+    None,
+    None,
+  )
+  .await
+  .unwrap();
+  let mut active: keys::ActiveModel = model.into();
+  active.expires_at = Set(Some(chrono::Utc::now() - chrono::Duration::minutes(1)));
+  active.update(&server.db).await.unwrap();
+
+  let response = server.post("/ingest", "default", Some(&token)).await;
+  assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+  let body: Value = response.json().await.unwrap();
+  assert_eq!(body["error"]["message"], "API key has expired");
+}
+
+#[tokio::test]
+async fn other_users_keys_cannot_be_revoked() {
+  let server = TestServer::start(5).await;
+  crate::entity::users::ActiveModel {
+    id: Set("other-user".to_string()),
+    email: Set("other@example.com".to_string()),
+    password: Set("x".to_string()),
+    confirmd: Set(true),
+    ..Default::default()
+  }
+  .insert(&server.db)
+  .await
+  .unwrap();
+  let (other, _) = crate::keys::issue_key(
+    &server.db,
+    "other-user",
+    "default",
+    &[crate::keys::Scope::Ingest],
+    None,
+    None,
+  )
+  .await
+  .unwrap();
+
+  let path = format!("/keys/{}", other.id);
+  let response = server
+    .request(Method::DELETE, &path, None)
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NOT_FOUND);
+  let stored = keys::Entity::find_by_id(other.id)
+    .one(&server.db)
+    .await
+    .unwrap()
+    .unwrap();
+  assert!(stored.revoked_at.is_none());
+}
@@ -0,0 +1,80 @@
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+
+use super::TestServer;
+
+async fn link(server: &TestServer, body: Value) -> (StatusCode, Value) {
+  let response = server
+    .client
+    .post(format!("{}/link", server.url))
+    .json(&body)
+    .send()
+    .await
+    .unwrap();
+  let status = response.status();
+  (status, response.json().await.unwrap())
+}
+
+async fn link_code(server: &TestServer) -> (String, String) {
+  let code: Value = server
+    .request(Method::GET, "/link", None)
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+  (
+    code["code"].as_str().unwrap().to_string(),
+    code["pin"].as_str().unwrap().to_string(),
+  )
+}
+
+#[tokio::test]
+async fn code_and_pin_mint_a_device_key() {
+  let server = TestServer::start(5).await;
+  let (code, pin) = link_code(&server).await;
+  assert_eq!(pin.len(), 4);
+  assert_eq!(link_code(&server).await, (code.clone(), pin.clone()));
+
+  let (status, linked) = link(
+    &server,
+    json!({ "code": code.to_lowercase(), "pin": pin, "name": "laptop" }),
+  )
+  .await;
+  assert_eq!(status, StatusCode::CREATED);
+  assert_eq!(linked["email"], "user@example.com");
+  assert_eq!(linked["name"], "laptop");
+  assert_eq!(linked["scopes"], json!(["ingest", "history"]));
+
+  let token = linked["key"].as_str().unwrap();
+  assert_eq!(
+    server
+      .post("/ingest", "default", Some(token))
+      .await
+      .status(),
+    StatusCode::OK
+  );
+}
+
+#[tokio::test]
+async fn wrong_pins_lock_the_code() {
+  let server = TestServer::start(5).await;
+  let (code, pin) = link_code(&server).await;
+  let wrong = if pin == "0000" { "1111" } else { "0000" };
+
+  // Codes that do not exist are not tracked, so they never lock either.
+  for _ in 0..6 {
+    let (status, body) = link(&server, json!({ "code": "NOPE-1", "pin": pin })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"]["code"], 109);
+  }
+
+  for _ in 0..5 {
+    let (status, _) = link(&server, json!({ "code": code, "pin": wrong })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+  }
+  let (status, body) = link(&server, json!({ "code": code, "pin": pin })).await;
+  assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+  assert_eq!(body["error"]["code"], 110);
+}
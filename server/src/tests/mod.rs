@@ -0,0 +1,281 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use sea_orm::{ConnectionTrait, Database, DatabaseConnection};
+use sea_orm_migration::MigratorTrait;
+
+use crate::image_store::LocalImageStore;
+use crate::mail::FileMailer;
+use crate::mock_upstream::{self, Fixtures};
+use crate::models::{ModelEntry, ModelRegistry, ModelSpec};
+use crate::payments::FakePaymentProvider;
+use crate::upload::{OutputFormat, UploadConfig};
+use crate::AppState;
+
+mod account;
+mod admin;
+mod auth;
+mod credits;
+mod db;
+mod follow_up;
+mod history;
+mod images;
+mod ingest;
+mod ingest_stream;
+mod keys;
+mod link;
+mod payments;
+mod prompt;
+mod rate_limit;
+mod settings;
+mod upload;
+mod usage;
+
+const USER_ID: &str = "0f2d5b6a-7f8a-4b2e-9b43-0a5a2a9f9b01";
+const PAYMENT_SECRET: &str = "whsec_test";
+const MAX_UPLOAD_BYTES: usize = 256 * 1024;
+const MAX_IMAGE_SIDE: u32 = 64;
+/// A 2x2 RGB PNG.
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR\x00\x00\x00\x02\x00\x00\x00\x02\x08\x02\x00\x00\x00\xfd\xd4\x9a\x73\x00\x00\x00\x11IDAT\x78\xda\x63\xf8\xcf\xc0\xc0\xf0\x1f\x8c\x80\x18\x00\x1d\xf0\x03\xfd\xae\x3f\xe2\x38\x00\x00\x00\x00IEND\xaeB`\x82";
+
+/// Models are named after the fixture they replay from `fixtures/upstream`; `priced` has
+/// no fixture of its own, replays `default` and is charged per token. `slow` pauses twice
+/// mid-stream and `truncated` ends cleanly without `response.completed`.
+const MODELS: &[&str] = &[
+  "default",
+  "no_tool_call",
+  "prose",
+  "malformed",
+  "overloaded",
+  "disconnect",
+  "priced",
+  "slow",
+  "truncated",
+];
+
+/// The server wired to an in-memory SQLite database and a mock upstream on random ports.
+struct TestServer {
+  url: String,
+  api_key: String,
+  db: DatabaseConnection,
+  image_dir: PathBuf,
+  client: reqwest::Client,
+}
+
+impl TestServer {
+  async fn start(credits: i64) -> Self {
+    let fixtures_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/upstream");
+    let fixtures = Fixtures::load(&fixtures_dir).expect("load fixtures");
+    let upstream = spawn(mock_upstream::router(fixtures)).await;
+
+    let client = reqwest::Client::new();
+    let models = MODELS
+      .iter()
+      .map(|name| {
+        let spec: ModelSpec = serde_json::from_value(serde_json::json!({
+          "name": name,
+          "provider": "openai",
+          "base_url": format!("{upstream}/v1"),
+          "api_key_env": "FAUX_TEST_API_KEY",
+          "price": (*name == "priced").then(|| serde_json::json!({ "input": 1000, "output": 10000 }))
+        }))
+        .expect("model spec");
+        ModelEntry::from_spec(spec, &client).expect("model entry")
+      })
+      .collect();
+
+    let db = Database::connect("sqlite::memory:")
+      .await
+      .expect("connect sqlite");
+    migration::Migrator::up(&db, None).await.expect("migrate");
+    db.execute_unprepared(&format!(
+      "INSERT INTO users (id, email, password, confirmd) VALUES ('{USER_ID}', 'user@example.com', 'x', 1);
+       INSERT INTO packages (id, name) VALUES (1, 'Free');
+       INSERT INTO subscriptions (user_id, package_id, credits) VALUES ('{USER_ID}', 1, {credits});"
+    ))
+    .await
+    .expect("seed");
+    let (_, api_key) = crate::keys::issue_key(
+      &db,
+      USER_ID,
+      "test",
+      &[crate::keys::Scope::Ingest, crate::keys::Scope::History],
+      None,
+      None,
+    )
+    .await
+    .expect("issue key");
+
+    let image_dir = std::env::temp_dir().join(format!("faux-test-{}", uuid::Uuid::new_v4()));
+    let state = AppState {
+      models: Arc::new(ModelRegistry::new("default".to_string(), models)),
+      system_prompt: "system".to_string(),
+      user_prompt: "user".to_string(),
+      stream_prompt: "stream".to_string(),
+      images: Arc::new(LocalImageStore::new(image_dir.clone())),
+      db: db.clone(),
+      mailer: Arc::new(FileMailer::new(
+        "test@localhost".to_string(),
+        image_dir.join("mail"),
+      )),
+      public_url: "http://faux.test".to_string(),
+      link_attempts: Arc::default(),
+      rate_limiter: Arc::default(),
+      payments: Some(Arc::new(FakePaymentProvider::new(PAYMENT_SECRET))),
+      upload: UploadConfig {
+        max_bytes: MAX_UPLOAD_BYTES,
+        max_side: MAX_IMAGE_SIDE,
+        format: OutputFormat::Png,
+      },
+    };
+    let url = spawn(crate::app(state)).await;
+
+    Self {
+      url,
+      api_key,
+      db,
+      image_dir,
+      client,
+    }
+  }
+
+  async fn post(&self, path: &str, model: &str, api_key: Option<&str>) -> reqwest::Response {
+    self.post_file(path, model, api_key, PNG.to_vec(), "image/png").await
+  }
+
+  async fn post_file(
+    &self,
+    path: &str,
+    model: &str,
+    api_key: Option<&str>,
+    bytes: Vec<u8>,
+    mime: &str,
+  ) -> reqwest::Response {
+    self
+      .post_files(path, model, api_key, vec![(bytes, mime)])
+      .await
+  }
+
+  /// Sends each of `files` as its own `file` part, in order.
+  async fn post_files(
+    &self,
+    path: &str,
+    model: &str,
+    api_key: Option<&str>,
+    files: Vec<(Vec<u8>, &str)>,
+  ) -> reqwest::Response {
+    let mut form = reqwest::multipart::Form::new();
+    for (bytes, mime) in files {
+      let part = reqwest::multipart::Part::bytes(bytes)
+        .file_name("screenshot.png")
+        .mime_str(mime)
+        .expect("mime");
+      form = form.part("file", part);
+    }
+    let mut request = self
+      .client
+      .post(format!("{}{path}", self.url))
+      .header("x-model", model)
+      .multipart(form);
+    if let Some(api_key) = api_key {
+      request = request.bearer_auth(api_key);
+    }
+    request.send().await.expect("send request")
+  }
+
+  /// A request to `path` authorized with `api_key` (the seeded key when `None`).
+  fn request(
+    &self,
+    method: reqwest::Method,
+    path: &str,
+    api_key: Option<&str>,
+  ) -> reqwest::RequestBuilder {
+    self
+      .client
+      .request(method, format!("{}{path}", self.url))
+      .bearer_auth(api_key.unwrap_or(&self.api_key))
+  }
+
+  async fn ingest(&self, model: &str) -> reqwest::Response {
+    self.post("/ingest", model, Some(&self.api_key)).await
+  }
+
+  /// Reads the whole SSE body and returns the decoded `StreamEnvelope`s.
+  async fn ingest_stream(&self, model: &str) -> Vec<serde_json::Value> {
+    let response = self
+      .post("/ingest_stream", model, Some(&self.api_key))
+      .await;
+    envelopes(response).await
+  }
+
+  async fn credits(&self) -> i64 {
+    let row = self
+      .db
+      .query_one(sea_orm::Statement::from_string(
+        self.db.get_database_backend(),
+        "SELECT credits FROM subscriptions LIMIT 1",
+      ))
+      .await
+      .expect("query credits")
+      .expect("subscription row");
+    row.try_get("", "credits").expect("credits")
+  }
+
+  /// Bodies of the mails sent so far, oldest first.
+  fn mails(&self) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(self.image_dir.join("mail")) else {
+      return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+      .map(|entry| entry.expect("mail entry").path())
+      .collect();
+    paths.sort();
+    paths
+      .iter()
+      .map(|path| std::fs::read_to_string(path).expect("read mail"))
+      .collect()
+  }
+
+  async fn statuses(&self) -> Vec<String> {
+    let rows = self
+      .db
+      .query_all(sea_orm::Statement::from_string(
+        self.db.get_database_backend(),
+        "SELECT status FROM screen_results",
+      ))
+      .await
+      .expect("query screen_results");
+    rows
+      .iter()
+      .map(|row| row.try_get("", "status").expect("status"))
+      .collect()
+  }
+}
+
+impl Drop for TestServer {
+  fn drop(&mut self) {
+    let _ = std::fs::remove_dir_all(&self.image_dir);
+  }
+}
+
+/// Reads the whole SSE body of a successful response and returns the decoded `StreamEnvelope`s.
+async fn envelopes(response: reqwest::Response) -> Vec<serde_json::Value> {
+  assert_eq!(response.status(), reqwest::StatusCode::OK);
+  let body = response.text().await.expect("stream body");
+  body
+    .split("\n\n")
+    .filter_map(|event| event.trim().strip_prefix("data:"))
+    .map(|data| serde_json::from_str(data.trim()).expect("envelope json"))
+    .collect()
+}
+
+async fn spawn(router: axum::Router) -> String {
+  let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+    .await
+    .expect("bind");
+  let addr = listener.local_addr().expect("local addr");
+  tokio::spawn(async move {
+    let _ = axum::serve(listener, router).await;
+  });
+  format!("http://{addr}")
+}
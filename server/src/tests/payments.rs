@@ -0,0 +1,135 @@
+use chrono::{DateTime, Duration, Utc};
+use reqwest::StatusCode;
+use sea_orm::ConnectionTrait;
+use serde_json::{json, Value};
+
+use super::{TestServer, PAYMENT_SECRET, USER_ID};
+use crate::payments::FakePaymentProvider;
+
+async fn add_pro_package(server: &TestServer) {
+  server
+    .db
+    .execute_unprepared(
+      "INSERT INTO packages (id, name, rate_limit, credits, duration_days) VALUES (2, 'Pro', 0, 100, 30)",
+    )
+    .await
+    .unwrap();
+}
+
+async fn webhook(server: &TestServer, event: Value) -> (StatusCode, Value) {
+  let body = event.to_string();
+  let signature = FakePaymentProvider::new(PAYMENT_SECRET).sign(body.as_bytes());
+  let response = server
+    .client
+    .post(format!("{}/payments/webhook", server.url))
+    .header("x-fake-signature", signature)
+    .header("content-type", "application/json")
+    .body(body)
+    .send()
+    .await
+    .unwrap();
+  let status = response.status();
+  (status, response.json().await.unwrap())
+}
+
+fn paid(kind: &str, transaction_id: &str) -> Value {
+  json!({
+    "type": kind,
+    "transaction_id": transaction_id,
+    "user_id": USER_ID,
+    "package": "Pro",
+    "amount": "9.99",
+    "currency": "EUR"
+  })
+}
+
+fn expires_at(body: &Value) -> DateTime<Utc> {
+  serde_json::from_value(body["expires_at"].clone()).unwrap()
+}
+
+#[tokio::test]
+async fn purchase_is_idempotent_and_renewal_extends() {
+  let server = TestServer::start(5).await;
+  add_pro_package(&server).await;
+
+  let (status, first) = webhook(&server, paid("purchase", "tx_1")).await;
+  assert_eq!(status, StatusCode::OK);
+  assert_eq!(first["status"], "PAID");
+  assert_eq!(first["credits"], 100);
+  let expires = expires_at(&first);
+  assert!(
+    (expires - (Utc::now() + Duration::days(30)))
+      .num_seconds()
+      .abs()
+      < 60
+  );
+
+  let (status, replay) = webhook(&server, paid("purchase", "tx_1")).await;
+  assert_eq!(status, StatusCode::OK);
+  assert_eq!(replay, first);
+
+  let (status, renewed) = webhook(&server, paid("renewal", "tx_2")).await;
+  assert_eq!(status, StatusCode::OK);
+  assert_eq!(renewed["subscription_id"], first["subscription_id"]);
+  assert_eq!(renewed["credits"], 200);
+  assert_eq!(expires_at(&renewed) - expires, Duration::days(30));
+
+  let payments = server
+    .db
+    .query_one(sea_orm::Statement::from_string(
+      server.db.get_database_backend(),
+      "SELECT COUNT(*) AS count FROM payments",
+    ))
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(payments.try_get::<i64>("", "count").unwrap(), 2);
+}
+
+#[tokio::test]
+async fn refund_revokes_once() {
+  let server = TestServer::start(5).await;
+  add_pro_package(&server).await;
+  let (_, purchased) = webhook(&server, paid("purchase", "tx_1")).await;
+
+  let refund = json!({ "type": "refund", "transaction_id": "tx_1" });
+  let (status, refunded) = webhook(&server, refund.clone()).await;
+  assert_eq!(status, StatusCode::OK);
+  assert_eq!(refunded["status"], "REFUNDED");
+  assert_eq!(refunded["credits"], 0);
+  assert!(expires_at(&refunded) < expires_at(&purchased));
+
+  let (status, again) = webhook(&server, refund).await;
+  assert_eq!(status, StatusCode::OK);
+  assert_eq!(again, refunded);
+
+  let (status, _) = webhook(
+    &server,
+    json!({ "type": "refund", "transaction_id": "tx_unknown" }),
+  )
+  .await;
+  assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn unsigned_webhooks_are_rejected() {
+  let server = TestServer::start(5).await;
+  add_pro_package(&server).await;
+  let response = server
+    .client
+    .post(format!("{}/payments/webhook", server.url))
+    .header("x-fake-signature", "00ff")
+    .body(paid("purchase", "tx_1").to_string())
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+  let (status, body) = webhook(
+    &server,
+    json!({ "type": "purchase", "transaction_id": "tx_1", "user_id": USER_ID, "package": "Gold" }),
+  )
+  .await;
+  assert_eq!(status, StatusCode::BAD_REQUEST);
+  assert!(body["error"]["message"].as_str().unwrap().contains("Gold"));
+}
@@ -0,0 +1,63 @@
+use reqwest::{Method, StatusCode};
+use sea_orm::ConnectionTrait;
+use serde_json::{json, Value};
+
+use super::TestServer;
+
+async fn set_package_limit(server: &TestServer, limit: i32) {
+  server
+    .db
+    .execute_unprepared(&format!("UPDATE packages SET rate_limit = {limit}"))
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn package_limit_returns_retry_after() {
+  let server = TestServer::start(10).await;
+  set_package_limit(&server, 2).await;
+
+  for _ in 0..2 {
+    assert_eq!(server.ingest("default").await.status(), StatusCode::OK);
+  }
+  let response = server.ingest("default").await;
+  assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+  let retry_after: u64 = response.headers()["retry-after"]
+    .to_str()
+    .unwrap()
+    .parse()
+    .unwrap();
+  assert!((1..=30).contains(&retry_after));
+  let body: Value = response.json().await.unwrap();
+  assert_eq!(body["error"]["code"], 112);
+  assert_eq!(body["error"]["retry_after"], retry_after);
+  assert_eq!(server.credits().await, 8);
+}
+
+#[tokio::test]
+async fn key_limit_applies_below_the_package() {
+  let server = TestServer::start(10).await;
+  set_package_limit(&server, 5).await;
+
+  let created: Value = server
+    .request(Method::POST, "/keys", None)
+    .json(&json!({ "name": "slow", "rate_limit": 1 }))
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+  assert_eq!(created["rate_limit"], 1);
+  let slow = created["key"].as_str().unwrap();
+
+  assert_eq!(
+    server.post("/ingest", "default", Some(slow)).await.status(),
+    StatusCode::OK
+  );
+  assert_eq!(
+    server.post("/ingest", "default", Some(slow)).await.status(),
+    StatusCode::TOO_MANY_REQUESTS
+  );
+  assert_eq!(server.ingest("default").await.status(), StatusCode::OK);
+}
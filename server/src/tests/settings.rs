@@ -0,0 +1,126 @@
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+
+use super::TestServer;
+
+async fn put(server: &TestServer, body: Value) -> (StatusCode, Value) {
+  let response = server
+    .request(Method::PUT, "/settings", None)
+    .json(&body)
+    .send()
+    .await
+    .unwrap();
+  let status = response.status();
+  (status, response.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn settings_round_trip_without_local_fields() {
+  let server = TestServer::start(5).await;
+  let empty: Value = server
+    .request(Method::GET, "/settings", None)
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+  assert_eq!(empty, json!({ "config": {}, "updated_at": null }));
+
+  let config = json!({
+    "theme": "light",
+    "model": "prose",
+    "api_key": "fx_secret",
+    "main_position": { "x": 1.0, "y": 2.0 }
+  });
+  let (status, saved) = put(
+    &server,
+    json!({ "config": config, "updated_at": "2026-01-01T00:00:00Z" }),
+  )
+  .await;
+  assert_eq!(status, StatusCode::OK);
+  assert_eq!(
+    saved["config"],
+    json!({ "theme": "light", "model": "prose" })
+  );
+
+  let fetched: Value = server
+    .request(Method::GET, "/settings", None)
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+  assert_eq!(fetched, saved);
+  let updated_at: chrono::DateTime<chrono::Utc> =
+    serde_json::from_value(fetched["updated_at"].clone()).unwrap();
+  assert_eq!(updated_at.to_rfc3339(), "2026-01-01T00:00:00+00:00");
+}
+
+#[tokio::test]
+async fn older_writes_conflict() {
+  let server = TestServer::start(5).await;
+  let (status, _) = put(
+    &server,
+    json!({ "config": { "theme": "light" }, "updated_at": "2026-02-01T00:00:00Z" }),
+  )
+  .await;
+  assert_eq!(status, StatusCode::OK);
+
+  let (status, body) = put(
+    &server,
+    json!({ "config": { "theme": "dark" }, "updated_at": "2026-01-01T00:00:00Z" }),
+  )
+  .await;
+  assert_eq!(status, StatusCode::CONFLICT);
+  assert_eq!(body["error"]["code"], 111);
+
+  let (status, saved) = put(
+    &server,
+    json!({ "config": { "theme": "dark" }, "updated_at": "2026-03-01T00:00:00Z" }),
+  )
+  .await;
+  assert_eq!(status, StatusCode::OK);
+  assert_eq!(saved["config"]["theme"], "dark");
+
+  let (status, _) = put(&server, json!({ "config": [1, 2] })).await;
+  assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn updates_keep_the_clients_edit_time() {
+  let server = TestServer::start(5).await;
+  for (theme, updated_at) in [
+    ("light", "2026-01-01T00:00:00Z"),
+    ("dark", "2026-02-01T00:00:00Z"),
+  ] {
+    let (status, _) = put(
+      &server,
+      json!({ "config": { "theme": theme }, "updated_at": updated_at }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+  }
+
+  let fetched: Value = server
+    .request(Method::GET, "/settings", None)
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+  assert_eq!(fetched["config"]["theme"], "dark");
+  let updated_at: chrono::DateTime<chrono::Utc> =
+    serde_json::from_value(fetched["updated_at"].clone()).unwrap();
+  assert_eq!(updated_at.to_rfc3339(), "2026-02-01T00:00:00+00:00");
+
+  let (status, body) = put(
+    &server,
+    json!({ "config": { "theme": "light" }, "updated_at": "2026-01-15T00:00:00Z" }),
+  )
+  .await;
+  assert_eq!(status, StatusCode::CONFLICT);
+  assert_eq!(body["error"]["code"], 111);
+}
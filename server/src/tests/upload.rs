@@ -0,0 +1,128 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use reqwest::StatusCode;
+use serde_json::Value;
+
+use super::{TestServer, MAX_IMAGE_SIDE, MAX_UPLOAD_BYTES, PNG};
+use crate::upload::{self, OutputFormat, INVALID_IMAGE, TOO_LARGE, TOO_MANY_IMAGES};
+
+async fn upload(server: &TestServer, bytes: Vec<u8>, mime: &str) -> reqwest::Response {
+  server
+    .post_file("/ingest", "default", Some(&server.api_key), bytes, mime)
+    .await
+}
+
+async fn error_code(response: reqwest::Response) -> i64 {
+  let body: Value = response.json().await.unwrap();
+  body["error"]["code"].as_i64().unwrap()
+}
+
+#[tokio::test]
+async fn non_images_are_rejected_before_charging() {
+  let server = TestServer::start(5).await;
+
+  let response = upload(&server, b"#!/bin/sh\nrm -rf /".to_vec(), "image/png").await;
+  assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+  assert_eq!(error_code(response).await, i64::from(INVALID_IMAGE));
+
+  let truncated = PNG[..PNG.len() / 2].to_vec();
+  let response = upload(&server, truncated, "image/png").await;
+  assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+  assert_eq!(error_code(response).await, i64::from(INVALID_IMAGE));
+
+  let response = upload(&server, vec![0x89; MAX_UPLOAD_BYTES + 1], "image/png").await;
+  assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+  assert_eq!(error_code(response).await, i64::from(TOO_LARGE));
+
+  assert_eq!(server.credits().await, 5);
+  assert!(server.statuses().await.is_empty());
+}
+
+#[tokio::test]
+async fn too_many_images_are_rejected_before_charging() {
+  let server = TestServer::start(5).await;
+  let files = vec![(PNG.to_vec(), "image/png"); upload::MAX_IMAGES + 1];
+  let response = server
+    .post_files("/ingest", "default", Some(&server.api_key), files)
+    .await;
+  assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+  assert_eq!(error_code(response).await, i64::from(TOO_MANY_IMAGES));
+
+  assert_eq!(server.credits().await, 5);
+  assert!(server.statuses().await.is_empty());
+}
+
+#[tokio::test]
+async fn large_screenshots_are_scaled_down_and_reencoded() {
+  let server = TestServer::start(5).await;
+  let screenshot = DynamicImage::new_rgb8(MAX_IMAGE_SIDE * 4, MAX_IMAGE_SIDE * 2);
+  let mut jpeg = Cursor::new(Vec::new());
+  screenshot.write_to(&mut jpeg, ImageFormat::Jpeg).unwrap();
+
+  // Declared as PNG; the server goes by the bytes.
+  let response = upload(&server, jpeg.into_inner(), "image/png").await;
+  assert_eq!(response.status(), StatusCode::OK);
+
+  let stored: Vec<_> = std::fs::read_dir(&server.image_dir)
+    .unwrap()
+    .map(|entry| entry.unwrap().path())
+    .collect();
+  assert_eq!(stored.len(), 1);
+  assert_eq!(stored[0].extension().unwrap(), "png");
+  let image = image::open(&stored[0]).unwrap();
+  assert_eq!(image.dimensions(), (MAX_IMAGE_SIDE, MAX_IMAGE_SIDE / 2));
+}
+
+#[test]
+fn stitched_screens_keep_a_tile_each() {
+  // Three 2:1 screens side by side: scaled to one `max_side` tile per screen, not to one.
+  let stitched = DynamicImage::new_rgb8(MAX_IMAGE_SIDE * 12, MAX_IMAGE_SIDE * 2);
+  let mut png = Cursor::new(Vec::new());
+  stitched.write_to(&mut png, ImageFormat::Png).unwrap();
+  let upload = upload::normalize(png.into_inner(), MAX_IMAGE_SIDE, OutputFormat::Png)
+    .unwrap_or_else(|_| panic!("normalize"));
+  let image = image::load_from_memory(&upload.bytes).unwrap();
+  assert_eq!(image.dimensions(), (MAX_IMAGE_SIDE * 3, MAX_IMAGE_SIDE / 2));
+
+  assert_eq!(upload::fit_bounds(1920, 1080, 2048), (2048, 2048));
+  assert_eq!(upload::fit_bounds(1080, 5760, 2048), (2048, 6144));
+  assert_eq!(upload::fit_bounds(100_000, 10, 2048), (8192, 2048));
+}
+
+#[test]
+fn webp_output_is_lossless() {
+  let screenshot = DynamicImage::ImageRgb8(image::RgbImage::from_fn(
+    MAX_IMAGE_SIDE * 2,
+    MAX_IMAGE_SIDE,
+    |x, y| image::Rgb([(x * 3) as u8, (y * 5) as u8, ((x + y) * 7) as u8]),
+  ));
+  let mut png = Cursor::new(Vec::new());
+  screenshot.write_to(&mut png, ImageFormat::Png).unwrap();
+  let upload = upload::normalize(png.into_inner(), MAX_IMAGE_SIDE, OutputFormat::WebP)
+    .unwrap_or_else(|_| panic!("normalize"));
+  assert_eq!(upload.mime, "image/webp");
+  let decoded = image::load_from_memory_with_format(&upload.bytes, ImageFormat::WebP).unwrap();
+  let resized =
+    screenshot.resize(MAX_IMAGE_SIDE, MAX_IMAGE_SIDE, image::imageops::FilterType::Triangle);
+  assert_eq!(decoded.to_rgb8(), resized.to_rgb8());
+
+  // Small enough: passed through untouched, whatever `IMAGE_FORMAT` says.
+  let upload = upload::normalize(PNG.to_vec(), MAX_IMAGE_SIDE, OutputFormat::WebP)
+    .unwrap_or_else(|_| panic!("normalize"));
+  assert_eq!(upload.bytes, PNG);
+  assert_eq!(upload.mime, "image/png");
+}
+
+#[test]
+fn small_jpegs_stay_jpegs() {
+  let mut jpeg = Cursor::new(Vec::new());
+  DynamicImage::new_rgb8(MAX_IMAGE_SIDE, MAX_IMAGE_SIDE / 2)
+    .write_to(&mut jpeg, ImageFormat::Jpeg)
+    .unwrap();
+  let jpeg = jpeg.into_inner();
+  let upload = upload::normalize(jpeg.clone(), MAX_IMAGE_SIDE, OutputFormat::Png)
+    .unwrap_or_else(|_| panic!("normalize"));
+  assert_eq!(upload.mime, "image/jpeg");
+  assert_eq!(upload.bytes, jpeg);
+}
@@ -0,0 +1,62 @@
+use reqwest::StatusCode;
+use sea_orm::{ConnectionTrait, Statement};
+
+use super::TestServer;
+
+/// `(model, input_tokens, output_tokens, credits)` of the only screen result.
+async fn usage(server: &TestServer) -> (String, i64, i64, i32) {
+  let row = server
+    .db
+    .query_one(Statement::from_string(
+      server.db.get_database_backend(),
+      "SELECT model, input_tokens, output_tokens, credits FROM screen_results",
+    ))
+    .await
+    .expect("query usage")
+    .expect("screen result row");
+  (
+    row.try_get("", "model").unwrap(),
+    row.try_get("", "input_tokens").unwrap(),
+    row.try_get("", "output_tokens").unwrap(),
+    row.try_get("", "credits").unwrap(),
+  )
+}
+
+#[tokio::test]
+async fn unpriced_model_records_usage_at_flat_cost() {
+  let server = TestServer::start(5).await;
+  assert_eq!(server.ingest("default").await.status(), StatusCode::OK);
+
+  assert_eq!(usage(&server).await, ("default".to_string(), 1203, 96, 1));
+  assert_eq!(server.credits().await, 4);
+}
+
+#[tokio::test]
+async fn priced_model_charges_by_tokens() {
+  let server = TestServer::start(10).await;
+  assert_eq!(server.ingest("priced").await.status(), StatusCode::OK);
+
+  // 1203 * 1000 / 1M + 96 * 10000 / 1M = 2.163, rounded up.
+  assert_eq!(usage(&server).await, ("priced".to_string(), 1203, 96, 3));
+  assert_eq!(server.credits().await, 7);
+}
+
+#[tokio::test]
+async fn streamed_usage_is_charged() {
+  let server = TestServer::start(10).await;
+  let events = server.ingest_stream("priced").await;
+  assert_eq!(events.last().unwrap()["type"], "done");
+
+  // 1180 * 1000 / 1M + 42 * 10000 / 1M = 1.6, rounded up.
+  assert_eq!(usage(&server).await, ("priced".to_string(), 1180, 42, 2));
+  assert_eq!(server.credits().await, 8);
+}
+
+#[tokio::test]
+async fn charge_above_the_reservation_never_overdraws() {
+  let server = TestServer::start(2).await;
+  assert_eq!(server.ingest("priced").await.status(), StatusCode::OK);
+
+  assert_eq!(usage(&server).await.3, 2);
+  assert_eq!(server.credits().await, 0);
+}
@@ -0,0 +1,251 @@
+use std::env;
+use std::io::Cursor;
+
+use axum::{
+  extract::{multipart::MultipartError, Multipart},
+  http::StatusCode,
+  Json,
+};
+use image::{imageops::FilterType, DynamicImage, ImageFormat, ImageReader, Limits};
+
+use crate::{error_response, internal_error, ErrorResponse};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+/// The upload is not a PNG, JPEG or WebP image, or cannot be decoded.
+pub const INVALID_IMAGE: i32 = 114;
+/// The request body is over `MAX_UPLOAD_BYTES`.
+pub const TOO_LARGE: i32 = 115;
+/// The request has more than `MAX_IMAGES` `file` parts.
+pub const TOO_MANY_IMAGES: i32 = 116;
+/// The `prompt` part is over `MAX_PROMPT_CHARS`.
+pub const PROMPT_TOO_LONG: i32 = 117;
+
+/// Most `file` parts one ingest request may carry.
+pub const MAX_IMAGES: usize = 8;
+/// Longest `prompt` part accepted, in characters.
+pub const MAX_PROMPT_CHARS: usize = 2_000;
+
+/// Refuse to decode anything bigger, whatever the file size says.
+const MAX_DECODED_SIDE: u32 = 16_384;
+const MAX_DECODED_BYTES: u64 = 512 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+  Png,
+  /// Lossless WebP.
+  WebP,
+}
+
+impl OutputFormat {
+  fn image_format(self) -> ImageFormat {
+    match self {
+      Self::Png => ImageFormat::Png,
+      Self::WebP => ImageFormat::WebP,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct UploadConfig {
+  /// Largest accepted `/ingest` body.
+  pub max_bytes: usize,
+  /// Longest image edge sent upstream when the model sets no `max_image_side`.
+  pub max_side: u32,
+  /// What resized uploads are re-encoded to.
+  pub format: OutputFormat,
+}
+
+impl UploadConfig {
+  /// `MAX_UPLOAD_BYTES` (20 MiB), `IMAGE_MAX_SIDE` (2048) and `IMAGE_FORMAT` (`png` or `webp`).
+  pub fn from_env() -> anyhow::Result<Self> {
+    let number = |name: &str, default: u64| -> anyhow::Result<u64> {
+      match env::var(name) {
+        Ok(value) if !value.trim().is_empty() => value
+          .trim()
+          .parse()
+          .map_err(|_| anyhow::anyhow!("{name} must be a positive number")),
+        _ => Ok(default),
+      }
+    };
+    let format = match env::var("IMAGE_FORMAT")
+      .unwrap_or_default()
+      .trim()
+      .to_ascii_lowercase()
+      .as_str()
+    {
+      "" | "png" => OutputFormat::Png,
+      "webp" => OutputFormat::WebP,
+      other => anyhow::bail!("Unknown IMAGE_FORMAT `{other}` (expected png or webp)"),
+    };
+    Ok(Self {
+      max_bytes: number("MAX_UPLOAD_BYTES", 20 * 1024 * 1024)? as usize,
+      max_side: number("IMAGE_MAX_SIDE", 2048)?.clamp(1, u64::from(MAX_DECODED_SIDE)) as u32,
+      format,
+    })
+  }
+}
+
+/// An upload that decoded as an image and fits the model.
+pub struct Upload {
+  pub bytes: Vec<u8>,
+  pub mime: &'static str,
+}
+
+/// Maps a failed multipart read to 413 when the body limit was hit.
+pub fn multipart_error(message: &str) -> impl FnOnce(MultipartError) -> ApiError + '_ {
+  move |err| {
+    if err.status() == StatusCode::PAYLOAD_TOO_LARGE {
+      error_response(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        "Upload is too large",
+        Some(TOO_LARGE),
+      )
+    } else {
+      internal_error(message)(err)
+    }
+  }
+}
+
+/// `faux_shared::fit_bounds`, kept within what the decoder accepts.
+pub fn fit_bounds(width: u32, height: u32, max_side: u32) -> (u32, u32) {
+  let (max_width, max_height) = faux_shared::fit_bounds(width, height, max_side);
+  (max_width.min(MAX_DECODED_SIDE), max_height.min(MAX_DECODED_SIDE))
+}
+
+/// The parts of an ingest request.
+pub struct IngestForm {
+  /// The `file` parts, in order; at least one and at most `MAX_IMAGES`.
+  pub images: Vec<Vec<u8>>,
+  /// The optional `prompt` part, trimmed; `None` when missing or blank.
+  pub prompt: Option<String>,
+}
+
+/// Reads the `file` parts of an ingest request and its optional `prompt` part.
+pub async fn read_form(multipart: &mut Multipart) -> Result<IngestForm, ApiError> {
+  let mut images = Vec::new();
+  let mut prompt = None;
+  while let Some(field) = multipart
+    .next_field()
+    .await
+    .map_err(multipart_error("Failed to read multipart"))?
+  {
+    if field.name() == Some("prompt") {
+      let text = field
+        .text()
+        .await
+        .map_err(multipart_error("Failed to read prompt"))?;
+      prompt = read_prompt(&text)?;
+      continue;
+    }
+    if field.name() != Some("file") {
+      continue;
+    }
+    if images.len() == MAX_IMAGES {
+      return Err(error_response(
+        StatusCode::BAD_REQUEST,
+        &format!("At most {MAX_IMAGES} images per request"),
+        Some(TOO_MANY_IMAGES),
+      ));
+    }
+    let data = field
+      .bytes()
+      .await
+      .map_err(multipart_error("Failed to read upload bytes"))?;
+    images.push(data.to_vec());
+  }
+  if images.is_empty() {
+    return Err(error_response(
+      StatusCode::BAD_REQUEST,
+      "Missing `file` field in multipart",
+      None,
+    ));
+  }
+  Ok(IngestForm { images, prompt })
+}
+
+/// Trims `text` and drops control characters other than line breaks and tabs.
+fn read_prompt(text: &str) -> Result<Option<String>, ApiError> {
+  let prompt: String = text
+    .trim()
+    .chars()
+    .filter(|c| !c.is_control() || matches!(c, '\n' | '\t'))
+    .collect();
+  if prompt.chars().count() > MAX_PROMPT_CHARS {
+    return Err(error_response(
+      StatusCode::BAD_REQUEST,
+      &format!("Prompt is longer than {MAX_PROMPT_CHARS} characters"),
+      Some(PROMPT_TOO_LONG),
+    ));
+  }
+  Ok(Some(prompt).filter(|prompt| !prompt.is_empty()))
+}
+
+/// Checks that `bytes` really are an image (by content, not by the declared type) and, when it
+/// is larger than `fit_bounds`, shrinks it and re-encodes it to `format`. Uploads that are
+/// small enough are passed through unchanged in their own format, so a compressed JPEG stays
+/// one.
+pub fn normalize(bytes: Vec<u8>, max_side: u32, format: OutputFormat) -> Result<Upload, ApiError> {
+  let source = match image::guess_format(&bytes) {
+    Ok(source @ (ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP)) => source,
+    _ => {
+      return Err(error_response(
+        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        "Upload must be a PNG, JPEG or WebP image",
+        Some(INVALID_IMAGE),
+      ));
+    }
+  };
+  let mut reader = ImageReader::with_format(Cursor::new(&bytes), source);
+  let mut limits = Limits::default();
+  limits.max_image_width = Some(MAX_DECODED_SIDE);
+  limits.max_image_height = Some(MAX_DECODED_SIDE);
+  limits.max_alloc = Some(MAX_DECODED_BYTES);
+  reader.limits(limits);
+  let image = reader.decode().map_err(|err| {
+    error_response(
+      StatusCode::BAD_REQUEST,
+      &format!("Upload is not a valid image: {err}"),
+      Some(INVALID_IMAGE),
+    )
+  })?;
+
+  let (max_width, max_height) = fit_bounds(image.width(), image.height(), max_side);
+  let oversized = image.width() > max_width || image.height() > max_height;
+  if !oversized {
+    return Ok(Upload {
+      bytes,
+      mime: source_mime(source),
+    });
+  }
+  let image = image.resize(max_width, max_height, FilterType::Triangle);
+  // The WebP encoder only takes 8-bit RGB(A); PNG keeps the same pixels either way.
+  let image = if image.color().has_alpha() {
+    DynamicImage::ImageRgba8(image.to_rgba8())
+  } else {
+    DynamicImage::ImageRgb8(image.to_rgb8())
+  };
+  let mut encoded = Cursor::new(Vec::new());
+  image
+    .write_to(&mut encoded, format.image_format())
+    .map_err(internal_error("Image encoding failed"))?;
+  Ok(Upload {
+    bytes: encoded.into_inner(),
+    mime: mime(format),
+  })
+}
+
+fn source_mime(source: ImageFormat) -> &'static str {
+  match source {
+    ImageFormat::Jpeg => "image/jpeg",
+    ImageFormat::WebP => "image/webp",
+    _ => "image/png",
+  }
+}
+
+fn mime(format: OutputFormat) -> &'static str {
+  match format {
+    OutputFormat::Png => "image/png",
+    OutputFormat::WebP => "image/webp",
+  }
+}
@@ -0,0 +1,20 @@
+//! What the desktop client and the server have to agree on. Captures are scaled with
+//! `fit_bounds` on both ends, so the client never uploads more pixels than the server keeps.
+
+/// Most `max_side` tiles a panorama keeps along its long edge.
+pub const MAX_TILES: u32 = 4;
+
+/// The box an image of `width` x `height` has to fit in: `max_side` on each edge, except
+/// that panoramas past 2:1, such as several monitors stitched side by side, get one
+/// `max_side` per started 2:1 tile along their long edge (at most `MAX_TILES`).
+pub fn fit_bounds(width: u32, height: u32, max_side: u32) -> (u32, u32) {
+  let long = width.max(height);
+  let short = width.min(height).max(1);
+  let tiles = long.div_ceil(short.saturating_mul(2)).clamp(1, MAX_TILES);
+  let long_side = max_side.saturating_mul(tiles);
+  if width >= height {
+    (long_side, max_side)
+  } else {
+    (max_side, long_side)
+  }
+}
@@ -1,7 +1,12 @@
-use std::fs;
-use std::io::BufRead;
+use std::io::{BufRead, Cursor, Read};
 use std::sync::mpsc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::Duration;
+
+use image::ImageEncoder;
+
+use faux_shared::fit_bounds;
+
+use crate::config::{CaptureRegion, UploadConfig, UploadFormat};
 
 use serde::Deserialize;
 use reqwest::header::AUTHORIZATION;
@@ -13,6 +18,115 @@ pub struct ApiResponse {
   pub code: String,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelInfo {
+  pub name: String,
+  pub label: String,
+  #[serde(default = "default_true")]
+  pub available: bool,
+}
+
+fn default_true() -> bool {
+  true
+}
+
+#[derive(Deserialize)]
+struct ModelsBody {
+  models: Vec<ModelInfo>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LinkedDevice {
+  pub key: String,
+  pub email: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RemoteSettings {
+  pub config: serde_json::Value,
+  pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// The parts of `GET /me` the client shows.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Account {
+  pub package: Option<String>,
+  pub credits: i64,
+  pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+  pub user: AccountUser,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AccountUser {
+  #[serde(default = "default_true")]
+  pub store_images: bool,
+}
+
+/// One row of `GET /history`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HistoryItem {
+  pub id: String,
+  pub status: String,
+  pub model: Option<String>,
+  pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+  pub preview: String,
+}
+
+/// The parts of `GET /history/{id}` the client shows; the images are skipped.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HistoryEntry {
+  pub id: String,
+  pub text: String,
+  pub code: String,
+  pub error: Option<String>,
+  #[serde(default)]
+  pub follow_ups: Vec<HistoryFollowUp>,
+}
+
+/// A follow-up question on a history entry; `answer` is `None` when answering failed.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HistoryFollowUp {
+  pub question: String,
+  pub answer: Option<String>,
+}
+
+/// What `capture_and_upload` grabs.
+#[derive(Debug, Clone, Copy)]
+pub enum CaptureTarget {
+  Screen(ScreenPick),
+  Region(CaptureRegion),
+  /// The foreground window; the picked screen where that is unknown.
+  Window(ScreenPick),
+  /// Every monitor, side by side from left to right.
+  AllScreens,
+}
+
+/// Which monitor a capture goes to: the pinned one, else the one under `point`, else the first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScreenPick {
+  pub pinned: Option<u32>,
+  pub point: Option<(i32, i32)>,
+}
+
+/// Most `file` parts the server takes in one request.
+pub const MAX_IMAGES: usize = 8;
+/// Longest `prompt` part the server takes, in characters.
+pub const MAX_PROMPT_CHARS: usize = 2_000;
+
+/// A capture encoded for upload, e.g. one held back to send with the next.
+pub struct EncodedCapture {
+  bytes: Vec<u8>,
+  format: UploadFormat,
+  thumbnail: Option<Vec<u8>>,
+}
+
+pub enum SettingsSync {
+  /// The server's copy, to be merged by `updated_at`.
+  Fetched(RemoteSettings),
+  /// Our copy was stored as is.
+  Saved(RemoteSettings),
+}
+
 #[derive(Deserialize)]
 struct ErrorBody {
   error: ErrorDetail,
@@ -22,84 +136,351 @@ struct ErrorBody {
 struct ErrorDetail {
   code: i32,
   message: String,
+  #[serde(default)]
+  retry_after: Option<u64>,
+}
+
+/// Server error code for a rate-limited request (HTTP 429).
+const RATE_LIMITED: i32 = 112;
+/// Response header with the server's id for a capture.
+const CAPTURE_ID_HEADER: &str = "x-capture-id";
+/// Width of the thumbnails kept in the local history.
+const THUMBNAIL_WIDTH: u32 = 240;
+
+enum UploadError {
+  Failed(String),
+  RateLimited(Duration),
+}
+
+impl From<String> for UploadError {
+  fn from(message: String) -> Self {
+    UploadError::Failed(message)
+  }
 }
 
 pub enum WorkerResult {
-  Uploading(u64),
+  /// A small PNG of the capture, for the local history.
+  Thumbnail(u64, Vec<u8>),
+  /// Bytes of the capture sent so far, and in total.
+  Uploading(u64, usize, usize),
+  /// The server's id for the capture, to ask follow-up questions on.
+  CaptureId(u64, String),
   StreamDelta(u64, String),
   Ok(u64, ApiResponse),
   Err(u64, String),
+  /// The server asked us to wait this long before the next capture.
+  RateLimited(u64, Duration),
+  /// A capture taken to be sent with a later one.
+  Queued(Result<EncodedCapture, String>),
+  Models(Result<Vec<ModelInfo>, String>),
+  Linked(Result<LinkedDevice, String>),
+  Settings(Result<SettingsSync, String>),
+  Account(Result<Account, String>),
+  /// A page of history starting at the given offset.
+  History(usize, Result<Vec<HistoryItem>, String>),
+  HistoryEntry(Result<HistoryEntry, String>),
+}
+
+/// Resolves `path` against the server that hosts `api_url` (e.g. `.../ingest_stream`).
+pub fn server_url(api_url: &str, path: &str) -> String {
+  let base = api_url
+    .rsplit_once('/')
+    .map_or(api_url, |(head, _)| head);
+  format!("{base}{path}")
+}
+
+pub fn fetch_models(api_url: &str, auth_token: Option<&str>) -> Result<Vec<ModelInfo>, String> {
+  let url = server_url(api_url, "/models");
+  let client = reqwest::blocking::Client::builder()
+    .timeout(Duration::from_secs(10))
+    .build()
+    .map_err(|e| e.to_string())?;
+  let mut request = client.get(&url);
+  if let Some(token) = auth_token.map(str::trim).filter(|token| !token.is_empty()) {
+    request = request.bearer_auth(token);
+  }
+  let response = request.send().map_err(|e| map_request_error(&url, e))?;
+  let status = response.status();
+  if !status.is_success() {
+    return Err(format!("Model list request returned {status}."));
+  }
+  let body: ModelsBody = response.json().map_err(|e| map_request_error(&url, e))?;
+  Ok(body.models)
+}
+
+/// Exchanges a link code and PIN (see `GET /link` on the server) for a new API key.
+pub fn link_device(api_url: &str, code: &str, pin: &str) -> Result<LinkedDevice, String> {
+  let url = server_url(api_url, "/link");
+  let client = reqwest::blocking::Client::builder()
+    .timeout(Duration::from_secs(10))
+    .build()
+    .map_err(|e| e.to_string())?;
+  let name = std::env::var("COMPUTERNAME")
+    .or_else(|_| std::env::var("HOSTNAME"))
+    .unwrap_or_else(|_| "desktop".to_string());
+  let body = serde_json::json!({ "code": code.trim(), "pin": pin.trim(), "name": name });
+  let response = client
+    .post(&url)
+    .json(&body)
+    .send()
+    .map_err(|e| map_request_error(&url, e))?;
+  let status = response.status();
+  let body_bytes = response.bytes().map_err(|e| map_request_error(&url, e))?;
+  if !status.is_success() {
+    if let Ok(parsed) = serde_json::from_slice::<ErrorBody>(&body_bytes) {
+      return Err(parsed.error.message);
+    }
+    return Err(format!("Link request returned {status}."));
+  }
+  serde_json::from_slice(&body_bytes).map_err(|_| "Server returned an invalid response.".to_string())
+}
+
+pub fn fetch_settings(api_url: &str, auth_token: &str) -> Result<RemoteSettings, String> {
+  let url = server_url(api_url, "/settings");
+  let request = client(Duration::from_secs(10))?.get(&url).bearer_auth(auth_token.trim());
+  send_json(&url, request, "Settings request")
+}
+
+pub fn fetch_account(api_url: &str, auth_token: &str) -> Result<Account, String> {
+  let url = server_url(api_url, "/me");
+  let request = client(Duration::from_secs(10))?.get(&url).bearer_auth(auth_token.trim());
+  send_json(&url, request, "Account request")
+}
+
+/// Turns keeping uploaded screenshots on the server on or off; off also deletes them.
+pub fn update_store_images(
+  api_url: &str,
+  auth_token: &str,
+  store_images: bool,
+) -> Result<Account, String> {
+  let url = server_url(api_url, "/me");
+  let request = client(Duration::from_secs(30))?
+    .patch(&url)
+    .bearer_auth(auth_token.trim())
+    .json(&serde_json::json!({ "store_images": store_images }));
+  send_json(&url, request, "Account update")
+}
+
+/// Past captures, newest first; `status` is `DONE` or `ERROR`, `None` for all.
+pub fn fetch_history(
+  api_url: &str,
+  auth_token: &str,
+  status: Option<&str>,
+  offset: usize,
+  limit: usize,
+) -> Result<Vec<HistoryItem>, String> {
+  let url = server_url(api_url, "/history");
+  let mut query = vec![("offset", offset.to_string()), ("limit", limit.to_string())];
+  if let Some(status) = status {
+    query.push(("status", status.to_string()));
+  }
+  let request = client(Duration::from_secs(10))?
+    .get(&url)
+    .query(&query)
+    .bearer_auth(auth_token.trim());
+  send_json(&url, request, "History request")
+}
+
+pub fn fetch_history_entry(
+  api_url: &str,
+  auth_token: &str,
+  id: &str,
+) -> Result<HistoryEntry, String> {
+  let url = server_url(api_url, &format!("/history/{id}"));
+  let request = client(Duration::from_secs(30))?.get(&url).bearer_auth(auth_token.trim());
+  send_json(&url, request, "History request")
+}
+
+/// Uploads the synced settings; when the server holds newer ones, returns those instead.
+pub fn put_settings(
+  api_url: &str,
+  auth_token: &str,
+  config: &serde_json::Value,
+  updated_at: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<SettingsSync, String> {
+  let url = server_url(api_url, "/settings");
+  let client = reqwest::blocking::Client::builder()
+    .timeout(Duration::from_secs(10))
+    .build()
+    .map_err(|e| e.to_string())?;
+  let body = serde_json::json!({ "config": config, "updated_at": updated_at });
+  let response = client
+    .put(&url)
+    .bearer_auth(auth_token.trim())
+    .json(&body)
+    .send()
+    .map_err(|e| map_request_error(&url, e))?;
+  let status = response.status();
+  if status == reqwest::StatusCode::CONFLICT {
+    return fetch_settings(api_url, auth_token).map(SettingsSync::Fetched);
+  }
+  if !status.is_success() {
+    return Err(format!("Settings upload returned {status}."));
+  }
+  let saved = response.json().map_err(|e| map_request_error(&url, e))?;
+  Ok(SettingsSync::Saved(saved))
+}
+
+/// Captures and encodes `target` without sending it.
+pub fn capture_encoded(target: CaptureTarget, upload: UploadConfig) -> Result<EncodedCapture, String> {
+  let image = capture_target(target)?;
+  let thumbnail = thumbnail_png(&image);
+  Ok(EncodedCapture {
+    bytes: encode_capture(image, upload)?,
+    format: upload.format,
+    thumbnail,
+  })
 }
 
+/// Sends the `queued` captures, then `target` if any, as one request, with `prompt` as the
+/// user's note on them.
+#[allow(clippy::too_many_arguments)]
 pub fn capture_and_upload(
   api_url: &str,
   tx: &mpsc::Sender<WorkerResult>,
-  screen_point: Option<(i32, i32)>,
+  target: Option<CaptureTarget>,
+  queued: Vec<EncodedCapture>,
+  prompt: Option<String>,
+  auth_token: Option<String>,
+  model: Option<String>,
+  upload: UploadConfig,
+  request_id: u64,
+) {
+  let result = capture_and_upload_inner(
+    api_url,
+    tx,
+    target,
+    queued,
+    prompt.as_deref(),
+    auth_token.as_deref(),
+    model.as_deref(),
+    upload,
+    request_id,
+  );
+  send_answer(tx, request_id, result);
+}
+
+/// Asks `question` about the capture the server knows as `capture_id`; the answer streams in
+/// like a capture's.
+pub fn ask_follow_up(
+  api_url: &str,
+  tx: &mpsc::Sender<WorkerResult>,
   auth_token: Option<String>,
   model: Option<String>,
+  capture_id: &str,
+  question: &str,
   request_id: u64,
 ) {
-  match capture_and_upload_inner(
+  let result = ask_follow_up_inner(
     api_url,
     tx,
-    screen_point,
     auth_token.as_deref(),
     model.as_deref(),
+    capture_id,
+    question,
     request_id,
-  ) {
+  );
+  send_answer(tx, request_id, result);
+}
+
+fn ask_follow_up_inner(
+  api_url: &str,
+  tx: &mpsc::Sender<WorkerResult>,
+  auth_token: Option<&str>,
+  model: Option<&str>,
+  capture_id: &str,
+  question: &str,
+  request_id: u64,
+) -> Result<ApiResponse, UploadError> {
+  let url = server_url(api_url, &format!("/history/{capture_id}/follow_up"));
+  let client = reqwest::blocking::Client::builder()
+    .timeout(Duration::from_secs(180))
+    .connect_timeout(Duration::from_secs(10))
+    .build()
+    .map_err(|e| e.to_string())?;
+  let mut request = client
+    .post(&url)
+    .json(&serde_json::json!({ "question": question }));
+  if let Some(token) = auth_token.map(str::trim).filter(|token| !token.is_empty()) {
+    request = request.bearer_auth(token);
+  }
+  if let Some(model) = model.map(str::trim).filter(|model| !model.is_empty()) {
+    request = request.header("x-model", model);
+  }
+  let response = request.send().map_err(|e| map_request_error(&url, e))?;
+  read_answer(&url, response, tx, request_id)
+}
+
+fn send_answer(
+  tx: &mpsc::Sender<WorkerResult>,
+  request_id: u64,
+  result: Result<ApiResponse, UploadError>,
+) {
+  match result {
     Ok(response) => {
       let _ = tx.send(WorkerResult::Ok(request_id, response));
     }
-    Err(err) => {
+    Err(UploadError::Failed(err)) => {
       let _ = tx.send(WorkerResult::Err(request_id, err));
     }
+    Err(UploadError::RateLimited(wait)) => {
+      let _ = tx.send(WorkerResult::RateLimited(request_id, wait));
+    }
   }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn capture_and_upload_inner(
   api_url: &str,
   tx: &mpsc::Sender<WorkerResult>,
-  screen_point: Option<(i32, i32)>,
+  target: Option<CaptureTarget>,
+  queued: Vec<EncodedCapture>,
+  prompt: Option<&str>,
   auth_token: Option<&str>,
   model: Option<&str>,
+  upload: UploadConfig,
   request_id: u64,
-) -> Result<ApiResponse, String> {
-  let screen = if let Some((x, y)) = screen_point {
-    if let Ok(screen) = screenshots::Screen::from_point(x, y) {
-      screen
-    } else {
-      let screens = screenshots::Screen::all().map_err(|e| e.to_string())?;
-      screens
-        .into_iter()
-        .next()
-        .ok_or_else(|| "No screens found".to_string())?
-    }
-  } else {
-    let screens = screenshots::Screen::all().map_err(|e| e.to_string())?;
-    screens
-      .into_iter()
-      .next()
-      .ok_or_else(|| "No screens found".to_string())?
-  };
-  let image = screen.capture().map_err(|e| e.to_string())?;
-  let _ = tx.send(WorkerResult::Uploading(request_id));
-
-  let timestamp = SystemTime::now()
-    .duration_since(UNIX_EPOCH)
-    .map_err(|e| e.to_string())?
-    .as_millis();
-  let temp_path = std::env::temp_dir().join(format!("faux_capture_{timestamp}.png"));
-  image.save(&temp_path).map_err(|e| e.to_string())?;
-
-  let bytes = fs::read(&temp_path).map_err(|e| e.to_string())?;
-  let _ = fs::remove_file(&temp_path);
-
-  let byte_len = bytes.len();
-  let part = reqwest::blocking::multipart::Part::bytes(bytes)
-    .file_name("screenshot.png")
-    .mime_str("image/png")
-    .map_err(|e| e.to_string())?;
-  let form = reqwest::blocking::multipart::Form::new().part("file", part);
+) -> Result<ApiResponse, UploadError> {
+  let mut captures = queued;
+  if let Some(target) = target {
+    captures.push(capture_encoded(target, upload)?);
+  }
+  if captures.is_empty() {
+    return Err("Nothing to send.".to_string().into());
+  }
+  if let Some(thumbnail) = captures.first_mut().and_then(|capture| capture.thumbnail.take()) {
+    let _ = tx.send(WorkerResult::Thumbnail(request_id, thumbnail));
+  }
+  let byte_len: usize = captures.iter().map(|capture| capture.bytes.len()).sum();
+  let _ = tx.send(WorkerResult::Uploading(request_id, 0, byte_len));
+
+  let mut form = reqwest::blocking::multipart::Form::new();
+  if let Some(prompt) = prompt.map(str::trim).filter(|prompt| !prompt.is_empty()) {
+    form = form.text("prompt", prompt.to_string());
+  }
+  let mut offset = 0;
+  for capture in captures {
+    let len = capture.bytes.len();
+    let (file_name, mime) = match capture.format {
+      UploadFormat::Png => ("screenshot.png", "image/png"),
+      UploadFormat::Jpeg => ("screenshot.jpg", "image/jpeg"),
+      UploadFormat::Webp => ("screenshot.webp", "image/webp"),
+    };
+    let body = UploadProgress {
+      bytes: Cursor::new(capture.bytes),
+      tx: tx.clone(),
+      request_id,
+      offset,
+      total: byte_len,
+      reported: offset,
+    };
+    let part = reqwest::blocking::multipart::Part::reader_with_length(body, len as u64)
+      .file_name(file_name)
+      .mime_str(mime)
+      .map_err(|e| e.to_string())?;
+    form = form.part("file", part);
+    offset += len;
+  }
 
   let timeout_secs = std::env::var("API_TIMEOUT_SECS")
     .ok()
@@ -132,6 +513,23 @@ fn capture_and_upload_inner(
   let response = client
     .execute(request)
     .map_err(|e| map_request_error(api_url, e))?;
+  if let Some(capture_id) = response
+    .headers()
+    .get(CAPTURE_ID_HEADER)
+    .and_then(|val| val.to_str().ok())
+  {
+    let _ = tx.send(WorkerResult::CaptureId(request_id, capture_id.to_string()));
+  }
+  read_answer(api_url, response, tx, request_id)
+}
+
+/// Reads an answer, streamed or not, or the error the server sent instead.
+fn read_answer(
+  api_url: &str,
+  response: reqwest::blocking::Response,
+  tx: &mpsc::Sender<WorkerResult>,
+  request_id: u64,
+) -> Result<ApiResponse, UploadError> {
   let status = response.status();
   if cfg!(debug_assertions) {
     log_response_details(status, response.headers());
@@ -144,8 +542,13 @@ fn capture_and_upload_inner(
     .unwrap_or(false);
 
   if is_stream {
-    return read_streaming_response(response, tx, request_id);
+    return Ok(read_streaming_response(response, tx, request_id)?);
   }
+  let retry_after_header = response
+    .headers()
+    .get(reqwest::header::RETRY_AFTER)
+    .and_then(|val| val.to_str().ok())
+    .and_then(|val| val.trim().parse::<u64>().ok());
 
   let body_bytes = response
     .bytes()
@@ -153,30 +556,237 @@ fn capture_and_upload_inner(
   let body_text = String::from_utf8_lossy(&body_bytes).to_string();
 
   if !status.is_success() {
-    if let Ok(parsed) = serde_json::from_slice::<ErrorBody>(&body_bytes) {
+    let parsed = serde_json::from_slice::<ErrorBody>(&body_bytes).ok();
+    if let Some(wait) = rate_limit_wait(status, retry_after_header, parsed.as_ref()) {
+      return Err(UploadError::RateLimited(wait));
+    }
+    if let Some(parsed) = parsed {
       let code = parsed.error.code;
       let message = parsed.error.message;
       if !message.is_empty() {
-        return Err(format!("Error ({code}): {message}"));
+        return Err(format!("Error ({code}): {message}").into());
       }
     }
     if cfg!(debug_assertions) {
       eprintln!("API error: {status}: {body_text}");
     }
     if body_text.is_empty() {
-      return Err(format!("API returned {status}."));
+      return Err(format!("API returned {status}.").into());
     }
-    return Err(format!("API returned {status}: {body_text}"));
+    return Err(format!("API returned {status}: {body_text}").into());
   }
 
   serde_json::from_slice::<ApiResponse>(&body_bytes).map_err(|err| {
     if cfg!(debug_assertions) {
       eprintln!("API response parse error: {err}. Body: {body_text}");
     }
-    "Server returned an invalid response. Please try again or check server logs.".to_string()
+    UploadError::Failed(
+      "Server returned an invalid response. Please try again or check server logs.".to_string(),
+    )
   })
 }
 
+fn capture_target(target: CaptureTarget) -> Result<image::RgbaImage, String> {
+  let region = match target {
+    CaptureTarget::Screen(pick) => {
+      return pick.screen()?.capture().map_err(|e| e.to_string());
+    }
+    CaptureTarget::Region(region) => region,
+    CaptureTarget::Window(pick) => match active_window_region() {
+      Some(region) => region,
+      None if cfg!(target_os = "windows") => {
+        return pick.screen()?.capture().map_err(|e| e.to_string());
+      }
+      // Rather than quietly sending the whole screen instead.
+      None => return Err("Active window capture is only available on Windows.".to_string()),
+    },
+    CaptureTarget::AllScreens => return capture_all_screens(),
+  };
+  let pick = ScreenPick {
+    pinned: None,
+    point: Some((region.screen_x, region.screen_y)),
+  };
+  let screen = pick.screen()?;
+  screen
+    .capture_area(
+      region.x as i32,
+      region.y as i32,
+      region.width,
+      region.height,
+    )
+    .map_err(|e| e.to_string())
+}
+
+impl ScreenPick {
+  pub fn screen(self) -> Result<screenshots::Screen, String> {
+    let screens = screenshots::Screen::all().map_err(|e| e.to_string())?;
+    if let Some(screen) = self
+      .pinned
+      .and_then(|id| screens.iter().find(|screen| screen.display_info.id == id))
+    {
+      return Ok(*screen);
+    }
+    if let Some((x, y)) = self.point {
+      if let Ok(screen) = screenshots::Screen::from_point(x, y) {
+        return Ok(screen);
+      }
+    }
+    screens
+      .into_iter()
+      .next()
+      .ok_or_else(|| "No screens found".to_string())
+  }
+}
+
+/// The monitors to offer in the settings, left to right.
+pub fn list_monitors() -> Vec<screenshots::display_info::DisplayInfo> {
+  let mut monitors: Vec<_> = screenshots::Screen::all()
+    .unwrap_or_default()
+    .into_iter()
+    .map(|screen| screen.display_info)
+    .collect();
+  monitors.sort_by_key(|info| (info.x, info.y));
+  monitors
+}
+
+/// Captures every monitor and lays them out left to right, top-aligned, on black.
+fn capture_all_screens() -> Result<image::RgbaImage, String> {
+  let mut screens = screenshots::Screen::all().map_err(|e| e.to_string())?;
+  if screens.is_empty() {
+    return Err("No screens found".to_string());
+  }
+  screens.sort_by_key(|screen| (screen.display_info.x, screen.display_info.y));
+  let captures = screens
+    .iter()
+    .map(|screen| screen.capture().map_err(|e| e.to_string()))
+    .collect::<Result<Vec<_>, _>>()?;
+  let width = captures.iter().map(|capture| capture.width()).sum();
+  let height = captures.iter().map(|capture| capture.height()).max().unwrap_or(0);
+  let mut stitched =
+    image::RgbaImage::from_pixel(width, height, image::Rgba([0, 0, 0, 255]));
+  let mut x = 0;
+  for capture in &captures {
+    image::imageops::replace(&mut stitched, capture, i64::from(x), 0);
+    x += capture.width();
+  }
+  Ok(stitched)
+}
+
+/// Bounds of the foreground window, cut to the screen its center is on.
+#[cfg(target_os = "windows")]
+fn active_window_region() -> Option<CaptureRegion> {
+  use windows::Win32::Foundation::RECT;
+  use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowRect};
+
+  let hwnd = unsafe { GetForegroundWindow() };
+  if hwnd.0 == 0 {
+    return None;
+  }
+  let mut rect = RECT::default();
+  unsafe { GetWindowRect(hwnd, &mut rect) }.ok()?;
+  let center = ((rect.left + rect.right) / 2, (rect.top + rect.bottom) / 2);
+  let display = screenshots::Screen::from_point(center.0, center.1).ok()?.display_info;
+  let left = rect.left.max(display.x);
+  let top = rect.top.max(display.y);
+  let right = rect.right.min(display.x + display.width as i32);
+  let bottom = rect.bottom.min(display.y + display.height as i32);
+  if right <= left || bottom <= top {
+    return None;
+  }
+  Some(CaptureRegion {
+    screen_x: display.x,
+    screen_y: display.y,
+    x: (left - display.x) as u32,
+    y: (top - display.y) as u32,
+    width: (right - left) as u32,
+    height: (bottom - top) as u32,
+  })
+}
+
+#[cfg(not(target_os = "windows"))]
+fn active_window_region() -> Option<CaptureRegion> {
+  None
+}
+
+/// Scales the capture down to `fit_bounds` and encodes it in memory.
+fn encode_capture(
+  capture: image::RgbaImage,
+  upload: UploadConfig,
+) -> Result<Vec<u8>, String> {
+  let (width, height) = capture.dimensions();
+  // Screens are opaque; dropping alpha keeps the files smaller and JPEG needs it anyway.
+  let mut rgb = image::DynamicImage::ImageRgba8(capture).into_rgb8();
+  let (max_width, max_height) = fit_bounds(width, height, upload.max_side);
+  if upload.max_side > 0 && (width > max_width || height > max_height) {
+    let scale = (max_width as f32 / width as f32).min(max_height as f32 / height as f32);
+    rgb = image::imageops::resize(
+      &rgb,
+      ((width as f32 * scale).round() as u32).max(1),
+      ((height as f32 * scale).round() as u32).max(1),
+      image::imageops::FilterType::Triangle,
+    );
+  }
+
+  let mut bytes = Vec::new();
+  let (width, height) = rgb.dimensions();
+  let color = image::ColorType::Rgb8;
+  let result = match upload.format {
+    UploadFormat::Png => {
+      image::codecs::png::PngEncoder::new(&mut bytes).write_image(&rgb, width, height, color)
+    }
+    UploadFormat::Jpeg => {
+      image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, upload.quality.clamp(1, 100))
+        .write_image(&rgb, width, height, color)
+    }
+    UploadFormat::Webp => image::codecs::webp::WebPEncoder::new_lossless(&mut bytes)
+      .write_image(&rgb, width, height, color),
+  };
+  result.map_err(|e| format!("Failed to encode capture: {e}"))?;
+  Ok(bytes)
+}
+
+/// One image of the upload body; reports how much of the whole body reqwest has read so far.
+struct UploadProgress {
+  bytes: Cursor<Vec<u8>>,
+  tx: mpsc::Sender<WorkerResult>,
+  request_id: u64,
+  /// Bytes of the images before this one, and of all of them.
+  offset: usize,
+  total: usize,
+  reported: usize,
+}
+
+impl Read for UploadProgress {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    let read = self.bytes.read(buf)?;
+    let sent = self.offset + self.bytes.position() as usize;
+    let total = self.total;
+    // At most about a hundred updates per upload.
+    if read > 0 && (sent == total || sent - self.reported >= (total / 100).max(16 * 1024)) {
+      self.reported = sent;
+      let _ = self
+        .tx
+        .send(WorkerResult::Uploading(self.request_id, sent, total));
+    }
+    Ok(read)
+  }
+}
+
+fn thumbnail_png(image: &image::RgbaImage) -> Option<Vec<u8>> {
+  let (width, height) = image.dimensions();
+  let scale = (THUMBNAIL_WIDTH as f32 / width.max(1) as f32).min(1.0);
+  let thumbnail = image::imageops::thumbnail(
+    image,
+    ((width as f32 * scale).round() as u32).max(1),
+    ((height as f32 * scale).round() as u32).max(1),
+  );
+  let mut bytes = std::io::Cursor::new(Vec::new());
+  thumbnail
+    .write_to(&mut bytes, image::ImageOutputFormat::Png)
+    .ok()?;
+  Some(bytes.into_inner())
+}
+
 #[derive(Deserialize)]
 struct StreamEnvelope {
   #[serde(rename = "type")]
@@ -250,6 +860,50 @@ fn read_streaming_response(
   })
 }
 
+/// How long to wait when the server rate-limited the request, from `Retry-After` or the body.
+fn rate_limit_wait(
+  status: reqwest::StatusCode,
+  retry_after_header: Option<u64>,
+  body: Option<&ErrorBody>,
+) -> Option<Duration> {
+  let code = body.map(|body| body.error.code);
+  if status != reqwest::StatusCode::TOO_MANY_REQUESTS || code.is_some_and(|code| code != RATE_LIMITED) {
+    return None;
+  }
+  let seconds = retry_after_header
+    .or_else(|| body.and_then(|body| body.error.retry_after))
+    .unwrap_or(60);
+  Some(Duration::from_secs(seconds.max(1)))
+}
+
+fn client(timeout: Duration) -> Result<reqwest::blocking::Client, String> {
+  reqwest::blocking::Client::builder()
+    .timeout(timeout)
+    .build()
+    .map_err(|e| e.to_string())
+}
+
+/// Sends `request` and decodes its JSON answer. A failed one reports the server's error like
+/// the ingest path does, or `what` and the status when the body carries none.
+fn send_json<T: serde::de::DeserializeOwned>(
+  url: &str,
+  request: reqwest::blocking::RequestBuilder,
+  what: &str,
+) -> Result<T, String> {
+  let response = request.send().map_err(|e| map_request_error(url, e))?;
+  let status = response.status();
+  if !status.is_success() {
+    let body_bytes = response.bytes().map_err(|e| map_request_error(url, e))?;
+    if let Ok(parsed) = serde_json::from_slice::<ErrorBody>(&body_bytes) {
+      if !parsed.error.message.is_empty() {
+        return Err(format!("Error ({}): {}", parsed.error.code, parsed.error.message));
+      }
+    }
+    return Err(format!("{what} returned {status}."));
+  }
+  response.json().map_err(|e| map_request_error(url, e))
+}
+
 fn map_request_error(api_url: &str, err: reqwest::Error) -> String {
   if cfg!(debug_assertions) {
     eprintln!("Network error for {api_url}: {err}");
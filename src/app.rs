@@ -11,10 +11,22 @@ use egui_phosphor as phosphor;
 use global_hotkey::hotkey::{Code, HotKey, Modifiers};
 use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
 
-use crate::api::{ApiResponse, WorkerResult, capture_and_upload};
-use crate::config::{AppConfig, WindowPosition, current_dir_config_path, read_config, write_config};
+use crate::api::{
+  Account, ApiResponse, CaptureTarget, EncodedCapture, HistoryItem, MAX_IMAGES, MAX_PROMPT_CHARS,
+  ModelInfo, RemoteSettings, ScreenPick, SettingsSync, WorkerResult, capture_and_upload, capture_encoded,
+  fetch_account, fetch_models, fetch_settings, link_device, list_monitors, put_settings,
+  update_store_images,
+};
+use crate::config::{
+  AppConfig, CaptureMode, WindowPosition, apply_synced_settings, current_dir_config_path, read_config,
+  synced_settings, write_config,
+};
+use crate::local_history::LocalHistory;
 use crate::ui::{draw_vertical_divider, install_phosphor_fonts};
 
+mod follow_up;
+mod history_window;
+mod region_overlay;
 mod response_window;
 mod settings_window;
 
@@ -55,12 +67,30 @@ fn load_app_icon() -> Option<egui::IconData> {
   eframe::icon_data::from_png_bytes(&bytes).ok()
 }
 
+/// "Uploading 1.2 / 3.4 MB (35%)".
+fn upload_status(sent: usize, total: usize) -> String {
+  let mb = |bytes: usize| bytes as f64 / (1024.0 * 1024.0);
+  let percent = (sent * 100).checked_div(total).unwrap_or(100);
+  format!(
+    "Uploading {:.1} / {:.1} MB ({percent}%)",
+    mb(sent),
+    mb(total)
+  )
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 struct HotKeys {
   show_hide: HotKey,
   screenshot: HotKey,
   close_response: HotKey,
   quit: HotKey,
+  history_back: HotKey,
+  history_forward: HotKey,
+  capture_region: HotKey,
+  capture_window: HotKey,
+  capture_last_region: HotKey,
+  queue_capture: HotKey,
+  follow_up: HotKey,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -69,6 +99,13 @@ enum HotkeyAction {
   Screenshot,
   CloseResponse,
   Quit,
+  HistoryBack,
+  HistoryForward,
+  CaptureRegion,
+  CaptureWindow,
+  CaptureLastRegion,
+  QueueCapture,
+  FollowUp,
 }
 
 struct AppState {
@@ -104,7 +141,9 @@ struct AppState {
   hotkey_capture: Option<HotkeyAction>,
   config_dirty: bool,
   last_config_save: std::time::Instant,
+  #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
   main_hwnd: Option<isize>,
+  #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
     main_hwnd_hooked: bool,
     settings_hwnd_hooked: bool,
     last_screen_point: Option<(i32, i32)>,
@@ -117,6 +156,68 @@ struct AppState {
     divider_picker_open: bool,
     next_request_id: u64,
     current_request_id: Option<u64>,
+    models: Vec<ModelInfo>,
+    models_loading: bool,
+    link_code: String,
+    link_pin: String,
+    linking: bool,
+    link_status: Option<String>,
+    /// Synced fields as last agreed with the server; `None` until the first fetch.
+    settings_snapshot: Option<serde_json::Value>,
+    settings_syncing: bool,
+    last_settings_check: std::time::Instant,
+    /// Set by a 429 from the server; captures wait for it and the error shows a countdown.
+    rate_limited_until: Option<std::time::Instant>,
+    /// From `GET /me`; refreshed after every capture and once a minute.
+    account: Option<Account>,
+    /// The server's message from the last failed `/me` call, shown on the credits badge.
+    account_error: Option<String>,
+    account_loading: bool,
+    last_account_fetch: std::time::Instant,
+    history_open: bool,
+    history_hwnd_hooked: bool,
+    /// Pages of `GET /history` loaded so far, newest first.
+    history: Vec<HistoryItem>,
+    history_loading: bool,
+    /// Whether the last page was full, so another one may follow.
+    history_more: bool,
+    /// `DONE` or `ERROR`; `None` shows everything.
+    history_filter: Option<&'static str>,
+    history_status: Option<String>,
+    /// Whether the history window lists this device's captures instead of the account's.
+    history_local: bool,
+    history_query: String,
+    local_history: LocalHistory,
+    /// `local_history.search` for the query it was run with; cleared when the store changes.
+    local_matches: Option<(String, Vec<usize>)>,
+    /// Newest-first index of the local entry shown in the response window.
+    history_cursor: Option<usize>,
+    /// Thumbnail of the capture in flight, stored with its answer.
+    pending_thumbnail: Option<(u64, Vec<u8>)>,
+    thumbnail_textures: std::collections::HashMap<String, egui::TextureHandle>,
+    /// Open while a region is being dragged out for `CaptureMode::Region`.
+    region_overlay: Option<region_overlay::RegionOverlay>,
+    /// Monitors listed in the settings; refreshed whenever they open.
+    monitors: Vec<screenshots::display_info::DisplayInfo>,
+    /// Captures held back by the queue hotkey, sent ahead of the next capture.
+    capture_queue: Vec<EncodedCapture>,
+    /// Whether a capture for the queue is being taken.
+    queueing: bool,
+    /// Server id of the answer on screen, which follow-up questions are asked about.
+    capture_id: Option<String>,
+    follow_ups: Vec<follow_up::FollowUp>,
+    follow_up_input: String,
+    /// Whether the question box under the answer is open.
+    follow_up_active: bool,
+    follow_up_focused: bool,
+    /// Whether the request in flight is a follow-up rather than a capture.
+    follow_up_pending: bool,
+    /// Note typed in the main bar, sent with the next capture.
+    prompt: String,
+    prompt_open: bool,
+    prompt_focused: bool,
+    /// Note sent with the capture in flight, stored with its answer.
+    pending_prompt: String,
   }
 
   impl AppState {
@@ -126,6 +227,8 @@ struct AppState {
   const RESPONSE_MIN_HEIGHT: f32 = 180.0;
   const RESPONSE_HEIGHT: f32 = 400.0;
   const RESPONSE_ANCHOR_GAP: f32 = 10.0;
+  const ACCOUNT_REFRESH: std::time::Duration = std::time::Duration::from_secs(60);
+  const LOW_CREDIT_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 150, 60);
     const RESPONSE_TITLE: &'static str = "Faux Response";
 
   fn parse_hotkey_spec(spec: &str, fallback: &str) -> HotKey {
@@ -179,16 +282,45 @@ struct AppState {
       .unwrap_or_else(|| "CmdOrCtrl+KeyX".to_string());
     let quit_spec = Self::hotkey_spec_from_token(&config.hotkeys.quit, HotkeyAction::Quit)
       .unwrap_or_else(|| "CmdOrCtrl+Escape".to_string());
+    let back_spec = Self::hotkey_spec_from_token(&config.hotkeys.history_back, HotkeyAction::HistoryBack)
+      .unwrap_or_else(|| "CmdOrCtrl+KeyJ".to_string());
+    let forward_spec = Self::hotkey_spec_from_token(&config.hotkeys.history_forward, HotkeyAction::HistoryForward)
+      .unwrap_or_else(|| "CmdOrCtrl+KeyK".to_string());
+    let region_spec = Self::hotkey_spec_from_token(&config.hotkeys.capture_region, HotkeyAction::CaptureRegion)
+      .unwrap_or_else(|| "CmdOrCtrl+Digit1".to_string());
+    let window_spec = Self::hotkey_spec_from_token(&config.hotkeys.capture_window, HotkeyAction::CaptureWindow)
+      .unwrap_or_else(|| "CmdOrCtrl+Digit2".to_string());
+    let last_region_spec =
+      Self::hotkey_spec_from_token(&config.hotkeys.capture_last_region, HotkeyAction::CaptureLastRegion)
+        .unwrap_or_else(|| "CmdOrCtrl+Digit3".to_string());
+    let queue_spec = Self::hotkey_spec_from_token(&config.hotkeys.queue_capture, HotkeyAction::QueueCapture)
+      .unwrap_or_else(|| "CmdOrCtrl+Digit4".to_string());
+    let follow_up_spec = Self::hotkey_spec_from_token(&config.hotkeys.follow_up, HotkeyAction::FollowUp)
+      .unwrap_or_else(|| "CmdOrCtrl+KeyU".to_string());
 
     let show_hide = Self::parse_hotkey_spec(&show_hide_spec, "CmdOrCtrl+KeyH");
     let screenshot = Self::parse_hotkey_spec(&screenshot_spec, "CmdOrCtrl+KeyQ");
     let close_response = Self::parse_hotkey_spec(&close_spec, "CmdOrCtrl+KeyX");
     let quit = Self::parse_hotkey_spec(&quit_spec, "CmdOrCtrl+Escape");
+    let history_back = Self::parse_hotkey_spec(&back_spec, "CmdOrCtrl+KeyJ");
+    let history_forward = Self::parse_hotkey_spec(&forward_spec, "CmdOrCtrl+KeyK");
+    let capture_region = Self::parse_hotkey_spec(&region_spec, "CmdOrCtrl+Digit1");
+    let capture_window = Self::parse_hotkey_spec(&window_spec, "CmdOrCtrl+Digit2");
+    let capture_last_region = Self::parse_hotkey_spec(&last_region_spec, "CmdOrCtrl+Digit3");
+    let queue_capture = Self::parse_hotkey_spec(&queue_spec, "CmdOrCtrl+Digit4");
+    let follow_up = Self::parse_hotkey_spec(&follow_up_spec, "CmdOrCtrl+KeyU");
     HotKeys {
       show_hide,
       screenshot,
       close_response,
       quit,
+      history_back,
+      history_forward,
+      capture_region,
+      capture_window,
+      capture_last_region,
+      queue_capture,
+      follow_up,
     }
   }
 
@@ -205,6 +337,20 @@ struct AppState {
     manager
       .register(hotkeys.close_response)
       .map_err(|e| format!("close-response hotkey: {e}"))?;
+    // Browsing history, the extra capture modes and follow-ups are optional; a taken key only disables that action.
+    for (name, hotkey) in [
+      ("history-back", hotkeys.history_back),
+      ("history-forward", hotkeys.history_forward),
+      ("capture-region", hotkeys.capture_region),
+      ("capture-window", hotkeys.capture_window),
+      ("capture-last-region", hotkeys.capture_last_region),
+      ("queue-capture", hotkeys.queue_capture),
+      ("follow-up", hotkeys.follow_up),
+    ] {
+      if let Err(err) = manager.register(hotkey) {
+        eprintln!("Failed to register {name} hotkey: {err}");
+      }
+    }
 
     if manager.register(hotkeys.quit).is_err() {
       let fallback = Self::parse_hotkey_spec("CmdOrCtrl+KeyP", "CmdOrCtrl+KeyP");
@@ -241,6 +387,13 @@ struct AppState {
     let _ = self._hotkey_manager.unregister(old.screenshot);
     let _ = self._hotkey_manager.unregister(old.close_response);
     let _ = self._hotkey_manager.unregister(old.quit);
+    let _ = self._hotkey_manager.unregister(old.history_back);
+    let _ = self._hotkey_manager.unregister(old.history_forward);
+    let _ = self._hotkey_manager.unregister(old.capture_region);
+    let _ = self._hotkey_manager.unregister(old.capture_window);
+    let _ = self._hotkey_manager.unregister(old.capture_last_region);
+    let _ = self._hotkey_manager.unregister(old.queue_capture);
+    let _ = self._hotkey_manager.unregister(old.follow_up);
 
     let (registered, quit_token) =
       match Self::register_hotkeys_with_fallback(&self._hotkey_manager, desired_hotkeys) {
@@ -304,6 +457,13 @@ struct AppState {
       HotkeyAction::Screenshot => self.config.hotkeys.screenshot = token,
       HotkeyAction::CloseResponse => self.config.hotkeys.close_response = token,
       HotkeyAction::Quit => self.config.hotkeys.quit = token,
+      HotkeyAction::HistoryBack => self.config.hotkeys.history_back = token,
+      HotkeyAction::HistoryForward => self.config.hotkeys.history_forward = token,
+      HotkeyAction::CaptureRegion => self.config.hotkeys.capture_region = token,
+      HotkeyAction::CaptureWindow => self.config.hotkeys.capture_window = token,
+      HotkeyAction::CaptureLastRegion => self.config.hotkeys.capture_last_region = token,
+      HotkeyAction::QueueCapture => self.config.hotkeys.queue_capture = token,
+      HotkeyAction::FollowUp => self.config.hotkeys.follow_up = token,
     }
     self.apply_hotkeys_from_config();
     self.save_config();
@@ -443,6 +603,8 @@ struct AppState {
   fn new(cc: &eframe::CreationContext<'_>) -> Self {
     let config_path = current_dir_config_path();
     let mut config = read_config(&config_path);
+    let local_history =
+      LocalHistory::open(config_path.parent().unwrap_or(std::path::Path::new(".")));
     let api_url = std::env::var("API_URL")
       .unwrap_or_else(|_| "http://localhost:3005/ingest_stream".to_string());
 
@@ -531,7 +693,7 @@ struct AppState {
       // Persist any fallback hotkey adjustments.
     }
 
-    Self {
+    let mut app = Self {
       config: config.clone(),
       config_path,
       api_url,
@@ -574,7 +736,52 @@ struct AppState {
         divider_picker_open: false,
         next_request_id: 1,
         current_request_id: None,
-      }
+        models: Vec::new(),
+        models_loading: false,
+        link_code: String::new(),
+        link_pin: String::new(),
+        linking: false,
+        link_status: None,
+        settings_snapshot: None,
+        settings_syncing: false,
+        last_settings_check: std::time::Instant::now(),
+        rate_limited_until: None,
+        account: None,
+        account_error: None,
+        account_loading: false,
+        last_account_fetch: std::time::Instant::now(),
+        history_open: false,
+        history_hwnd_hooked: false,
+        history: Vec::new(),
+        history_loading: false,
+        history_more: false,
+        history_filter: None,
+        history_status: None,
+        history_local: true,
+        history_query: String::new(),
+        local_history,
+        local_matches: None,
+        history_cursor: None,
+        pending_thumbnail: None,
+        thumbnail_textures: std::collections::HashMap::new(),
+        region_overlay: None,
+        monitors: Vec::new(),
+        capture_queue: Vec::new(),
+        queueing: false,
+        capture_id: None,
+        follow_ups: Vec::new(),
+        follow_up_input: String::new(),
+        follow_up_active: false,
+        follow_up_focused: false,
+        follow_up_pending: false,
+        prompt: String::new(),
+        prompt_open: false,
+        prompt_focused: false,
+        pending_prompt: String::new(),
+      };
+    app.fetch_remote_settings();
+    app.refresh_account();
+    app
   }
 
   fn process_hotkeys(&mut self, ctx: &egui::Context) {
@@ -596,9 +803,37 @@ struct AppState {
           ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
         }
       } else if event.id == self.hotkeys.screenshot.id() {
-        self.start_capture(ctx);
+        self.start_capture(ctx, self.config.capture_mode, false);
+      } else if event.id == self.hotkeys.capture_region.id() {
+        if event.state == HotKeyState::Pressed {
+          self.start_capture(ctx, CaptureMode::Region, false);
+        }
+      } else if event.id == self.hotkeys.capture_window.id() {
+        if event.state == HotKeyState::Pressed {
+          self.start_capture(ctx, CaptureMode::Window, false);
+        }
+      } else if event.id == self.hotkeys.capture_last_region.id() {
+        if event.state == HotKeyState::Pressed {
+          self.start_capture(ctx, CaptureMode::LastRegion, false);
+        }
+      } else if event.id == self.hotkeys.queue_capture.id() {
+        if event.state == HotKeyState::Pressed {
+          self.start_capture(ctx, self.config.capture_mode, true);
+        }
+      } else if event.id == self.hotkeys.follow_up.id() {
+        if event.state == HotKeyState::Pressed {
+          self.toggle_follow_up();
+        }
       } else if event.id == self.hotkeys.close_response.id() {
         self.close_response();
+      } else if event.id == self.hotkeys.history_back.id() {
+        if event.state == HotKeyState::Pressed {
+          self.step_local_history(true);
+        }
+      } else if event.id == self.hotkeys.history_forward.id() {
+        if event.state == HotKeyState::Pressed {
+          self.step_local_history(false);
+        }
       } else if event.id == self.hotkeys.quit.id() {
         if event.state == HotKeyState::Pressed {
           self.quit_requested = true;
@@ -633,17 +868,34 @@ struct AppState {
   fn process_worker_results(&mut self) {
     while let Ok(result) = self.worker_rx.try_recv() {
       match result {
-        WorkerResult::Uploading(id) => {
+        WorkerResult::Thumbnail(id, png) => {
+          if Some(id) == self.current_request_id {
+            self.pending_thumbnail = Some((id, png));
+          }
+        }
+        WorkerResult::CaptureId(id, capture_id) => {
+          if Some(id) == self.current_request_id && !self.follow_up_pending {
+            self.capture_id = Some(capture_id);
+          }
+        }
+        WorkerResult::Uploading(id, sent, total) => {
           if Some(id) != self.current_request_id {
             continue;
           }
-          self.response_status = Some("Uploading...".to_string());
+          self.response_status = Some(upload_status(sent, total));
           self.loading = true;
         }
         WorkerResult::StreamDelta(id, delta) => {
           if Some(id) != self.current_request_id {
             continue;
           }
+          if self.follow_up_pending {
+            if let Some(follow_up) = self.follow_ups.last_mut() {
+              follow_up.answer.push_str(&delta);
+            }
+            self.response_scroll_offset = f32::MAX;
+            continue;
+          }
           if self.response.is_none() {
             self.response = Some(ApiResponse {
               text: String::new(),
@@ -660,6 +912,10 @@ struct AppState {
           if Some(id) != self.current_request_id {
             continue;
           }
+          if self.follow_up_pending {
+            self.finish_follow_up(Ok(response));
+            continue;
+          }
           let trimmed = response.code.trim();
           if trimmed.eq_ignore_ascii_case("rs")
             || trimmed.eq_ignore_ascii_case("rust")
@@ -668,37 +924,211 @@ struct AppState {
               response.code.clear();
             }
             self.loading = false;
+            self.remember_capture(id, &response);
             self.response = Some(response);
             self.last_error = None;
             self.response_status = Some("Ready".to_string());
+            self.refresh_account();
           }
         WorkerResult::Err(id, err) => {
           if Some(id) != self.current_request_id {
             continue;
           }
+          if self.follow_up_pending {
+            self.finish_follow_up(Err(err));
+            continue;
+          }
           self.loading = false;
           self.response = None;
           self.last_error = Some(err);
           self.response_status = Some("Error".to_string());
+          self.refresh_account();
+        }
+        WorkerResult::RateLimited(id, wait) => {
+          if Some(id) != self.current_request_id {
+            continue;
+          }
+          if self.follow_up_pending {
+            // Keep the answer on screen; the follow-up says when to try again.
+            let secs = wait.as_secs().max(1);
+            self.finish_follow_up(Err(format!("Rate limited. Try again in {secs}s.")));
+            continue;
+          }
+          self.loading = false;
+          self.response = None;
+          self.rate_limited_until = Some(std::time::Instant::now() + wait);
+          self.update_rate_limit_countdown();
+        }
+        WorkerResult::Queued(result) => {
+          self.queueing = false;
+          match result {
+            Ok(capture) => self.capture_queue.push(capture),
+            Err(err) => {
+              self.response_open = true;
+              self.response = None;
+              self.last_error = Some(err);
+              self.response_status = Some("Error".to_string());
+            }
+          }
+        }
+        WorkerResult::Models(result) => {
+          self.models_loading = false;
+          match result {
+            Ok(models) => self.models = models,
+            Err(err) => eprintln!("Failed to load models: {err}"),
+          }
+        }
+        WorkerResult::Linked(result) => {
+          self.linking = false;
+          match result {
+            Ok(linked) => {
+              self.config.api_key = linked.key;
+              self.save_config();
+              self.link_code.clear();
+              self.link_pin.clear();
+              self.link_status = Some(format!("Linked to {}", linked.email));
+              self.refresh_models();
+              self.settings_snapshot = None;
+              self.fetch_remote_settings();
+              self.account = None;
+              self.account_error = None;
+              self.refresh_account();
+            }
+            Err(err) => self.link_status = Some(err),
+          }
+        }
+        WorkerResult::Settings(result) => {
+          self.settings_syncing = false;
+          match result {
+            Ok(SettingsSync::Fetched(remote)) => self.merge_remote_settings(remote),
+            Ok(SettingsSync::Saved(remote)) => {
+              self.config.settings_updated_at = remote.updated_at;
+              self.save_config();
+            }
+            Err(err) => eprintln!("Settings sync failed: {err}"),
+          }
+        }
+        WorkerResult::Account(result) => {
+          self.account_loading = false;
+          match result {
+            Ok(account) => {
+              self.account = Some(account);
+              self.account_error = None;
+            }
+            Err(err) => {
+              eprintln!("Failed to load account: {err}");
+              self.account_error = Some(err);
+            }
+          }
+        }
+        WorkerResult::History(offset, result) => self.apply_history_page(offset, result),
+        WorkerResult::HistoryEntry(result) => {
+          if self.current_request_id.is_some() || !self.response_open {
+            continue;
+          }
+          self.loading = false;
+          match result {
+            Ok(entry) => {
+              self.last_error = entry.error;
+              self.capture_id = Some(entry.id);
+              self.follow_ups = entry
+                .follow_ups
+                .into_iter()
+                .map(|follow_up| follow_up::FollowUp {
+                  question: follow_up.question,
+                  answer: follow_up.answer.unwrap_or_default(),
+                  error: None,
+                })
+                .collect();
+              self.response = Some(ApiResponse {
+                text: entry.text,
+                code: entry.code,
+              });
+              self.response_status = Some("From history".to_string());
+            }
+            Err(err) => {
+              self.last_error = Some(err);
+              self.response_status = Some("Error".to_string());
+            }
+          }
         }
       }
     }
   }
 
-  fn start_capture(&mut self, ctx: &egui::Context) {
-    if self.loading {
+  /// Captures in `mode` and sends it with the queue, or only adds it to the queue when `queue`.
+  fn start_capture(&mut self, ctx: &egui::Context, mode: CaptureMode, queue: bool) {
+    if self.loading || self.queueing || self.region_overlay.is_some() {
+      return;
+    }
+    // Leave room for the capture that sends the queue.
+    if queue && self.capture_queue.len() + 1 >= MAX_IMAGES {
+      return;
+    }
+    if self
+      .rate_limited_until
+      .is_some_and(|until| until > std::time::Instant::now())
+    {
+      self.response_open = true;
+      self.response = None;
+      self.update_rate_limit_countdown();
       return;
     }
+    self.rate_limited_until = None;
     self.update_last_screen_point(ctx);
+    let target = match (mode, self.config.last_region) {
+      (CaptureMode::Screen, _) => CaptureTarget::Screen(self.screen_pick()),
+      (CaptureMode::Window, _) => CaptureTarget::Window(self.screen_pick()),
+      (CaptureMode::AllScreens, _) => CaptureTarget::AllScreens,
+      (CaptureMode::LastRegion, Some(region)) => CaptureTarget::Region(region),
+      (CaptureMode::Region | CaptureMode::LastRegion, _) => {
+        self.open_region_overlay(queue);
+        return;
+      }
+    };
+    if queue {
+      self.queue_capture(target, std::time::Duration::ZERO);
+    } else {
+      self.start_upload(Some(target), std::time::Duration::ZERO);
+    }
+  }
+
+  /// Captures `target` once `settle` has passed and adds it to the queue.
+  fn queue_capture(&mut self, target: CaptureTarget, settle: std::time::Duration) {
+    self.queueing = true;
+    let tx = self.worker_tx.clone();
+    let upload = self.config.upload;
+    std::thread::spawn(move || {
+      std::thread::sleep(settle);
+      let _ = tx.send(WorkerResult::Queued(capture_encoded(target, upload)));
+    });
+  }
+
+  fn screen_pick(&self) -> ScreenPick {
+    ScreenPick {
+      pinned: self.config.monitor,
+      point: self.last_screen_point,
+    }
+  }
+
+  /// Sends the queue and `target` to the server once `settle` has passed, e.g. for an overlay to
+  /// disappear.
+  fn start_upload(&mut self, target: Option<CaptureTarget>, settle: std::time::Duration) {
+    let queued = std::mem::take(&mut self.capture_queue);
+    self.pending_prompt = std::mem::take(&mut self.prompt).trim().to_string();
+    self.prompt_open = false;
     self.loading = true;
     let request_id = self.next_request_id;
     self.next_request_id = self.next_request_id.wrapping_add(1).max(1);
     self.current_request_id = Some(request_id);
+    self.history_cursor = None;
+    self.pending_thumbnail = None;
     self.response_open = true;
     self.response_hwnd_hooked = false;
     self.response_last_pos = None;
     self.response = None;
     self.last_error = None;
+    self.clear_follow_ups();
     self.response_status = Some("Capturing...".to_string());
     self.response_size.y = Self::RESPONSE_MIN_HEIGHT;
     self.response_scroll_offset = 0.0;
@@ -713,14 +1143,164 @@ struct AppState {
       .to_string();
     let model = self.config.model.trim().to_string();
     let tx = self.worker_tx.clone();
-    let capture_point = self.last_screen_point;
+    let upload = self.config.upload;
+    let prompt = Some(self.pending_prompt.clone()).filter(|prompt| !prompt.is_empty());
     std::thread::spawn(move || {
+      std::thread::sleep(settle);
       let token = if auth_token.is_empty() { None } else { Some(auth_token) };
       let model = if model.is_empty() { None } else { Some(model) };
-      capture_and_upload(&api_url, &tx, capture_point, token, model, request_id);
+      capture_and_upload(&api_url, &tx, target, queued, prompt, token, model, upload, request_id);
+    });
+  }
+
+  fn refresh_models(&mut self) {
+    if self.models_loading {
+      return;
+    }
+    self.models_loading = true;
+    let api_url = self.api_url.clone();
+    let auth_token = self.config.api_key.trim().to_string();
+    let tx = self.worker_tx.clone();
+    std::thread::spawn(move || {
+      let token = if auth_token.is_empty() { None } else { Some(auth_token) };
+      let _ = tx.send(WorkerResult::Models(fetch_models(&api_url, token.as_deref())));
+    });
+  }
+
+  fn start_link(&mut self) {
+    if self.linking || self.link_code.trim().is_empty() || self.link_pin.trim().is_empty() {
+      return;
+    }
+    self.linking = true;
+    self.link_status = Some("Linking...".to_string());
+    let api_url = self.api_url.clone();
+    let code = self.link_code.clone();
+    let pin = self.link_pin.clone();
+    let tx = self.worker_tx.clone();
+    std::thread::spawn(move || {
+      let _ = tx.send(WorkerResult::Linked(link_device(&api_url, &code, &pin)));
     });
   }
 
+  fn refresh_account(&mut self) {
+    let auth_token = self.config.api_key.trim().to_string();
+    if auth_token.is_empty() || self.account_loading {
+      return;
+    }
+    self.account_loading = true;
+    self.last_account_fetch = std::time::Instant::now();
+    let api_url = self.api_url.clone();
+    let tx = self.worker_tx.clone();
+    std::thread::spawn(move || {
+      let _ = tx.send(WorkerResult::Account(fetch_account(&api_url, &auth_token)));
+    });
+  }
+
+  fn set_store_images(&mut self, store_images: bool) {
+    let auth_token = self.config.api_key.trim().to_string();
+    if auth_token.is_empty() {
+      return;
+    }
+    self.account_loading = true;
+    let api_url = self.api_url.clone();
+    let tx = self.worker_tx.clone();
+    std::thread::spawn(move || {
+      let result = update_store_images(&api_url, &auth_token, store_images);
+      let _ = tx.send(WorkerResult::Account(result));
+    });
+  }
+
+  /// Rewrites the rate-limit error with the seconds left; clears it once the wait is over.
+  fn update_rate_limit_countdown(&mut self) {
+    let Some(until) = self.rate_limited_until else {
+      return;
+    };
+    let remaining = until.saturating_duration_since(std::time::Instant::now());
+    if remaining.is_zero() {
+      self.rate_limited_until = None;
+      if self.response.is_none() && !self.loading {
+        self.last_error = Some("Rate limit lifted. You can capture again.".to_string());
+        self.response_status = Some("Ready".to_string());
+      }
+      return;
+    }
+    let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+    self.last_error = Some(format!(
+      "Rate limit reached for your package. Try again in {seconds}s."
+    ));
+    self.response_status = Some("Rate limited".to_string());
+  }
+
+  fn fetch_remote_settings(&mut self) {
+    let auth_token = self.config.api_key.trim().to_string();
+    if !self.config.sync_settings || auth_token.is_empty() || self.settings_syncing {
+      return;
+    }
+    self.settings_syncing = true;
+    let api_url = self.api_url.clone();
+    let tx = self.worker_tx.clone();
+    std::thread::spawn(move || {
+      let result = fetch_settings(&api_url, &auth_token).map(SettingsSync::Fetched);
+      let _ = tx.send(WorkerResult::Settings(result));
+    });
+  }
+
+  fn push_settings(&mut self) {
+    let auth_token = self.config.api_key.trim().to_string();
+    if auth_token.is_empty() {
+      return;
+    }
+    self.settings_syncing = true;
+    let api_url = self.api_url.clone();
+    let config = synced_settings(&self.config);
+    let updated_at = self.config.settings_updated_at;
+    let tx = self.worker_tx.clone();
+    std::thread::spawn(move || {
+      let result = put_settings(&api_url, &auth_token, &config, updated_at);
+      let _ = tx.send(WorkerResult::Settings(result));
+    });
+  }
+
+  /// Newer side wins: remote settings replace the synced fields, or ours get uploaded.
+  fn merge_remote_settings(&mut self, remote: RemoteSettings) {
+    let remote_is_newer = match (remote.updated_at, self.config.settings_updated_at) {
+      (Some(remote_at), Some(local_at)) => remote_at > local_at,
+      (Some(_), None) => true,
+      (None, _) => false,
+    };
+    if remote_is_newer {
+      self.config = apply_synced_settings(&self.config, &remote.config);
+      self.config.settings_updated_at = remote.updated_at;
+      self.save_config();
+      self.apply_hotkeys_from_config();
+    } else if remote.updated_at.is_none() || remote.updated_at != self.config.settings_updated_at {
+      self.push_settings();
+    }
+    self.settings_snapshot = Some(synced_settings(&self.config));
+  }
+
+  /// Uploads local changes to synced fields, checked a couple of times per second.
+  fn sync_settings_if_changed(&mut self) {
+    if !self.config.sync_settings || self.settings_syncing {
+      return;
+    }
+    if self.last_settings_check.elapsed().as_millis() < 500 {
+      return;
+    }
+    self.last_settings_check = std::time::Instant::now();
+    let Some(snapshot) = &self.settings_snapshot else {
+      return;
+    };
+    let current = synced_settings(&self.config);
+    if &current == snapshot {
+      return;
+    }
+    self.config.settings_updated_at = Some(chrono::Utc::now());
+    self.save_config();
+    self.settings_snapshot = Some(current);
+    self.push_settings();
+  }
+
   fn close_response(&mut self) {
     self.response_open = false;
     self.loading = false;
@@ -732,6 +1312,7 @@ struct AppState {
     self.response_scroll_offset = 0.0;
     self.response_scroll_max = 0.0;
     self.current_request_id = None;
+    self.clear_follow_ups();
   }
 
   fn save_config(&self) {
@@ -790,7 +1371,9 @@ struct AppState {
       self.main_visible = desired;
       if !self.main_visible {
         self.settings_open = false;
+        self.history_open = false;
         self.hotkey_capture = None;
+        self.region_overlay = None;
       }
       if self.main_visible {
         self.main_fade = 0.0;
@@ -835,6 +1418,7 @@ struct AppState {
     ui.add(egui::Label::new(text).selectable(false));
   }
 
+  #[allow(clippy::too_many_arguments)]
   fn icon_badge(
     &self,
     ui: &mut egui::Ui,
@@ -905,6 +1489,116 @@ struct AppState {
     ui.add_space(-3.0);
   }
 
+  /// Remaining credits, highlighted once they drop below `low_credit_warning`. Before the
+  /// first successful `/me` a failed one shows as a warning carrying the server's message.
+  fn credits_label(&self, ui: &mut egui::Ui, icon_size: f32) {
+    let Some(account) = &self.account else {
+      if let Some(err) = &self.account_error {
+        let color = Self::fade_color(Self::LOW_CREDIT_COLOR, self.main_fade);
+        let icon = egui::RichText::new(phosphor::regular::WARNING)
+          .size(icon_size)
+          .strong()
+          .color(color);
+        ui.add(egui::Label::new(icon).selectable(false))
+          .on_hover_text(err);
+      }
+      return;
+    };
+    let low = account.credits < self.config.low_credit_warning;
+    let color = if low {
+      Some(Self::fade_color(Self::LOW_CREDIT_COLOR, self.main_fade))
+    } else {
+      None
+    };
+    let tint = |text: egui::RichText| match color {
+      Some(color) => text.color(color),
+      None => text,
+    };
+    let icon = if low {
+      phosphor::regular::WARNING
+    } else {
+      phosphor::regular::COINS
+    };
+    let response = ui
+      .horizontal(|ui| {
+        ui.spacing_mut().item_spacing.x = 4.0;
+        let icon = egui::RichText::new(icon).size(icon_size).strong();
+        Self::main_label(ui, tint(icon).into());
+        let text = egui::RichText::new(account.credits.to_string())
+          .strong()
+          .extra_letter_spacing(Self::MAIN_LETTER_SPACING);
+        Self::main_label(ui, tint(text).into());
+      })
+      .response;
+    let package = account.package.as_deref().unwrap_or("No package");
+    let mut hover = match account.credits {
+      credits if credits <= 0 => format!("{package}: no credits left"),
+      credits if low => format!("{package}: only {credits} credits left"),
+      credits => format!("{package}: {credits} credits"),
+    };
+    if let Some(expires_at) = account.expires_at {
+      hover.push_str(&format!(
+        "\nRenews or expires {}",
+        expires_at.with_timezone(&chrono::Local).format("%Y-%m-%d")
+      ));
+    }
+    if let Some(err) = &self.account_error {
+      hover.push_str(&format!("\n{err}"));
+    }
+    response.on_hover_text(hover);
+  }
+
+  /// A badge that opens a text box for a note to send with the next capture, e.g. "focus on
+  /// the error in the terminal". Enter keeps the note, Escape drops it.
+  fn prompt_row(&mut self, ui: &mut egui::Ui, icon_size: f32) {
+    let note = self
+      .icon_badge(ui, phosphor::regular::NOTE_PENCIL, icon_size + 2.0, 2.0, 0.0, true, true)
+      .on_hover_text("Add a note to the next capture");
+    if note.clicked() {
+      self.prompt_open = !self.prompt_open;
+      self.prompt_focused = false;
+    }
+    if !self.prompt_open && self.prompt.is_empty() {
+      return;
+    }
+    let edit = ui.add(
+      egui::TextEdit::singleline(&mut self.prompt)
+        .hint_text("Note for the next capture")
+        .char_limit(MAX_PROMPT_CHARS)
+        .desired_width(200.0),
+    );
+    if self.prompt_open && !self.prompt_focused {
+      edit.request_focus();
+      self.prompt_focused = true;
+    }
+    if edit.lost_focus() {
+      self.prompt_open = false;
+      if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+        self.prompt.clear();
+      }
+    }
+  }
+
+  /// Size of the capture queue, with badges to send it on its own or drop it.
+  fn queue_row(&mut self, ui: &mut egui::Ui, icon_size: f32) {
+    Self::main_label(ui, self.main_icon(phosphor::regular::STACK, icon_size));
+    ui.add_space(-4.0);
+    let count = self.capture_queue.len() + usize::from(self.queueing);
+    Self::main_label(ui, self.main_text(format!("{count} queued")));
+    let send = self
+      .icon_badge(ui, phosphor::regular::PAPER_PLANE_RIGHT, icon_size + 2.0, 2.0, 0.0, true, true)
+      .on_hover_text("Send queued captures");
+    if send.clicked() && !self.loading && !self.queueing && !self.capture_queue.is_empty() {
+      self.start_upload(None, std::time::Duration::ZERO);
+    }
+    let clear = self
+      .icon_badge(ui, phosphor::regular::TRASH, icon_size + 2.0, 2.0, 0.0, true, true)
+      .on_hover_text("Clear queue");
+    if clear.clicked() {
+      self.capture_queue.clear();
+    }
+  }
+
   fn show_main_window(&mut self, ctx: &egui::Context) {
     #[cfg(target_os = "windows")]
     if !self.main_hwnd_hooked {
@@ -973,9 +1667,36 @@ struct AppState {
             self.text_badge(ui, &shot_label, 3.0, 2.0, false);
             Self::main_label(ui, self.main_icon(phosphor::regular::CAMERA, icon_size));
             Self::main_label(ui, self.main_text("Take screenshot"));
+            self.prompt_row(ui, icon_size);
+            if !self.capture_queue.is_empty() || self.queueing {
+              draw_vertical_divider(ui, 1.5, self.divider_color(), 2.0);
+              self.queue_row(ui, icon_size);
+            }
+            if self.account.is_some() || self.account_error.is_some() {
+              draw_vertical_divider(ui, 1.5, self.divider_color(), 2.0);
+              self.credits_label(ui, icon_size);
+            }
 
             ui.add_space(1.0);
             draw_vertical_divider(ui, 1.5, self.divider_color(), 2.0);
+            let history_resp = self.icon_badge(
+              ui,
+              phosphor::regular::CLOCK_COUNTER_CLOCKWISE,
+              icon_size + 2.0,
+              2.0,
+              0.0,
+              true,
+              true,
+            )
+            .on_hover_text("History");
+            if history_resp.clicked() {
+              self.history_open = !self.history_open;
+              if self.history_open && !self.history_local {
+                self.load_history(false);
+              } else {
+                self.history_hwnd_hooked = false;
+              }
+            }
             let settings_resp = self.icon_badge(
               ui,
               phosphor::regular::GEAR,
@@ -989,7 +1710,10 @@ struct AppState {
             let clicked = settings_resp.clicked();
             if clicked {
               self.settings_open = !self.settings_open;
-              if !self.settings_open {
+              if self.settings_open {
+                self.refresh_models();
+                self.monitors = list_monitors();
+              } else {
                 self.settings_hwnd_hooked = false;
               }
             }
@@ -1046,6 +1770,14 @@ impl eframe::App for AppState {
     self.process_hotkeys(ctx);
     self.process_hotkey_capture(ctx);
     self.process_worker_results();
+    if self.rate_limited_until.is_some() && self.response_open {
+      self.update_rate_limit_countdown();
+      ctx.request_repaint_after(std::time::Duration::from_millis(250));
+    }
+    self.sync_settings_if_changed();
+    if self.last_account_fetch.elapsed() >= Self::ACCOUNT_REFRESH {
+      self.refresh_account();
+    }
     self.sync_visibility(ctx);
     if self.response_open {
       let delta = ctx.input(|i| i.raw_scroll_delta.y);
@@ -1065,7 +1797,9 @@ impl eframe::App for AppState {
       self.update_last_screen_point(ctx);
       self.show_main_window(ctx);
       self.show_settings_window(ctx);
+      self.show_history_window(ctx);
       self.show_response_window(ctx);
+      self.show_region_overlay(ctx);
       self.maybe_save_position(ctx);
     }
 
@@ -0,0 +1,143 @@
+use eframe::egui;
+use egui_commonmark::CommonMarkViewer;
+
+use crate::api::{ApiResponse, ask_follow_up};
+
+use super::AppState;
+
+/// Longest question sent, in characters; the server takes up to 4000.
+const MAX_QUESTION_CHARS: usize = 4_000;
+
+/// A question asked about the answer on screen and its answer as far as it streamed.
+pub(super) struct FollowUp {
+  pub(super) question: String,
+  pub(super) answer: String,
+  pub(super) error: Option<String>,
+}
+
+impl AppState {
+  /// Opens or closes the question box under the answer on screen.
+  pub(super) fn toggle_follow_up(&mut self) {
+    if self.follow_up_active {
+      self.follow_up_active = false;
+      return;
+    }
+    if !self.response_open || self.loading || self.response.is_none() || self.capture_id.is_none()
+    {
+      return;
+    }
+    self.follow_up_active = true;
+    self.follow_up_focused = false;
+  }
+
+  /// Forgets the thread of the answer on screen, e.g. before another one is shown.
+  pub(super) fn clear_follow_ups(&mut self) {
+    self.capture_id = None;
+    self.follow_ups.clear();
+    self.follow_up_active = false;
+    self.follow_up_pending = false;
+  }
+
+  fn send_follow_up(&mut self) {
+    let question: String = self
+      .follow_up_input
+      .trim()
+      .chars()
+      .take(MAX_QUESTION_CHARS)
+      .collect();
+    let Some(capture_id) = self.capture_id.clone() else {
+      return;
+    };
+    if question.is_empty() || self.loading {
+      return;
+    }
+    self.follow_up_input.clear();
+    self.follow_up_active = false;
+    self.follow_ups.push(FollowUp {
+      question: question.clone(),
+      answer: String::new(),
+      error: None,
+    });
+    self.loading = true;
+    self.follow_up_pending = true;
+    let request_id = self.next_request_id;
+    self.next_request_id = self.next_request_id.wrapping_add(1).max(1);
+    self.current_request_id = Some(request_id);
+    self.response_status = Some("Asking...".to_string());
+    self.response_scroll_offset = f32::MAX;
+
+    let api_url = self.api_url.clone();
+    let auth_token = self.config.api_key.trim().to_string();
+    let model = self.config.model.trim().to_string();
+    let tx = self.worker_tx.clone();
+    std::thread::spawn(move || {
+      let token = if auth_token.is_empty() { None } else { Some(auth_token) };
+      let model = if model.is_empty() { None } else { Some(model) };
+      ask_follow_up(&api_url, &tx, token, model, &capture_id, &question, request_id);
+    });
+  }
+
+  /// Settles the follow-up in flight with the final answer or the reason it failed.
+  pub(super) fn finish_follow_up(&mut self, result: Result<ApiResponse, String>) {
+    self.loading = false;
+    self.follow_up_pending = false;
+    let Some(follow_up) = self.follow_ups.last_mut() else {
+      return;
+    };
+    match result {
+      Ok(response) => {
+        follow_up.answer = response.text;
+        self.response_status = Some("Ready".to_string());
+      }
+      Err(err) => {
+        follow_up.error = Some(err);
+        self.response_status = Some("Error".to_string());
+      }
+    }
+    self.response_scroll_offset = f32::MAX;
+    self.refresh_account();
+  }
+
+  /// The questions asked so far and their answers, below the first answer.
+  pub(super) fn show_follow_ups(&mut self, ui: &mut egui::Ui) {
+    for (index, follow_up) in self.follow_ups.iter().enumerate() {
+      ui.add_space(10.0);
+      ui.separator();
+      ui.add_space(6.0);
+      ui.label(egui::RichText::new(&follow_up.question).strong());
+      ui.add_space(4.0);
+      if let Some(err) = &follow_up.error {
+        ui.label(egui::RichText::new(err).color(egui::Color32::from_rgb(255, 150, 150)));
+      } else if !follow_up.answer.is_empty() {
+        CommonMarkViewer::new(format!("follow_up_markdown_{index}"))
+          .syntax_theme_dark("base16-ocean.dark")
+          .syntax_theme_light("base16-ocean.light")
+          .show(ui, &mut self.markdown_cache, &follow_up.answer);
+      }
+    }
+  }
+
+  /// The question box while it is open; Enter sends, Escape closes it.
+  pub(super) fn show_follow_up_input(&mut self, ui: &mut egui::Ui) {
+    if !self.follow_up_active {
+      return;
+    }
+    ui.add_space(8.0);
+    let edit = ui.add(
+      egui::TextEdit::singleline(&mut self.follow_up_input)
+        .hint_text("Ask a follow-up, e.g. \"explain step 3\"")
+        .desired_width(f32::INFINITY),
+    );
+    if !self.follow_up_focused {
+      ui.ctx().send_viewport_cmd(egui::ViewportCommand::Focus);
+      edit.request_focus();
+      self.follow_up_focused = true;
+    }
+    let (enter, escape) = ui.input(|i| (i.key_pressed(egui::Key::Enter), i.key_pressed(egui::Key::Escape)));
+    if escape {
+      self.follow_up_active = false;
+    } else if enter && edit.lost_focus() {
+      self.send_follow_up();
+    }
+  }
+}
@@ -0,0 +1,416 @@
+use eframe::egui;
+
+use crate::api::{ApiResponse, HistoryItem, WorkerResult, fetch_history, fetch_history_entry};
+use crate::local_history::LocalEntry;
+
+use super::AppState;
+
+/// Entries fetched per page of the history window.
+const HISTORY_PAGE: usize = 20;
+const THUMBNAIL_SIZE: egui::Vec2 = egui::vec2(96.0, 54.0);
+
+impl AppState {
+  /// Loads the first page again, or the next one when `more` is set.
+  pub(super) fn load_history(&mut self, more: bool) {
+    let auth_token = self.config.api_key.trim().to_string();
+    if auth_token.is_empty() {
+      self.history_status = Some("Add an API key or link this device first.".to_string());
+      return;
+    }
+    if self.history_loading {
+      return;
+    }
+    self.history_loading = true;
+    self.history_status = None;
+    let offset = if more { self.history.len() } else { 0 };
+    let filter = self.history_filter.map(str::to_string);
+    let api_url = self.api_url.clone();
+    let tx = self.worker_tx.clone();
+    std::thread::spawn(move || {
+      let result = fetch_history(&api_url, &auth_token, filter.as_deref(), offset, HISTORY_PAGE);
+      let _ = tx.send(WorkerResult::History(offset, result));
+    });
+  }
+
+  /// Shows a past answer in the response window; reading history costs no credits.
+  fn open_history_entry(&mut self, id: &str) {
+    let auth_token = self.config.api_key.trim().to_string();
+    if self.loading || auth_token.is_empty() {
+      return;
+    }
+    self.reset_response_window();
+    self.loading = true;
+    self.response_status = Some("Loading...".to_string());
+    let api_url = self.api_url.clone();
+    let id = id.to_string();
+    let tx = self.worker_tx.clone();
+    std::thread::spawn(move || {
+      let _ = tx.send(WorkerResult::HistoryEntry(fetch_history_entry(
+        &api_url,
+        &auth_token,
+        &id,
+      )));
+    });
+  }
+
+  fn reset_response_window(&mut self) {
+    self.current_request_id = None;
+    self.history_cursor = None;
+    self.response_open = true;
+    self.response_hwnd_hooked = false;
+    self.response_last_pos = None;
+    self.response = None;
+    self.last_error = None;
+    self.response_size.y = Self::RESPONSE_MIN_HEIGHT;
+    self.response_scroll_offset = 0.0;
+    self.response_scroll_max = 0.0;
+    self.clear_follow_ups();
+  }
+
+  /// Adds a finished capture to the local history; it is then the newest entry shown.
+  pub(super) fn remember_capture(&mut self, request_id: u64, response: &ApiResponse) {
+    let thumbnail = self
+      .pending_thumbnail
+      .take()
+      .filter(|(id, _)| *id == request_id)
+      .map(|(_, png)| png);
+    let limit = self.config.local_history_limit;
+    if limit == 0 {
+      return;
+    }
+    let created_at = chrono::Utc::now();
+    let entry = LocalEntry {
+      id: created_at.format("%Y%m%d%H%M%S%3f").to_string(),
+      created_at,
+      model: self.config.model.trim().to_string(),
+      prompt: std::mem::take(&mut self.pending_prompt),
+      text: response.text.clone(),
+      code: response.code.clone(),
+      thumbnail: None,
+      capture_id: self.capture_id.clone(),
+    };
+    self.local_matches = None;
+    match self.local_history.add(entry, thumbnail.as_deref(), limit) {
+      Ok(()) => self.history_cursor = Some(0),
+      Err(err) => eprintln!("Failed to save local history: {err}"),
+    }
+  }
+
+  /// Moves through the local history from the answer on screen; `older` steps back.
+  pub(super) fn step_local_history(&mut self, older: bool) {
+    if self.loading || self.local_history.is_empty() {
+      return;
+    }
+    let last = self.local_history.len() - 1;
+    let index = match (self.history_cursor, older) {
+      (None, _) => 0,
+      (Some(index), true) => (index + 1).min(last),
+      (Some(index), false) => index.saturating_sub(1),
+    };
+    self.show_local_entry(index);
+  }
+
+  fn show_local_entry(&mut self, index: usize) {
+    let Some(entry) = self.local_history.get(index) else {
+      return;
+    };
+    let response = ApiResponse {
+      text: entry.text.clone(),
+      code: entry.code.clone(),
+    };
+    let capture_id = entry.capture_id.clone();
+    let status = format!(
+      "History {}/{}, {}",
+      index + 1,
+      self.local_history.len(),
+      entry
+        .created_at
+        .with_timezone(&chrono::Local)
+        .format("%Y-%m-%d %H:%M")
+    );
+    self.reset_response_window();
+    self.response = Some(response);
+    self.response_status = Some(status);
+    self.history_cursor = Some(index);
+    self.capture_id = capture_id;
+  }
+
+  fn thumbnail_texture(&mut self, ctx: &egui::Context, entry: &LocalEntry) -> Option<egui::TextureId> {
+    if let Some(texture) = self.thumbnail_textures.get(&entry.id) {
+      return Some(texture.id());
+    }
+    let bytes = std::fs::read(self.local_history.thumbnail_path(entry)?).ok()?;
+    let image = image::load_from_memory(&bytes).ok()?.to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+    let color_image = egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw());
+    let texture = ctx.load_texture(
+      format!("history-{}", entry.id),
+      color_image,
+      egui::TextureOptions::LINEAR,
+    );
+    let id = texture.id();
+    self.thumbnail_textures.insert(entry.id.clone(), texture);
+    Some(id)
+  }
+
+  fn show_local_list(&mut self, ui: &mut egui::Ui) {
+    ui.add(
+      egui::TextEdit::singleline(&mut self.history_query)
+        .hint_text("Search this device")
+        .desired_width(ui.available_width()),
+    );
+    ui.add_space(6.0);
+    let matches = match &self.local_matches {
+      Some((query, matches)) if *query == self.history_query => matches.clone(),
+      _ => {
+        let matches = self.local_history.search(&self.history_query);
+        self.local_matches = Some((self.history_query.clone(), matches.clone()));
+        matches
+      }
+    };
+    let mut open = None;
+    egui::ScrollArea::vertical()
+      .auto_shrink([false, false])
+      .show(ui, |ui| {
+        if matches.is_empty() {
+          ui.label(if self.local_history.is_empty() {
+            "No captures on this device yet."
+          } else {
+            "Nothing matches."
+          });
+        }
+        for index in matches {
+          let Some(entry) = self.local_history.get(index).cloned() else {
+            continue;
+          };
+          let texture = self.thumbnail_texture(ui.ctx(), &entry);
+          let when = entry
+            .created_at
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string();
+          let preview: String = entry
+            .text
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .chars()
+            .take(120)
+            .collect();
+          let selected = self.history_cursor == Some(index) && self.response_open;
+          let response = egui::Frame::none()
+            .stroke(egui::Stroke::new(
+              if selected { 2.0 } else { 1.0 },
+              self.button_border(),
+            ))
+            .rounding(egui::Rounding::same(4.0))
+            .inner_margin(egui::Margin::same(6.0))
+            .show(ui, |ui| {
+              ui.set_width(ui.available_width());
+              ui.horizontal_top(|ui| {
+                match texture {
+                  Some(texture) => {
+                    ui.add(egui::Image::new((texture, THUMBNAIL_SIZE)).fit_to_exact_size(THUMBNAIL_SIZE));
+                  }
+                  None => {
+                    ui.allocate_exact_size(THUMBNAIL_SIZE, egui::Sense::hover());
+                  }
+                }
+                ui.vertical(|ui| {
+                  ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(when).strong());
+                    if !entry.model.is_empty() {
+                      ui.label(egui::RichText::new(&entry.model).small());
+                    }
+                  });
+                  if !entry.prompt.is_empty() {
+                    ui.label(egui::RichText::new(&entry.prompt).italics().small());
+                  }
+                  ui.add(egui::Label::new(preview).wrap(true).selectable(false));
+                });
+              });
+            })
+            .response
+            .interact(egui::Sense::click())
+            .on_hover_cursor(egui::CursorIcon::PointingHand);
+          if response.clicked() {
+            open = Some(index);
+          }
+          ui.add_space(4.0);
+        }
+      });
+    if let Some(index) = open {
+      if !self.loading {
+        self.show_local_entry(index);
+      }
+    }
+  }
+
+  pub(super) fn show_history_window(&mut self, ctx: &egui::Context) {
+    if !self.history_open {
+      return;
+    }
+
+    let viewport = egui::ViewportBuilder::default()
+      .with_title("History")
+      .with_inner_size([380.0, 460.0])
+      .with_transparent(true)
+      .with_taskbar(false);
+    let viewport = if self.config.always_on_top {
+      viewport.with_always_on_top()
+    } else {
+      viewport
+    };
+
+    ctx.show_viewport_immediate(
+      egui::ViewportId::from_hash_of("history"),
+      viewport,
+      |ctx, _class| {
+        if ctx.input(|i| i.viewport().close_requested()) {
+          self.history_open = false;
+          self.history_hwnd_hooked = false;
+          ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+          return;
+        }
+
+        #[cfg(target_os = "windows")]
+        {
+          use windows::Win32::Foundation::HWND;
+          if let Some(hwnd) = Self::find_window_by_title("History") {
+            if !self.history_hwnd_hooked {
+              Self::apply_windows_tool_window(HWND(hwnd.0));
+              self.history_hwnd_hooked = true;
+            }
+            Self::apply_windows_exclude_from_capture(HWND(hwnd.0), self.config.stealth);
+          }
+        }
+
+        let frame = egui::Frame::none()
+          .fill(self.background_color())
+          .stroke(egui::Stroke::new(1.0, self.border_color()))
+          .inner_margin(egui::Margin::symmetric(10.0, 10.0));
+
+        egui::CentralPanel::default().frame(frame).show(ctx, |ui| {
+          ui.visuals_mut().override_text_color = Some(self.text_color());
+
+          ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.history_local, true, "This device");
+            let account = ui.selectable_value(&mut self.history_local, false, "Account");
+            if account.clicked() && self.history.is_empty() {
+              self.load_history(false);
+            }
+          });
+          ui.add_space(6.0);
+          if self.history_local {
+            self.show_local_list(ui);
+            return;
+          }
+
+          ui.horizontal(|ui| {
+            let mut filter = self.history_filter;
+            ui.add_enabled_ui(!self.history_loading, |ui| {
+              egui::ComboBox::from_id_source("history_filter")
+                .selected_text(match filter {
+                  Some("DONE") => "Answers",
+                  Some(_) => "Errors",
+                  None => "All",
+                })
+                .show_ui(ui, |ui| {
+                  ui.selectable_value(&mut filter, None, "All");
+                  ui.selectable_value(&mut filter, Some("DONE"), "Answers");
+                  ui.selectable_value(&mut filter, Some("ERROR"), "Errors");
+                });
+            });
+            if filter != self.history_filter {
+              self.history_filter = filter;
+              self.load_history(false);
+            }
+            let refresh = ui.add_enabled(!self.history_loading, egui::Button::new("Refresh"));
+            if refresh.clicked() {
+              self.load_history(false);
+            }
+            if self.history_loading {
+              ui.spinner();
+            }
+          });
+          if let Some(status) = &self.history_status {
+            ui.label(egui::RichText::new(status).small());
+          }
+          ui.add_space(6.0);
+
+          let mut open = None;
+          egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+              if self.history.is_empty() && !self.history_loading {
+                ui.label("No captures yet.");
+              }
+              for item in &self.history {
+                let when = item.created_at.map_or_else(String::new, |at| {
+                  at.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+                });
+                let response = egui::Frame::none()
+                  .stroke(egui::Stroke::new(1.0, self.button_border()))
+                  .rounding(egui::Rounding::same(4.0))
+                  .inner_margin(egui::Margin::same(6.0))
+                  .show(ui, |ui| {
+                    ui.set_width(ui.available_width());
+                    ui.horizontal(|ui| {
+                      ui.label(egui::RichText::new(when).strong());
+                      if let Some(model) = &item.model {
+                        ui.label(egui::RichText::new(model).small());
+                      }
+                      if item.status != "DONE" {
+                        ui.label(
+                          egui::RichText::new(item.status.to_lowercase())
+                            .small()
+                            .color(Self::LOW_CREDIT_COLOR),
+                        );
+                      }
+                    });
+                    ui.add(egui::Label::new(&item.preview).wrap(true).selectable(false));
+                  })
+                  .response
+                  .interact(egui::Sense::click())
+                  .on_hover_cursor(egui::CursorIcon::PointingHand);
+                if response.clicked() {
+                  open = Some(item.id.clone());
+                }
+                ui.add_space(4.0);
+              }
+              if self.history_more
+                && ui
+                  .add_enabled(!self.history_loading, egui::Button::new("Load more"))
+                  .clicked()
+              {
+                self.load_history(true);
+              }
+            });
+          if let Some(id) = open {
+            self.open_history_entry(&id);
+          }
+        });
+      },
+    );
+  }
+
+  pub(super) fn apply_history_page(
+    &mut self,
+    offset: usize,
+    result: Result<Vec<HistoryItem>, String>,
+  ) {
+    self.history_loading = false;
+    match result {
+      Ok(items) => {
+        if offset == 0 {
+          self.history.clear();
+        }
+        self.history_more = items.len() == HISTORY_PAGE;
+        self.history.extend(items);
+      }
+      Err(err) => self.history_status = Some(err),
+    }
+  }
+}
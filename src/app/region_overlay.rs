@@ -0,0 +1,184 @@
+use eframe::egui;
+
+use crate::api::CaptureTarget;
+use crate::config::CaptureRegion;
+
+use super::AppState;
+
+/// Time for the overlay to disappear before the region is captured.
+const OVERLAY_SETTLE: std::time::Duration = std::time::Duration::from_millis(150);
+/// Drags smaller than this, in screen units, are ignored.
+const MIN_REGION: u32 = 8;
+const OVERLAY_TITLE: &str = "Faux Region";
+
+/// The screen being dimmed and the rectangle dragged on it so far, in overlay points.
+pub(super) struct RegionOverlay {
+  screen_x: i32,
+  screen_y: i32,
+  width: u32,
+  height: u32,
+  drag_start: Option<egui::Pos2>,
+  drag_current: Option<egui::Pos2>,
+  /// Focused once so Escape reaches it.
+  focused: bool,
+  /// Whether the selection goes to the capture queue instead of being sent.
+  queue: bool,
+  #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+  hwnd_hooked: bool,
+}
+
+impl AppState {
+  /// Covers the pinned screen, or the one the main bar is on, with an overlay to drag a
+  /// region on.
+  pub(super) fn open_region_overlay(&mut self, queue: bool) {
+    match self.screen_pick().screen() {
+      Ok(screen) => {
+        let info = screen.display_info;
+        self.region_overlay = Some(RegionOverlay {
+          screen_x: info.x,
+          screen_y: info.y,
+          width: info.width,
+          height: info.height,
+          drag_start: None,
+          drag_current: None,
+          focused: false,
+          queue,
+          hwnd_hooked: false,
+        });
+      }
+      Err(err) => {
+        self.response_open = true;
+        self.response = None;
+        self.last_error = Some(err);
+        self.response_status = Some("Error".to_string());
+      }
+    }
+  }
+
+  pub(super) fn show_region_overlay(&mut self, ctx: &egui::Context) {
+    let Some(overlay) = &self.region_overlay else {
+      return;
+    };
+
+    let viewport = egui::ViewportBuilder::default()
+      .with_title(OVERLAY_TITLE)
+      .with_position([overlay.screen_x as f32, overlay.screen_y as f32])
+      .with_inner_size([overlay.width as f32, overlay.height as f32])
+      .with_decorations(false)
+      .with_resizable(false)
+      .with_transparent(true)
+      .with_taskbar(false)
+      .with_always_on_top();
+
+    ctx.show_viewport_immediate(
+      egui::ViewportId::from_hash_of("region_overlay"),
+      viewport,
+      |ctx, _class| {
+        let cancelled = ctx.input(|i| {
+          i.viewport().close_requested() || i.key_pressed(egui::Key::Escape)
+        });
+        if cancelled {
+          self.region_overlay = None;
+          ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+          return;
+        }
+        let Some(overlay) = &mut self.region_overlay else {
+          return;
+        };
+
+        #[cfg(target_os = "windows")]
+        {
+          use windows::Win32::Foundation::HWND;
+          if let Some(hwnd) = Self::find_window_by_title(OVERLAY_TITLE) {
+            if !overlay.hwnd_hooked {
+              Self::apply_windows_tool_window(HWND(hwnd.0));
+              Self::apply_windows_exclude_from_capture(HWND(hwnd.0), true);
+              overlay.hwnd_hooked = true;
+            }
+          }
+        }
+        if !overlay.focused {
+          ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+          overlay.focused = true;
+        }
+        ctx.set_cursor_icon(egui::CursorIcon::Crosshair);
+
+        let queue = overlay.queue;
+        let mut selected: Option<CaptureRegion> = None;
+        let frame = egui::Frame::none().fill(egui::Color32::from_black_alpha(90));
+        egui::CentralPanel::default().frame(frame).show(ctx, |ui| {
+          let area = ui.max_rect();
+          let drag = ui.interact(area, ui.id().with("region_drag"), egui::Sense::drag());
+          if drag.drag_started() {
+            overlay.drag_start = drag.interact_pointer_pos();
+          }
+          if drag.dragged() {
+            overlay.drag_current = drag.interact_pointer_pos();
+          }
+
+          let selection = overlay
+            .drag_start
+            .zip(overlay.drag_current)
+            .map(|(start, current)| egui::Rect::from_two_pos(start, current));
+          if let Some(rect) = selection {
+            let painter = ui.painter();
+            painter.rect_filled(rect, 0.0, egui::Color32::from_white_alpha(12));
+            painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.5, egui::Color32::WHITE));
+          } else {
+            ui.painter().text(
+              area.center(),
+              egui::Align2::CENTER_CENTER,
+              "Drag to select an area. Esc cancels.",
+              egui::FontId::proportional(18.0),
+              egui::Color32::WHITE,
+            );
+          }
+
+          if drag.drag_stopped() {
+            if let Some(rect) = selection {
+              selected = overlay.region(rect, area);
+            }
+            overlay.drag_start = None;
+            overlay.drag_current = None;
+          }
+        });
+
+        if let Some(region) = selected {
+          self.region_overlay = None;
+          ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+          self.config.last_region = Some(region);
+          self.save_config();
+          if queue {
+            self.queue_capture(CaptureTarget::Region(region), OVERLAY_SETTLE);
+          } else {
+            self.start_upload(Some(CaptureTarget::Region(region)), OVERLAY_SETTLE);
+          }
+        }
+      },
+    );
+  }
+}
+
+impl RegionOverlay {
+  /// Converts `rect` on the overlay covering `area` to the screen's units.
+  fn region(&self, rect: egui::Rect, area: egui::Rect) -> Option<CaptureRegion> {
+    let scale_x = self.width as f32 / area.width().max(1.0);
+    let scale_y = self.height as f32 / area.height().max(1.0);
+    let rect = rect.intersect(area);
+    let x = ((rect.min.x - area.min.x) * scale_x).round().max(0.0) as u32;
+    let y = ((rect.min.y - area.min.y) * scale_y).round().max(0.0) as u32;
+    let width = (rect.width() * scale_x).round().max(0.0) as u32;
+    let height = (rect.height() * scale_y).round().max(0.0) as u32;
+    if width < MIN_REGION || height < MIN_REGION {
+      return None;
+    }
+    Some(CaptureRegion {
+      screen_x: self.screen_x,
+      screen_y: self.screen_y,
+      x,
+      y,
+      width,
+      height,
+    })
+  }
+}
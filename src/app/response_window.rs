@@ -99,6 +99,10 @@ impl AppState {
 
     let ex_style = unsafe { GetWindowLongW(hwnd, GWL_EXSTYLE) };
     let mut new_style = ex_style | WS_EX_LAYERED.0 as i32 | WS_EX_TRANSPARENT.0 as i32;
+    // Clicks have to reach the question box while it is open.
+    if self.follow_up_active {
+      new_style &= !(WS_EX_TRANSPARENT.0 as i32);
+    }
     new_style |= WS_EX_TOOLWINDOW.0 as i32;
     new_style &= !(WS_EX_APPWINDOW.0 as i32);
     let alpha =
@@ -171,14 +175,14 @@ impl AppState {
 
         if self
           .response_last_pos
-          .map_or(true, |prev| (prev - anchor_pos).length_sq() > 0.5)
+          .is_none_or(|prev| (prev - anchor_pos).length_sq() > 0.5)
         {
           ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(anchor_pos));
           self.response_last_pos = Some(anchor_pos);
         }
         ctx.send_viewport_cmd(egui::ViewportCommand::Transparent(true));
         ctx.send_viewport_cmd(egui::ViewportCommand::Decorations(false));
-        ctx.send_viewport_cmd(egui::ViewportCommand::MousePassthrough(true));
+        ctx.send_viewport_cmd(egui::ViewportCommand::MousePassthrough(!self.follow_up_active));
         ctx.send_viewport_cmd(egui::ViewportCommand::Resizable(false));
         ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(if self.config.always_on_top {
           egui::WindowLevel::AlwaysOnTop
@@ -228,6 +232,12 @@ impl AppState {
                 let close_label = Self::hotkey_label_from_token(&self.config.hotkeys.close_response);
                 ui.label(format!("+ {}", close_label));
                 ui.label("Close response");
+                if self.capture_id.is_some() {
+                  let follow_up_label = Self::hotkey_label_from_token(&self.config.hotkeys.follow_up);
+                  ui.add_space(8.0);
+                  ui.label(format!("+ {}", follow_up_label));
+                  ui.label("Ask a follow-up");
+                }
               });
               ui.add_space(8.0);
               ui.separator();
@@ -297,6 +307,7 @@ impl AppState {
                         .syntax_theme_dark("base16-ocean.dark")
                         .syntax_theme_light("base16-ocean.light");
                       viewer.show(ui, &mut self.markdown_cache, &render_text);
+                      self.show_follow_ups(ui);
                     });
                   });
                 let max_offset = (output.content_size.y - output.inner_rect.height()).max(0.0);
@@ -306,6 +317,7 @@ impl AppState {
                 self.response_scroll_max = max_offset;
                 scroll_view_height = Some(output.inner_rect.height());
                 scroll_content_height = Some(output.content_size.y);
+                self.show_follow_up_input(ui);
               }
             });
             frame_rect = Some(response.response.rect);
@@ -1,6 +1,6 @@
 use eframe::egui;
 
-use crate::config::ColorConfig;
+use crate::config::{CaptureMode, ColorConfig, UploadFormat};
 
 use super::AppState;
 
@@ -12,7 +12,7 @@ impl AppState {
 
     let viewport = egui::ViewportBuilder::default()
       .with_title("Settings")
-      .with_inner_size([315.0, 475.0])
+      .with_inner_size([315.0, 920.0])
       .with_resizable(false)
       .with_transparent(true)
       .with_taskbar(false);
@@ -88,17 +88,54 @@ impl AppState {
                 .desired_width((ui.available_width() - 5.0).max(0.0)),
             );
             changed |= response.changed();
+            ui.add_space(6.0);
+            ui.label("Or link this device with the code and PIN from your account:");
+            ui.horizontal(|ui| {
+              let button_width = 48.0;
+              let field_width = (ui.available_width() - button_width - 60.0).max(0.0);
+              ui.add(
+                egui::TextEdit::singleline(&mut self.link_code)
+                  .hint_text("Code")
+                  .desired_width(field_width),
+              );
+              let pin = ui.add(
+                egui::TextEdit::singleline(&mut self.link_pin)
+                  .hint_text("PIN")
+                  .password(true)
+                  .char_limit(4)
+                  .desired_width(36.0),
+              );
+              let submitted = pin.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+              let link = ui.add_enabled(!self.linking, egui::Button::new("Link"));
+              if link.clicked() || submitted {
+                self.start_link();
+              }
+            });
+            if let Some(status) = &self.link_status {
+              ui.label(egui::RichText::new(status).small());
+            }
             ui.add_space(8.0);
             ui.label(egui::RichText::new("Model").strong());
             let mut model = self.config.model.clone();
+            let selected_text = self
+              .models
+              .iter()
+              .find(|info| info.name == model)
+              .map_or_else(|| model.clone(), |info| info.label.clone());
             egui::ComboBox::from_id_source("model_select")
-              .selected_text(model.clone())
+              .selected_text(selected_text)
               .width((ui.available_width() - 5.0).max(0.0))
               .show_ui(ui, |ui| {
-                ui.selectable_value(&mut model, "gpt-5.2".to_string(), "gpt-5.2 (best)");
-                ui.selectable_value(&mut model, "gpt-5-mini".to_string(), "gpt-5-mini");
-                ui.selectable_value(&mut model, "gpt-5-nano".to_string(), "gpt-5-nano (fast)");
-                ui.selectable_value(&mut model, "gpt-4o-mini".to_string(), "gpt-4o-mini (fastest)");
+                if self.models.is_empty() {
+                  let hint = if self.models_loading { "Loading..." } else { "No models available" };
+                  ui.label(hint);
+                }
+                for info in &self.models {
+                  ui.add_enabled_ui(info.available, |ui| {
+                    ui.selectable_value(&mut model, info.name.clone(), &info.label)
+                      .on_disabled_hover_text("Not included in your package");
+                  });
+                }
               });
             if model != self.config.model {
               self.config.model = model;
@@ -162,6 +199,113 @@ impl AppState {
                   changed = true;
                 }
                 ui.end_row();
+
+                ui.label("Low credit warning");
+                let slider_width = (ui.available_width() - 15.0).max(0.0);
+                changed |= ui
+                  .add_sized(
+                    [slider_width, 18.0],
+                    egui::Slider::new(&mut self.config.low_credit_warning, 0..=100)
+                      .show_value(true),
+                  )
+                  .on_hover_text("Highlight the credit count below this many credits")
+                  .changed();
+                ui.end_row();
+
+                ui.label("Local history");
+                let slider_width = (ui.available_width() - 15.0).max(0.0);
+                changed |= ui
+                  .add_sized(
+                    [slider_width, 18.0],
+                    egui::Slider::new(&mut self.config.local_history_limit, 0..=1000)
+                      .show_value(true),
+                  )
+                  .on_hover_text("Captures kept on this device for offline browsing; 0 keeps none")
+                  .changed();
+                ui.end_row();
+
+                ui.label("Upload format");
+                let upload = &mut self.config.upload;
+                egui::ComboBox::from_id_source("upload_format")
+                  .selected_text(upload.format.label())
+                  .show_ui(ui, |ui| {
+                    for format in UploadFormat::ALL {
+                      changed |= ui
+                        .selectable_value(&mut upload.format, format, format.label())
+                        .changed();
+                    }
+                  });
+                ui.end_row();
+
+                ui.label("JPEG quality");
+                let slider_width = (ui.available_width() - 15.0).max(0.0);
+                changed |= ui
+                  .add_enabled_ui(upload.format == UploadFormat::Jpeg, |ui| {
+                    ui.add_sized(
+                      [slider_width, 18.0],
+                      egui::Slider::new(&mut upload.quality, 1..=100).show_value(true),
+                    )
+                  })
+                  .inner
+                  .on_hover_text("PNG and WebP are lossless")
+                  .changed();
+                ui.end_row();
+
+                ui.label("Max image size");
+                let slider_width = (ui.available_width() - 15.0).max(0.0);
+                changed |= ui
+                  .add_sized(
+                    [slider_width, 18.0],
+                    egui::Slider::new(&mut upload.max_side, 0..=4096)
+                      .suffix(" px")
+                      .show_value(true),
+                  )
+                  .on_hover_text("Longest edge of the uploaded capture; 0 uploads full resolution")
+                  .changed();
+                ui.end_row();
+
+                ui.label("Capture");
+                egui::ComboBox::from_id_source("capture_mode")
+                  .selected_text(self.config.capture_mode.label())
+                  .show_ui(ui, |ui| {
+                    for mode in CaptureMode::ALL {
+                      changed |= ui
+                        .selectable_value(&mut self.config.capture_mode, mode, mode.label())
+                        .changed();
+                    }
+                  })
+                  .response
+                  .on_hover_text("What the screenshot hotkey captures");
+                ui.end_row();
+
+                ui.label("Monitor");
+                let monitor_label = |info: &screenshots::display_info::DisplayInfo| {
+                  let primary = if info.is_primary { ", primary" } else { "" };
+                  format!("{}x{} at {}, {}{primary}", info.width, info.height, info.x, info.y)
+                };
+                let selected_text = match self.config.monitor {
+                  None => "Under the main bar".to_string(),
+                  Some(id) => self
+                    .monitors
+                    .iter()
+                    .find(|info| info.id == id)
+                    .map_or_else(|| "Disconnected".to_string(), monitor_label),
+                };
+                egui::ComboBox::from_id_source("monitor_select")
+                  .selected_text(selected_text)
+                  .show_ui(ui, |ui| {
+                    changed |= ui
+                      .selectable_value(&mut self.config.monitor, None, "Under the main bar")
+                      .changed();
+                    for info in &self.monitors {
+                      changed |= ui
+                        .selectable_value(&mut self.config.monitor, Some(info.id), monitor_label(info))
+                        .changed();
+                    }
+                  })
+                  .response
+                  .on_hover_text("Full screen and region captures use this monitor");
+                ui.end_row();
               });
             ui.add_space(6.0);
             changed |= ui
@@ -170,6 +314,25 @@ impl AppState {
             changed |= ui
               .checkbox(&mut self.config.always_on_top, "Always on top")
               .changed();
+            let sync = ui
+              .checkbox(&mut self.config.sync_settings, "Sync settings with account")
+              .on_hover_text("API key and window position stay on this device");
+            if sync.changed() {
+              changed = true;
+              if self.config.sync_settings {
+                self.settings_snapshot = None;
+                self.fetch_remote_settings();
+              }
+            }
+            if let Some(account) = &mut self.account {
+              let store = ui
+                .checkbox(&mut account.user.store_images, "Keep screenshots on the server")
+                .on_hover_text("Needed for images in the account history; turning it off deletes them");
+              if store.changed() {
+                let store_images = account.user.store_images;
+                self.set_store_images(store_images);
+              }
+            }
           });
 
           ui.add_space(10.0);
@@ -280,6 +443,86 @@ impl AppState {
                       });
                       ui.end_row();
 
+                      ui.label("Region");
+                      ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing = egui::vec2(4.0, 0.0);
+                        self.modifiers_row(ui, 12.0);
+                        ui.label("+");
+                        let label = if self.hotkey_capture == Some(super::HotkeyAction::CaptureRegion) {
+                          "Press key...".to_string()
+                        } else {
+                          Self::hotkey_label_from_token(&self.config.hotkeys.capture_region)
+                        };
+                        if self.text_badge(ui, &label, 3.0, 2.0, true).clicked() {
+                          self.hotkey_capture = Some(super::HotkeyAction::CaptureRegion);
+                        }
+                      });
+                      ui.end_row();
+
+                      ui.label("Window");
+                      ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing = egui::vec2(4.0, 0.0);
+                        self.modifiers_row(ui, 12.0);
+                        ui.label("+");
+                        let label = if self.hotkey_capture == Some(super::HotkeyAction::CaptureWindow) {
+                          "Press key...".to_string()
+                        } else {
+                          Self::hotkey_label_from_token(&self.config.hotkeys.capture_window)
+                        };
+                        if self.text_badge(ui, &label, 3.0, 2.0, true).clicked() {
+                          self.hotkey_capture = Some(super::HotkeyAction::CaptureWindow);
+                        }
+                      });
+                      ui.end_row();
+
+                      ui.label("Last region");
+                      ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing = egui::vec2(4.0, 0.0);
+                        self.modifiers_row(ui, 12.0);
+                        ui.label("+");
+                        let label = if self.hotkey_capture == Some(super::HotkeyAction::CaptureLastRegion) {
+                          "Press key...".to_string()
+                        } else {
+                          Self::hotkey_label_from_token(&self.config.hotkeys.capture_last_region)
+                        };
+                        if self.text_badge(ui, &label, 3.0, 2.0, true).clicked() {
+                          self.hotkey_capture = Some(super::HotkeyAction::CaptureLastRegion);
+                        }
+                      });
+                      ui.end_row();
+
+                      ui.label("Queue capture");
+                      ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing = egui::vec2(4.0, 0.0);
+                        self.modifiers_row(ui, 12.0);
+                        ui.label("+");
+                        let label = if self.hotkey_capture == Some(super::HotkeyAction::QueueCapture) {
+                          "Press key...".to_string()
+                        } else {
+                          Self::hotkey_label_from_token(&self.config.hotkeys.queue_capture)
+                        };
+                        if self.text_badge(ui, &label, 3.0, 2.0, true).clicked() {
+                          self.hotkey_capture = Some(super::HotkeyAction::QueueCapture);
+                        }
+                      });
+                      ui.end_row();
+
+                      ui.label("Follow-up");
+                      ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing = egui::vec2(4.0, 0.0);
+                        self.modifiers_row(ui, 12.0);
+                        ui.label("+");
+                        let label = if self.hotkey_capture == Some(super::HotkeyAction::FollowUp) {
+                          "Press key...".to_string()
+                        } else {
+                          Self::hotkey_label_from_token(&self.config.hotkeys.follow_up)
+                        };
+                        if self.text_badge(ui, &label, 3.0, 2.0, true).clicked() {
+                          self.hotkey_capture = Some(super::HotkeyAction::FollowUp);
+                        }
+                      });
+                      ui.end_row();
+
                       ui.label("Close resp.");
                       ui.horizontal(|ui| {
                         ui.spacing_mut().item_spacing = egui::vec2(4.0, 0.0);
@@ -296,6 +539,38 @@ impl AppState {
                       });
                       ui.end_row();
 
+                      ui.label("Older answer");
+                      ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing = egui::vec2(4.0, 0.0);
+                        self.modifiers_row(ui, 12.0);
+                        ui.label("+");
+                        let label = if self.hotkey_capture == Some(super::HotkeyAction::HistoryBack) {
+                          "Press key...".to_string()
+                        } else {
+                          Self::hotkey_label_from_token(&self.config.hotkeys.history_back)
+                        };
+                        if self.text_badge(ui, &label, 3.0, 2.0, true).clicked() {
+                          self.hotkey_capture = Some(super::HotkeyAction::HistoryBack);
+                        }
+                      });
+                      ui.end_row();
+
+                      ui.label("Newer answer");
+                      ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing = egui::vec2(4.0, 0.0);
+                        self.modifiers_row(ui, 12.0);
+                        ui.label("+");
+                        let label = if self.hotkey_capture == Some(super::HotkeyAction::HistoryForward) {
+                          "Press key...".to_string()
+                        } else {
+                          Self::hotkey_label_from_token(&self.config.hotkeys.history_forward)
+                        };
+                        if self.text_badge(ui, &label, 3.0, 2.0, true).clicked() {
+                          self.hotkey_capture = Some(super::HotkeyAction::HistoryForward);
+                        }
+                      });
+                      ui.end_row();
+
                       ui.label("Show/Hide");
                       ui.horizontal(|ui| {
                         ui.spacing_mut().item_spacing = egui::vec2(4.0, 0.0);
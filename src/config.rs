@@ -1,9 +1,21 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use eframe::egui;
 
+/// Fields that stay on this machine when settings are synced with the server.
+const LOCAL_ONLY: [&str; 7] = [
+  "test",
+  "main_position",
+  "api_key",
+  "sync_settings",
+  "settings_updated_at",
+  "last_region",
+  "monitor",
+];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct AppConfig {
@@ -21,6 +33,68 @@ pub struct AppConfig {
   pub hotkeys: HotkeyConfig,
   pub theme: String,
   pub model: String,
+  pub sync_settings: bool,
+  /// When the synced fields last changed, here or on another device.
+  pub settings_updated_at: Option<DateTime<Utc>>,
+  /// The main bar warns once the remaining credits drop below this.
+  pub low_credit_warning: i64,
+  /// Captures kept in the local history; 0 keeps none.
+  pub local_history_limit: usize,
+  pub upload: UploadConfig,
+  /// What the screenshot hotkey captures.
+  pub capture_mode: CaptureMode,
+  /// The last region picked on the overlay, for `CaptureMode::LastRegion`.
+  pub last_region: Option<CaptureRegion>,
+  /// Display id of the monitor to capture; `None` follows the main bar.
+  pub monitor: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureMode {
+  /// The whole screen the main bar is on.
+  Screen,
+  /// A rectangle dragged on an overlay.
+  Region,
+  /// The active window.
+  Window,
+  /// `last_region` again, or the overlay when there is none yet.
+  LastRegion,
+  /// Every monitor, stitched side by side.
+  AllScreens,
+}
+
+impl CaptureMode {
+  pub const ALL: [Self; 5] = [
+    Self::Screen,
+    Self::Region,
+    Self::Window,
+    Self::LastRegion,
+    Self::AllScreens,
+  ];
+
+  pub fn label(self) -> &'static str {
+    match self {
+      Self::Screen => "Full screen",
+      Self::Region => "Region",
+      Self::Window if cfg!(target_os = "windows") => "Active window",
+      Self::Window => "Active window (Windows only)",
+      Self::LastRegion => "Last region",
+      Self::AllScreens => "All monitors",
+    }
+  }
+}
+
+/// A rectangle on the screen whose top-left corner is at `screen_x, screen_y`, in that
+/// screen's coordinates as `screenshots` reports them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureRegion {
+  pub screen_x: i32,
+  pub screen_y: i32,
+  pub x: u32,
+  pub y: u32,
+  pub width: u32,
+  pub height: u32,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -44,6 +118,57 @@ pub struct HotkeyConfig {
   pub screenshot: String,
   pub close_response: String,
   pub quit: String,
+  pub history_back: String,
+  pub history_forward: String,
+  pub capture_region: String,
+  pub capture_window: String,
+  pub capture_last_region: String,
+  /// Captures in the current mode and holds the image for the next request instead of sending.
+  pub queue_capture: String,
+  /// Opens the question box under the answer on screen.
+  pub follow_up: String,
+}
+
+/// How captures are encoded before they are uploaded.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UploadConfig {
+  pub format: UploadFormat,
+  /// JPEG quality, 1-100; PNG and WebP are lossless.
+  pub quality: u8,
+  /// Captures are scaled down so neither edge exceeds this, or this per screen along the
+  /// long edge of stitched captures; 0 keeps the full resolution.
+  pub max_side: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadFormat {
+  Png,
+  Jpeg,
+  Webp,
+}
+
+impl UploadFormat {
+  pub const ALL: [Self; 3] = [Self::Png, Self::Jpeg, Self::Webp];
+
+  pub fn label(self) -> &'static str {
+    match self {
+      Self::Png => "PNG",
+      Self::Jpeg => "JPEG",
+      Self::Webp => "WebP",
+    }
+  }
+}
+
+impl Default for UploadConfig {
+  fn default() -> Self {
+    Self {
+      format: UploadFormat::Png,
+      quality: 85,
+      max_side: 2048,
+    }
+  }
 }
 
 impl Default for HotkeyConfig {
@@ -53,6 +178,13 @@ impl Default for HotkeyConfig {
       screenshot: "Q".to_string(),
       close_response: "X".to_string(),
       quit: "P".to_string(),
+      history_back: "J".to_string(),
+      history_forward: "K".to_string(),
+      capture_region: "1".to_string(),
+      capture_window: "2".to_string(),
+      capture_last_region: "3".to_string(),
+      queue_capture: "4".to_string(),
+      follow_up: "U".to_string(),
     }
   }
 }
@@ -161,6 +293,14 @@ impl Default for AppConfig {
       hotkeys: HotkeyConfig::default(),
       theme: "dark".to_string(),
       model: "gpt-5-mini".to_string(),
+      sync_settings: true,
+      settings_updated_at: None,
+      low_credit_warning: 5,
+      local_history_limit: 200,
+      upload: UploadConfig::default(),
+      capture_mode: CaptureMode::Screen,
+      last_region: None,
+      monitor: None,
     }
   }
 }
@@ -186,3 +326,30 @@ pub fn write_config(path: &Path, config: &AppConfig) -> Result<(), String> {
   let contents = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
   fs::write(path, contents).map_err(|e| e.to_string())
 }
+
+/// The fields of `config` that are shared through `GET/PUT /settings`.
+pub fn synced_settings(config: &AppConfig) -> serde_json::Value {
+  let mut value = serde_json::to_value(config).unwrap_or_default();
+  if let Some(object) = value.as_object_mut() {
+    for key in LOCAL_ONLY {
+      object.remove(key);
+    }
+  }
+  value
+}
+
+/// Overlays remote settings on `config`, keeping local-only fields and ignoring unknown ones.
+pub fn apply_synced_settings(config: &AppConfig, remote: &serde_json::Value) -> AppConfig {
+  let Some(remote) = remote.as_object() else {
+    return config.clone();
+  };
+  let mut merged = serde_json::to_value(config).unwrap_or_default();
+  if let Some(object) = merged.as_object_mut() {
+    for (key, value) in remote {
+      if !LOCAL_ONLY.contains(&key.as_str()) && object.contains_key(key) {
+        object.insert(key.clone(), value.clone());
+      }
+    }
+  }
+  serde_json::from_value(merged).unwrap_or_else(|_| config.clone())
+}
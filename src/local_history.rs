@@ -0,0 +1,147 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+const FILE_NAME: &str = "history.jsonl";
+const THUMBNAIL_DIR: &str = "history";
+
+/// One finished capture as stored on this machine.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalEntry {
+  pub id: String,
+  pub created_at: DateTime<Utc>,
+  pub model: String,
+  #[serde(default)]
+  pub prompt: String,
+  pub text: String,
+  pub code: String,
+  /// The server's id for the capture, to ask follow-up questions on.
+  #[serde(default)]
+  pub capture_id: Option<String>,
+  /// File name in the thumbnail directory.
+  pub thumbnail: Option<String>,
+}
+
+/// Past captures kept next to `config.json`: one JSON line per entry in `history.jsonl` and
+/// a PNG thumbnail per entry in `history/`. Works without a connection to the server.
+pub struct LocalHistory {
+  file: PathBuf,
+  thumbnail_dir: PathBuf,
+  /// Oldest first, like the file.
+  entries: Vec<LocalEntry>,
+}
+
+impl LocalHistory {
+  /// Reads the store in `dir`; unreadable lines are skipped.
+  pub fn open(dir: &Path) -> Self {
+    let file = dir.join(FILE_NAME);
+    let entries = fs::read_to_string(&file)
+      .map(|contents| {
+        contents
+          .lines()
+          .filter_map(|line| serde_json::from_str(line).ok())
+          .collect()
+      })
+      .unwrap_or_default();
+    Self {
+      file,
+      thumbnail_dir: dir.join(THUMBNAIL_DIR),
+      entries,
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// The `index`-th newest entry.
+  pub fn get(&self, index: usize) -> Option<&LocalEntry> {
+    self
+      .entries
+      .len()
+      .checked_sub(index + 1)
+      .and_then(|position| self.entries.get(position))
+  }
+
+  /// Newest-first indexes (as used by `get`) of the entries matching every word of `query`
+  /// in their prompt, answer or model.
+  pub fn search(&self, query: &str) -> Vec<usize> {
+    let words: Vec<String> = query
+      .split_whitespace()
+      .map(str::to_lowercase)
+      .collect();
+    (0..self.entries.len())
+      .filter(|&index| {
+        let Some(entry) = self.get(index) else {
+          return false;
+        };
+        let haystack = format!(
+          "{}\n{}\n{}\n{}",
+          entry.prompt, entry.text, entry.code, entry.model
+        )
+        .to_lowercase();
+        words.iter().all(|word| haystack.contains(word.as_str()))
+      })
+      .collect()
+  }
+
+  pub fn thumbnail_path(&self, entry: &LocalEntry) -> Option<PathBuf> {
+    entry
+      .thumbnail
+      .as_ref()
+      .map(|name| self.thumbnail_dir.join(name))
+  }
+
+  /// Stores `entry` with its thumbnail and drops the oldest entries beyond `limit`.
+  pub fn add(
+    &mut self,
+    mut entry: LocalEntry,
+    thumbnail_png: Option<&[u8]>,
+    limit: usize,
+  ) -> Result<(), String> {
+    if let Some(png) = thumbnail_png {
+      fs::create_dir_all(&self.thumbnail_dir).map_err(|e| e.to_string())?;
+      let name = format!("{}.png", entry.id);
+      fs::write(self.thumbnail_dir.join(&name), png).map_err(|e| e.to_string())?;
+      entry.thumbnail = Some(name);
+    }
+    let line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+    let mut file = fs::OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&self.file)
+      .map_err(|e| e.to_string())?;
+    writeln!(file, "{line}").map_err(|e| e.to_string())?;
+    self.entries.push(entry);
+    self.prune(limit)
+  }
+
+  fn prune(&mut self, limit: usize) -> Result<(), String> {
+    if self.entries.len() <= limit {
+      return Ok(());
+    }
+    let excess = self.entries.len() - limit;
+    let mut contents = String::new();
+    for entry in &self.entries[excess..] {
+      contents.push_str(&serde_json::to_string(entry).map_err(|e| e.to_string())?);
+      contents.push('\n');
+    }
+    // Swapped in whole, so a crash mid-write cannot leave a truncated history behind.
+    let temp = self.file.with_extension("jsonl.tmp");
+    fs::write(&temp, contents).map_err(|e| e.to_string())?;
+    fs::rename(&temp, &self.file).map_err(|e| e.to_string())?;
+    for entry in self.entries.drain(..excess) {
+      if let Some(name) = entry.thumbnail {
+        let _ = fs::remove_file(self.thumbnail_dir.join(name));
+      }
+    }
+    Ok(())
+  }
+}
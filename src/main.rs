@@ -1,6 +1,7 @@
 mod api;
 mod app;
 mod config;
+mod local_history;
 mod ui;
 
 fn main() -> eframe::Result<()> {